@@ -4,7 +4,7 @@ pub mod winit;
 use std::time::Duration;
 
 use smithay::{
-    backend::allocator::dmabuf::Dmabuf, desktop::utils::surface_primary_scanout_output, reexports::{
+    backend::{allocator::dmabuf::Dmabuf, renderer::gles::GlesRenderer}, desktop::utils::surface_primary_scanout_output, output::Output, reexports::{
         calloop::LoopHandle,
         wayland_server::{protocol::wl_surface::WlSurface, DisplayHandle},
     },
@@ -105,6 +105,22 @@ impl Backend {
         }
     }
 
+    /// Run `f` with the renderer of the primary gpu.
+    pub fn with_renderer<T>(&mut self, f: impl FnOnce(&mut GlesRenderer) -> T) -> Option<T> {
+        match self {
+            Backend::Tty(tty) => tty.with_renderer(f),
+            Backend::Winit(winit) => Some(winit.with_renderer(f)),
+        }
+    }
+
+    /// Something on screen changed and needs to be drawn.
+    pub fn queue_redraw(&mut self, loop_handle: &LoopHandle<'_, GlobalData>) {
+        match self {
+            Backend::Tty(tty) => tty.queue_redraw(loop_handle),
+            Backend::Winit(winit) => winit.queue_redraw(),
+        }
+    }
+
     pub fn change_vt(&mut self, vt: i32) {
         match self {
             Backend::Tty(tty) => tty.change_vt(vt),
@@ -116,6 +132,16 @@ impl Backend {
 }
 
 impl GlobalData {
+    pub fn queue_redraw(&mut self) {
+        self.backend.queue_redraw(&self.loop_handle);
+    }
+
+    /// Answer screen captures that wait for `output` to be drawn.
+    pub fn post_render(&mut self, output: &Output, damaged: bool) {
+        self.process_screencopies(output, damaged);
+        self.process_image_captures();
+    }
+
     pub fn post_repaint(
         &mut self,
         time: impl Into<Duration>,
@@ -140,8 +140,20 @@ pub struct GpuDevice {
     non_desktop_connectors: HashSet<(connector::Handle, crtc::Handle)>,
 }
 
+/// Where an output is in its frame cycle.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RedrawState {
+    // nothing changed since the last frame
+    Idle,
+    // a redraw is scheduled in an idle callback
+    Queued,
+    // a frame is queued, `redraw_needed` is set when something changes meanwhile
+    WaitingForVBlank { redraw_needed: bool },
+}
+
 pub struct Surface {
     output: Output,
+    redraw_state: RedrawState,
     #[allow(dead_code)]
     device_id: DrmNode,
     render_node: DrmNode,
@@ -195,42 +207,15 @@ impl Tty {
                     if data.backend.tty().libinput.resume().is_err() {
                         error!("error resuming libinput session");
                     };
-                    for (node, device) in data
-                        .backend
-                        .tty()
-                        .devices
-                        .iter_mut()
-                        .map(|(node, device)| (*node, device)) 
-                    {
+                    for device in data.backend.tty().devices.values_mut() {
                         device.drm.activate(false).expect("failed to activate drm backend");
-                        data.loop_handle.insert_idle(move |data| {
-
-                            let device: &mut GpuDevice = if let Some(device) = data.backend.tty().devices.get_mut(&node) {
-                                device
-                            } else {
-                                warn!("not change because of unknown device");
-                                return;
-                            };
-
-                            let crtcs: Vec<_> = device.surfaces.keys().copied().collect();
-                            for crtc in crtcs {
-                                data.backend.tty().render_output(
-                                    node,
-                                    crtc,
-                                    data.clock.now(),
-                                    &mut data.render_manager,
-                                    &data.output_manager,
-                                    &data.workspace_manager,
-                                    &data.window_manager,
-                                    &mut data.cursor_manager,
-                                    &data.input_manager,
-                                    &mut data.animation_manager,
-                                    &data.clock,
-                                    &data.loop_handle,
-                                );
-                            }
-                        });
+
+                        // vblanks of frames queued before the pause never arrive
+                        for surface in device.surfaces.values_mut() {
+                            surface.redraw_state = RedrawState::Idle;
+                        }
                     }
+                    data.queue_redraw();
                 }
                 SessionEvent::PauseSession => {
                     info!("Session paused");
@@ -433,6 +418,9 @@ impl Tty {
                             &data.clock,
                             &data.loop_handle,
                         );
+
+                        // the frame is on screen, let clients draw the next one
+                        data.post_repaint(data.clock.now());
                     }
                     DrmEvent::Error(error) => warn!("DRM Vblank error: {error}"),
                 };
@@ -675,28 +663,38 @@ impl Tty {
             }
         };
 
-        if schedule_render {
-            let next_frame_target = clock + frame_duration;
+        // only draw again if something changed while waiting
+        let redraw_needed = matches!(surface.redraw_state, RedrawState::WaitingForVBlank { redraw_needed: true });
+        if schedule_render && redraw_needed {
+            surface.redraw_state = RedrawState::Queued;
 
-            loop_handle.insert_idle(move |data| {
-                
-                data.backend.tty().render_output(
-                    node,
-                    crtc,
-                    next_frame_target,
-                    &mut data.render_manager,
-                    &data.output_manager,
-                    &data.workspace_manager,
-                    &data.window_manager,
-                    &mut data.cursor_manager,
-                    &data.input_manager,
-                    &mut data.animation_manager,
-                    &data.clock,
-                    &data.loop_handle,
-                );
+            let next_frame_target = clock + frame_duration;
+            loop_handle.insert_idle(move |data| redraw(data, node, crtc, next_frame_target));
+        } else {
+            surface.redraw_state = RedrawState::Idle;
+        }
+    }
 
-                data.post_repaint(next_frame_target);
-            });
+    /// Mark every output dirty, drawing idle ones as soon as the event loop is idle.
+    pub fn queue_redraw(&mut self, loop_handle: &LoopHandle<'_, GlobalData>) {
+        for (node, device) in self.devices.iter_mut() {
+            for (crtc, surface) in device.surfaces.iter_mut() {
+                match surface.redraw_state {
+                    RedrawState::Idle => {
+                        surface.redraw_state = RedrawState::Queued;
+
+                        let (node, crtc) = (*node, *crtc);
+                        loop_handle.insert_idle(move |data| {
+                            let frame_target = data.clock.now();
+                            redraw(data, node, crtc, frame_target);
+                        });
+                    }
+                    RedrawState::Queued => {}
+                    RedrawState::WaitingForVBlank { .. } => {
+                        surface.redraw_state = RedrawState::WaitingForVBlank { redraw_needed: true };
+                    }
+                }
+            }
         }
     }
 
@@ -930,6 +928,7 @@ impl Tty {
 
             let surface = Surface {
                 output: output_manager.current_output().clone(),
+                redraw_state: RedrawState::Idle,
                 device_id: node,
                 render_node: device.render_node,
                 compositor,
@@ -938,23 +937,8 @@ impl Tty {
 
             device.surfaces.insert(crtc, surface);
 
-            // kick-off rendering 
-            loop_handle.insert_idle(move |data| {
-                data.backend.tty().render_output(
-                    node,
-                    crtc,
-                    data.clock.now(),
-                    &mut data.render_manager,
-                    &data.output_manager,
-                    &data.workspace_manager,
-                    &data.window_manager,
-                    &mut data.cursor_manager,
-                    &data.input_manager,
-                    &mut data.animation_manager,
-                    &data.clock,
-                    &data.loop_handle,
-                );
-            });
+            // kick-off rendering
+            self.queue_redraw(loop_handle);
         }
     }
 
@@ -992,6 +976,7 @@ impl Tty {
         }
     }
 
+    /// Returns whether the frame had damage.
     pub fn render_output(
         &mut self,
         node: DrmNode,
@@ -1008,20 +993,20 @@ impl Tty {
 
         clock: &Clock<Monotonic>,
         loop_handle: &LoopHandle<'_, GlobalData>,
-    ) {
+    ) -> bool {
         let _span = tracy_client::span!("tty_render");
 
         let device: &mut GpuDevice = if let Some(device) = self.devices.get_mut(&node) {
             device
         } else {
             warn!("not change because of unknown device");
-            return;
+            return false;
         };
 
         let surface = if let Some(surface) = device.surfaces.get_mut(&crtc) {
             surface
         } else {
-            return;
+            return false;
         };
 
         let mut renderer = self
@@ -1029,6 +1014,9 @@ impl Tty {
             .single_renderer(&surface.render_node)
             .unwrap();
 
+        // animations follow the presentation time of this frame
+        animation_manager.advance(frame_target.into());
+
         let elements = render_manager.get_render_elements(
             &mut renderer,
             output_manager,
@@ -1037,6 +1025,7 @@ impl Tty {
             cursor_manager,
             input_manager,
             animation_manager,
+            true,
         );
 
         match surface
@@ -1079,9 +1068,12 @@ impl Tty {
                         .queue_frame(Some(output_presentation_feedback))
                         .map_err(Into::<SwapBuffersError>::into)
                     {
-                        Ok(_) => {}
+                        Ok(_) => {
+                            surface.redraw_state = RedrawState::WaitingForVBlank { redraw_needed: false };
+                        }
                         Err(err) => {
                             warn!("error queue frame: {:?}", err);
+                            surface.redraw_state = RedrawState::Idle;
                             match err {
                                 SwapBuffersError::AlreadySwapped => {
                                     panic!("AlreadySwapped: {}", err);
@@ -1101,13 +1093,16 @@ impl Tty {
                         }
                     }
                 } else {
+                    // nothing to show, but clients still wait for their frame callbacks
+                    surface.redraw_state = RedrawState::Idle;
+
                     let output_refresh = output_manager.current_refresh();
                     let next_frame_target = frame_target+Duration::from_millis(1_000_000/output_refresh as u64);
                     
                     let reschedule_timeout =
                         Duration::from(next_frame_target).saturating_sub(clock.now().into());
                     debug!(
-                        "reschedule repaint timer with delay {:?} on {:?}",
+                        "estimated vblank timer with delay {:?} on {:?}",
                         reschedule_timeout,
                         crtc,
                     );
@@ -1115,27 +1110,17 @@ impl Tty {
 
                     loop_handle
                         .insert_source(timer, move |_, _, data| {
-                            data.backend.tty().render_output(
-                                node,
-                                crtc,
-                                next_frame_target,
-                                &mut data.render_manager,
-                                &data.output_manager,
-                                &data.workspace_manager,
-                                &data.window_manager,
-                                &mut data.cursor_manager,
-                                &data.input_manager,
-                                &mut data.animation_manager,
-                                &data.clock,
-                                &data.loop_handle,
-                            );
+                            data.post_repaint(next_frame_target);
                             TimeoutAction::Drop
                         })
                         .expect("failed to schedule frame timer");
                 }
+
+                rendered
             }
             Err(err) => {
                 warn!("error rendering frame: {:?}", err);
+                surface.redraw_state = RedrawState::Idle;
                 match err {
                     SwapBuffersError::AlreadySwapped => {}
                     SwapBuffersError::TemporaryFailure(err) => {
@@ -1143,13 +1128,15 @@ impl Tty {
                             err.downcast_ref::<DrmError>(),
                             Some(&DrmError::DeviceInactive)
                         ) {
-                            return;
+                            return false;
                         }
                     }
                     SwapBuffersError::ContextLost(err) => {
                         panic!("Rendering loop lost: {}", err)
                     }
                 }
+
+                false
             }
         }
     }
@@ -1174,6 +1161,16 @@ impl Tty {
         }
     }
 
+    pub fn with_renderer<T>(&mut self, f: impl FnOnce(&mut GlesRenderer) -> T) -> Option<T> {
+        match self.gpu_manager.single_renderer(&self.primary_render_node) {
+            Ok(mut renderer) => Some(f(renderer.as_mut())),
+            Err(err) => {
+                warn!("error creating renderer for primary GPU: {:?}", err);
+                None
+            }
+        }
+    }
+
     pub fn early_import(&mut self, surface: &WlSurface) {
         if let Err(err) = self.gpu_manager.early_import(
             // We always render on the primary GPU.
@@ -1307,4 +1304,24 @@ pub fn update_primary_scanout_output(
             );
         });
     }
-}
\ No newline at end of file
+}
+
+fn redraw(data: &mut GlobalData, node: DrmNode, crtc: crtc::Handle, frame_target: Time<Monotonic>) {
+    let damaged = data.backend.tty().render_output(
+        node,
+        crtc,
+        frame_target,
+        &mut data.render_manager,
+        &data.output_manager,
+        &data.workspace_manager,
+        &data.window_manager,
+        &mut data.cursor_manager,
+        &data.input_manager,
+        &mut data.animation_manager,
+        &data.clock,
+        &data.loop_handle,
+    );
+
+    let output = data.output_manager.current_output().clone();
+    data.post_render(&output, damaged);
+}
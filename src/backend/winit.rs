@@ -12,7 +12,7 @@ use smithay::{
     },
     output::{Mode as OutputMode, Subpixel},
     reexports::{calloop::LoopHandle, wayland_server::DisplayHandle},
-    utils::{Rectangle, Transform},
+    utils::Transform,
     wayland::dmabuf::DmabufFeedbackBuilder,
 };
 
@@ -26,6 +26,8 @@ use crate::{
 #[derive(Debug)]
 pub struct Winit {
     pub backend: WinitGraphicsBackend<GlesRenderer>,
+    // created with the output in init
+    damage_tracker: Option<OutputDamageTracker>,
 }
 impl Winit {
     pub fn new(loop_handle: &LoopHandle<'_, GlobalData>) -> anyhow::Result<Self> {
@@ -47,19 +49,14 @@ impl Winit {
                         );
 
                         data.update_output_working_size();
+                        data.queue_redraw();
                     }
                     WinitEvent::Input(event) => {
                         data.process_input_event(event);
                     }
                     WinitEvent::Redraw => {
-                        let size = data.backend.winit().backend.window_size();
-                        let damage = Rectangle::from_size(size);
-
-                        let damage_traker = &mut OutputDamageTracker::from_output(
-                            data.output_manager.current_output(),
-                        );
-                        data.backend.winit().render_output(
-                            damage_traker,
+                        data.animation_manager.advance(data.clock.now().into());
+                        let damaged = data.backend.winit().render_output(
                             &mut data.render_manager,
                             &data.output_manager,
                             &data.workspace_manager,
@@ -69,12 +66,8 @@ impl Winit {
                             &mut data.animation_manager,
                         );
 
-                        match data.backend.winit().backend.submit(Some(&[damage])) {
-                            Ok(_) => {}
-                            Err(err) => {
-                                warn!("Winit: Failed to submit frame: {:?}", err);
-                            }
-                        }
+                        let output = data.output_manager.current_output().clone();
+                        data.post_render(&output, damaged);
 
                         // For each of the windows send the frame callbacks to tell them to draw next frame.
                         data.workspace_manager.windows().for_each(|window| {
@@ -89,9 +82,6 @@ impl Winit {
                         // Refresh space nuonuo_state and handle certain events like enter/leave for outputs/windows
                         data.workspace_manager.refresh();
                         data.popups.cleanup();
-
-                        // Ask for redraw to schedule new frame.
-                        data.backend.winit().backend.window().request_redraw();
                     }
                     WinitEvent::CloseRequested => {}
                     _ => (),
@@ -99,7 +89,7 @@ impl Winit {
             })
             .unwrap();
 
-        Ok(Self { backend })
+        Ok(Self { backend, damage_tracker: None })
     }
 
     pub fn init(
@@ -134,6 +124,9 @@ impl Winit {
         );
         output_manager.set_preferred(mode);
 
+        // kept across frames, so only what changed is drawn again
+        self.damage_tracker = Some(OutputDamageTracker::from_output(output_manager.current_output()));
+
         // initial dmabuf
         #[cfg(feature = "egl")]
         if self.get_renderer().bind_wl_display(display_handle).is_ok() {
@@ -181,9 +174,9 @@ impl Winit {
         render_manager.compile_shaders(self.get_renderer());
     }
 
+    /// Returns whether the frame had damage.
     pub fn render_output(
         &mut self,
-        damage_tracker: &mut OutputDamageTracker,
         render_manager: &mut RenderManager,
         output_manager: &OutputManager,
         workspace_manager: &WorkspaceManager,
@@ -191,37 +184,73 @@ impl Winit {
         cursor_manager: &mut CursorManager,
         input_manager: &InputManager,
         animation_manager: &mut AnimationManager
-    ) {
+    ) -> bool {
         let _span = tracy_client::span!("winit_render");
 
-        if let Ok((renderer, mut framebuffer)) = self.backend.bind() {
-            let elements = render_manager.get_render_elements(
-                renderer,
-                output_manager,
-                workspace_manager,
-                window_manager,
-                cursor_manager,
-                input_manager,
-                animation_manager,
-            );
-
-            let _ = damage_tracker.render_output(
-                renderer,
-                &mut framebuffer,
-                0,
-                &elements,
-                Color32F::from([0.0; 4]),
-            );
-        } else {
-            warn!("Winit: Failed to get renderer & framebuffer");
-            return;
+        let Some(damage_tracker) = self.damage_tracker.as_mut() else {
+            warn!("Winit: render before the output was initialized");
+            return false;
+        };
+
+        let age = self.backend.buffer_age().unwrap_or(0);
+        let damage = match self.backend.bind() {
+            Ok((renderer, mut framebuffer)) => {
+                let elements = render_manager.get_render_elements(
+                    renderer,
+                    output_manager,
+                    workspace_manager,
+                    window_manager,
+                    cursor_manager,
+                    input_manager,
+                    animation_manager,
+                    true,
+                );
+
+                match damage_tracker.render_output(
+                    renderer,
+                    &mut framebuffer,
+                    age,
+                    &elements,
+                    Color32F::from([0.0; 4]),
+                ) {
+                    Ok(result) => result.damage.cloned(),
+                    Err(err) => {
+                        warn!("Winit: Failed to render output: {:?}", err);
+                        return false;
+                    }
+                }
+            }
+            Err(err) => {
+                warn!("Winit: Failed to get renderer & framebuffer: {:?}", err);
+                return false;
+            }
+        };
+
+        // nothing changed on screen, keep the old buffer
+        let Some(damage) = damage else {
+            return false;
+        };
+
+        if let Err(err) = self.backend.submit(Some(&damage)) {
+            warn!("Winit: Failed to submit frame: {:?}", err);
         }
+
+        true
+    }
+
+    /// Ask winit for a redraw, requests coalesce until the next frame.
+    pub fn queue_redraw(&mut self) {
+        self.backend.window().request_redraw();
     }
 
     fn get_renderer(&mut self) -> &mut GlesRenderer {
         self.backend.renderer()
     }
 
+    pub fn with_renderer<T>(&mut self, f: impl FnOnce(&mut GlesRenderer) -> T) -> T {
+        f(self.get_renderer())
+    }
+
     pub fn dmabuf_imported(&mut self, dmabuf: &Dmabuf) -> bool {
         match self.get_renderer().import_dmabuf(dmabuf, None) {
             Ok(_) => true,
@@ -0,0 +1,180 @@
+use std::{collections::HashMap, fs, time::Duration};
+
+use regex::Regex;
+
+use crate::manager::animation::AnimationType;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AnimationEvent {
+    WindowOpen,
+    WindowClose,
+    WindowMove,
+    WindowResize,
+    WorkspaceSwitch,
+    Expansion,
+    FloatingToggle,
+    FocusDim,
+}
+
+impl AnimationEvent {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "windowOpen" => Some(AnimationEvent::WindowOpen),
+            "windowClose" => Some(AnimationEvent::WindowClose),
+            "windowMove" => Some(AnimationEvent::WindowMove),
+            "windowResize" => Some(AnimationEvent::WindowResize),
+            "workspaceSwitch" => Some(AnimationEvent::WorkspaceSwitch),
+            "expansion" => Some(AnimationEvent::Expansion),
+            "floatingToggle" => Some(AnimationEvent::FloatingToggle),
+            "focusDim" => Some(AnimationEvent::FocusDim),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct AnimationStyle {
+    pub enabled: bool,
+    pub duration: Duration,
+    pub curve: AnimationType,
+}
+
+impl AnimationStyle {
+    fn new(duration: u64, curve: AnimationType) -> Self {
+        Self {
+            enabled: true,
+            duration: Duration::from_millis(duration),
+            curve,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct AnimationConfigs {
+    pub styles: HashMap<AnimationEvent, AnimationStyle>,
+}
+
+impl AnimationConfigs {
+    pub fn default() -> Self {
+        let styles = [
+            (AnimationEvent::WindowOpen, AnimationStyle::new(350, AnimationType::OvershootBounce)),
+            (AnimationEvent::WindowClose, AnimationStyle::new(250, AnimationType::EaseInOutQuad)),
+            (AnimationEvent::WindowMove, AnimationStyle::new(250, AnimationType::EaseInOutQuad)),
+            (AnimationEvent::WindowResize, AnimationStyle::new(250, AnimationType::EaseInOutQuad)),
+            (AnimationEvent::WorkspaceSwitch, AnimationStyle::new(250, AnimationType::EaseInOutQuad)),
+            (AnimationEvent::Expansion, AnimationStyle::new(250, AnimationType::EaseInOutQuad)),
+            (AnimationEvent::FloatingToggle, AnimationStyle::new(150, AnimationType::EaseInOutQuad)),
+            (AnimationEvent::FocusDim, AnimationStyle::new(200, AnimationType::EaseInOutQuad)),
+        ]
+        .into_iter()
+        .collect();
+
+        Self { styles }
+    }
+
+    pub fn style(&self, event: AnimationEvent) -> AnimationStyle {
+        self.styles
+            .get(&event)
+            .cloned()
+            .unwrap_or(AnimationStyle::new(250, AnimationType::EaseInOutQuad))
+    }
+
+    pub fn load_configs(&mut self, path: &str) -> anyhow::Result<()> {
+        let content = fs::read_to_string(path)?;
+
+        // named curves, built-in ones can be overridden
+        let mut curves: HashMap<String, AnimationType> = [
+            ("linear", AnimationType::Linear),
+            ("easeInOutQuad", AnimationType::EaseInOutQuad),
+            ("overshootBounce", AnimationType::OvershootBounce),
+        ]
+        .into_iter()
+        .map(|(k, v)| (k.to_string(), v))
+        .collect();
+
+        let re_bezier =
+            // bezier = smooth, 0.25, 0.1, 0.25, 1.0
+            Regex::new(
+                r#"(?m)^\s*bezier\s*=\s*(\w+)\s*,\s*([-0-9.]+)\s*,\s*([-0-9.]+)\s*,\s*([-0-9.]+)\s*,\s*([-0-9.]+)\s*(?:#.*)?$"#
+            ).unwrap();
+
+        let re_spring =
+            // spring = bouncy, 300, 20, 1.0  (stiffness, damping, mass)
+            Regex::new(
+                r#"(?m)^\s*spring\s*=\s*(\w+)\s*,\s*([0-9.]+)\s*,\s*([0-9.]+)\s*,\s*([0-9.]+)\s*(?:#.*)?$"#
+            ).unwrap();
+
+        let re_animation =
+            // animation = windowOpen, true, 350, bouncy
+            Regex::new(
+                r#"(?m)^\s*animation\s*=\s*(\w+)\s*,\s*(true|false)\s*,\s*([0-9]+)\s*,\s*(\w+)\s*(?:#.*)?$"#
+            ).unwrap();
+
+        for cap in re_bezier.captures_iter(&content) {
+            let points: Result<Vec<f64>, _> = (2..=5).map(|i| cap[i].parse::<f64>()).collect();
+
+            match points {
+                // x must stay monotonic for the curve to be solvable
+                Ok(p) if (0.0..=1.0).contains(&p[0]) && (0.0..=1.0).contains(&p[2]) => {
+                    curves.insert(cap[1].to_string(), AnimationType::CubicBezier(p[0], p[1], p[2], p[3]));
+                }
+                Ok(_) => {
+                    error!("bezier {}: x of the control points must be within 0 and 1", &cap[1]);
+                }
+                Err(e) => {
+                    error!("cannot parse bezier {}: {:?}", &cap[1], e);
+                }
+            }
+        }
+
+        for cap in re_spring.captures_iter(&content) {
+            let params: Result<Vec<f64>, _> = (2..=4).map(|i| cap[i].parse::<f64>()).collect();
+
+            match params {
+                Ok(p) if p[0] > 0.0 && p[2] > 0.0 => {
+                    curves.insert(
+                        cap[1].to_string(),
+                        AnimationType::Spring { stiffness: p[0], damping: p[1], mass: p[2] },
+                    );
+                }
+                Ok(_) => {
+                    error!("spring {}: stiffness and mass must be positive", &cap[1]);
+                }
+                Err(e) => {
+                    error!("cannot parse spring {}: {:?}", &cap[1], e);
+                }
+            }
+        }
+
+        for cap in re_animation.captures_iter(&content) {
+            let event = match AnimationEvent::from_name(&cap[1]) {
+                Some(event) => event,
+                None => {
+                    warn!("unknown animation event: {}", &cap[1]);
+                    continue;
+                }
+            };
+
+            let curve = match curves.get(&cap[4]) {
+                Some(curve) => *curve,
+                None => {
+                    warn!("unknown animation curve: {}", &cap[4]);
+                    continue;
+                }
+            };
+
+            let duration = cap[3].parse::<u64>().unwrap_or(250);
+
+            #[cfg(feature = "trace_config")]
+            info!("animation {:?}: {:?}ms, {:?}", event, duration, curve);
+
+            self.styles.insert(event, AnimationStyle {
+                enabled: &cap[2] == "true",
+                duration: Duration::from_millis(duration),
+                curve,
+            });
+        }
+
+        Ok(())
+    }
+}
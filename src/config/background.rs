@@ -0,0 +1,130 @@
+use std::{collections::HashMap, fs};
+
+use regex::Regex;
+
+use crate::config::decoration::parse_color;
+
+/// How an image is laid out on an output of another size.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageFit {
+    // cover the output, cropping the overflow
+    Fill,
+    // show the whole image, letterboxed
+    Fit,
+    // unscaled in the middle
+    Center,
+    // unscaled and repeated from the top left
+    Tile,
+}
+
+impl ImageFit {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "fill" => Some(ImageFit::Fill),
+            "fit" => Some(ImageFit::Fit),
+            "center" => Some(ImageFit::Center),
+            "tile" => Some(ImageFit::Tile),
+            _ => None,
+        }
+    }
+}
+
+/// The bundled shaders, named after their files.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BackgroundShader {
+    Default,
+    Blobs,
+    Slow,
+    Dark,
+    Light,
+}
+
+impl BackgroundShader {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "default" => Some(BackgroundShader::Default),
+            "blobs" => Some(BackgroundShader::Blobs),
+            "slow" => Some(BackgroundShader::Slow),
+            "dark" => Some(BackgroundShader::Dark),
+            "light" => Some(BackgroundShader::Light),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum BackgroundSource {
+    Image { path: String, fit: ImageFit },
+    Color([f32; 4]),
+    // static shaders are drawn once and never redrawn
+    Shader { shader: BackgroundShader, animate: bool },
+}
+
+#[derive(Debug, Clone)]
+pub struct BackgroundConfigs {
+    // for outputs without an entry of their own, none leaves it to layer shell clients
+    pub fallback: Option<BackgroundSource>,
+    pub outputs: HashMap<String, BackgroundSource>,
+}
+
+impl BackgroundConfigs {
+    pub fn default() -> Self {
+        Self {
+            fallback: None,
+            outputs: HashMap::new(),
+        }
+    }
+
+    pub fn source(&self, output_name: &str) -> Option<&BackgroundSource> {
+        self.outputs.get(output_name).or(self.fallback.as_ref())
+    }
+
+    pub fn load_configs(&mut self, path: &str) -> anyhow::Result<()> {
+        let content = fs::read_to_string(path)?;
+
+        let re_background =
+            // background = OUTPUT, KIND, VALUE[, OPTION]
+            Regex::new(r#"(?m)^\s*background\s*=\s*([^,]+?)\s*,\s*(\w+)\s*,\s*([^,]+?)\s*(?:,\s*(\w+)\s*)?$"#).unwrap();
+
+        for cap in re_background.captures_iter(&content) {
+            let output = &cap[1];
+            let option = cap.get(4).map(|m| m.as_str());
+
+            let source = match (&cap[2], option) {
+                ("image", option) => {
+                    let fit = option.map(ImageFit::from_name).unwrap_or(Some(ImageFit::Fill));
+                    fit.map(|fit| BackgroundSource::Image { path: expand_home(&cap[3]), fit })
+                }
+                ("color", None) => parse_color(&cap[3]).map(BackgroundSource::Color),
+                ("shader", None | Some("animate")) => BackgroundShader::from_name(&cap[3])
+                    .map(|shader| BackgroundSource::Shader { shader, animate: true }),
+                ("shader", Some("static")) => BackgroundShader::from_name(&cap[3])
+                    .map(|shader| BackgroundSource::Shader { shader, animate: false }),
+                _ => None,
+            };
+
+            let Some(source) = source else {
+                warn!("invalid background: {}", &cap[0].trim());
+                continue;
+            };
+
+            #[cfg(feature = "trace_config")]
+            info!("background {} = {:?}", output, source);
+
+            if output == "*" {
+                self.fallback = Some(source);
+            } else {
+                self.outputs.insert(output.to_string(), source);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn expand_home(path: &str) -> String {
+    match (path.strip_prefix("~/"), dirs::home_dir()) {
+        (Some(rest), Some(home)) => home.join(rest).to_string_lossy().into_owned(),
+        _ => path.to_string(),
+    }
+}
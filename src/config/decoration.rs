@@ -0,0 +1,302 @@
+use std::fs;
+
+use regex::Regex;
+
+/// A solid color or a linear gradient, colors are straight rgba in 0..1.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GradientColor {
+    pub start: [f32; 4],
+    pub end: [f32; 4],
+    // radians, 0 points right and grows clockwise
+    pub angle: f32,
+}
+
+impl GradientColor {
+    pub fn solid(color: [f32; 4]) -> Self {
+        Self { start: color, end: color, angle: 0.0 }
+    }
+
+    // rgba(bbcafcff) rgba(462ae9ff) 45deg
+    pub fn parse(value: &str) -> Option<Self> {
+        let mut colors = Vec::new();
+        let mut angle = 0.0;
+
+        for part in value.split_whitespace() {
+            if let Some(deg) = part.strip_suffix("deg") {
+                angle = deg.parse::<f32>().ok()?.to_radians();
+            } else {
+                colors.push(parse_color(part)?);
+            }
+        }
+
+        match colors.as_slice() {
+            [color] => Some(Self::solid(*color)),
+            [start, end] => Some(Self { start: *start, end: *end, angle }),
+            _ => None,
+        }
+    }
+}
+
+/// Parse `rgba(RRGGBBAA)`, `rgb(RRGGBB)` or `#RRGGBB[AA]`.
+pub fn parse_color(value: &str) -> Option<[f32; 4]> {
+    let value = value.trim();
+    let hex = if let Some(hex) = value.strip_prefix("rgba(").and_then(|v| v.strip_suffix(')')) {
+        hex
+    } else if let Some(hex) = value.strip_prefix("rgb(").and_then(|v| v.strip_suffix(')')) {
+        hex
+    } else {
+        value.strip_prefix('#')?
+    };
+
+    if hex.len() != 6 && hex.len() != 8 {
+        return None;
+    }
+
+    let mut channels = [1.0; 4];
+    for (i, channel) in channels.iter_mut().enumerate().take(hex.len() / 2) {
+        let byte = u8::from_str_radix(hex.get(i * 2..i * 2 + 2)?, 16).ok()?;
+        *channel = byte as f32 / 255.0;
+    }
+
+    Some(channels)
+}
+
+// 0 6
+fn parse_offset(value: &str) -> Option<(i32, i32)> {
+    let mut parts = value.split_whitespace().map(|v| v.parse::<i32>());
+
+    match (parts.next(), parts.next(), parts.next()) {
+        (Some(Ok(x)), Some(Ok(y)), None) => Some((x, y)),
+        _ => None,
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct BorderConfigs {
+    pub thickness: i32,
+    pub radius: f32,
+    // draw borders around unfocused windows too
+    pub unfocused: bool,
+    // breathing effect driven by u_time
+    pub pulse: bool,
+
+    pub active: GradientColor,
+    pub inactive: GradientColor,
+    pub urgent: GradientColor,
+    pub floating: GradientColor,
+}
+
+impl BorderConfigs {
+    pub fn default() -> Self {
+        Self {
+            thickness: 3,
+            radius: 10.0,
+            unfocused: false,
+            pulse: false,
+            active: GradientColor {
+                start: [0.7333, 0.7922, 0.9882, 1.0],
+                end: [0.2745, 0.1647, 0.9137, 1.0],
+                angle: 45f32.to_radians(),
+            },
+            inactive: GradientColor::solid([0.35, 0.35, 0.35, 0.67]),
+            urgent: GradientColor::solid([1.0, 0.33, 0.33, 1.0]),
+            floating: GradientColor::solid([0.55, 0.75, 0.55, 1.0]),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ShadowConfigs {
+    pub enabled: bool,
+    // floating windows always get shadows when enabled
+    pub tiled: bool,
+    pub blur_radius: i32,
+    pub spread: i32,
+    pub offset: (i32, i32),
+
+    pub active: [f32; 4],
+    pub inactive: [f32; 4],
+}
+
+impl ShadowConfigs {
+    pub fn default() -> Self {
+        Self {
+            enabled: true,
+            tiled: false,
+            blur_radius: 20,
+            spread: 2,
+            offset: (0, 6),
+            active: [0.0, 0.0, 0.0, 0.55],
+            inactive: [0.0, 0.0, 0.0, 0.3],
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct BlurConfigs {
+    pub enabled: bool,
+    // dual kawase passes, each one halves the resolution
+    pub passes: usize,
+    // sample distance of each pass
+    pub size: f32,
+}
+
+impl BlurConfigs {
+    pub fn default() -> Self {
+        Self {
+            enabled: true,
+            passes: 3,
+            size: 3.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct DimConfigs {
+    pub enabled: bool,
+    // how much inactive windows are darkened, 0..1
+    pub strength: f32,
+    // how much inactive windows lose their color, 0..1
+    pub desaturate: f32,
+}
+
+impl DimConfigs {
+    pub fn default() -> Self {
+        Self {
+            enabled: false,
+            strength: 0.2,
+            desaturate: 0.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct TitleBarConfigs {
+    pub enabled: bool,
+    pub height: i32,
+    // a ttf or otf file, there is no font lookup by name, the default path is the Arch one
+    pub font: String,
+    pub font_size: f32,
+
+    pub active: [f32; 4],
+    pub inactive: [f32; 4],
+    pub text_active: [f32; 4],
+    pub text_inactive: [f32; 4],
+    pub close: [f32; 4],
+    pub float: [f32; 4],
+}
+
+impl TitleBarConfigs {
+    pub fn default() -> Self {
+        Self {
+            enabled: false,
+            height: 24,
+            font: "/usr/share/fonts/TTF/DejaVuSans.ttf".to_string(),
+            font_size: 13.0,
+            active: [0.16, 0.16, 0.22, 1.0],
+            inactive: [0.12, 0.12, 0.14, 1.0],
+            text_active: [0.9, 0.9, 0.95, 1.0],
+            text_inactive: [0.55, 0.55, 0.6, 1.0],
+            close: [1.0, 0.33, 0.33, 1.0],
+            float: [0.55, 0.75, 0.55, 1.0],
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct DecorationConfigs {
+    // let clients draw their own decorations instead of requesting server side
+    pub client_side: bool,
+    // preview of a preselected split
+    pub presel: [f32; 4],
+    pub border: BorderConfigs,
+    pub shadow: ShadowConfigs,
+    pub blur: BlurConfigs,
+    pub dim: DimConfigs,
+    pub title_bar: TitleBarConfigs,
+}
+
+impl DecorationConfigs {
+    pub fn default() -> Self {
+        Self {
+            client_side: false,
+            presel: [0.27, 0.16, 0.91, 0.3],
+            border: BorderConfigs::default(),
+            shadow: ShadowConfigs::default(),
+            blur: BlurConfigs::default(),
+            dim: DimConfigs::default(),
+            title_bar: TitleBarConfigs::default(),
+        }
+    }
+
+    pub fn load_configs(&mut self, path: &str) -> anyhow::Result<()> {
+        let content = fs::read_to_string(path)?;
+
+        let re_option =
+            // border_active = rgba(bbcafcff) rgba(462ae9ff) 45deg
+            // border_active = #bbcafc #462ae9 45deg # a comment, hex colors are not one
+            Regex::new(r#"(?m)^\s*(\w+)\s*=\s*(.+?)\s*(?:\s#(?:[^0-9a-fA-F\n].*)?)?$"#).unwrap();
+
+        for cap in re_option.captures_iter(&content) {
+            let key = &cap[1];
+            let value = &cap[2];
+
+            #[cfg(feature = "trace_config")]
+            info!("decoration {} = {}", key, value);
+
+            if !self.apply_option(key, value) {
+                warn!("invalid decoration option: {} = {}", key, value);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn apply_option(&mut self, key: &str, value: &str) -> bool {
+        let border = &mut self.border;
+        let shadow = &mut self.shadow;
+        let blur = &mut self.blur;
+        let dim = &mut self.dim;
+        let title_bar = &mut self.title_bar;
+
+        match key {
+            "client_side_decorations" => value.parse().map(|v| self.client_side = v).is_ok(),
+            "presel_color" => parse_color(value).map(|v| self.presel = v).is_some(),
+            "border_thickness" => value.parse().map(|v| border.thickness = v).is_ok(),
+            "border_radius" => value.parse().map(|v| border.radius = v).is_ok(),
+            "border_unfocused" => value.parse().map(|v| border.unfocused = v).is_ok(),
+            "border_pulse" => value.parse().map(|v| border.pulse = v).is_ok(),
+            "border_active" => GradientColor::parse(value).map(|v| border.active = v).is_some(),
+            "border_inactive" => GradientColor::parse(value).map(|v| border.inactive = v).is_some(),
+            "border_urgent" => GradientColor::parse(value).map(|v| border.urgent = v).is_some(),
+            "border_floating" => GradientColor::parse(value).map(|v| border.floating = v).is_some(),
+            "shadow_enabled" => value.parse().map(|v| shadow.enabled = v).is_ok(),
+            "shadow_tiled" => value.parse().map(|v| shadow.tiled = v).is_ok(),
+            "shadow_blur_radius" => value.parse().map(|v| shadow.blur_radius = v).is_ok(),
+            "shadow_spread" => value.parse().map(|v| shadow.spread = v).is_ok(),
+            "shadow_offset" => parse_offset(value).map(|v| shadow.offset = v).is_some(),
+            "shadow_active" => parse_color(value).map(|v| shadow.active = v).is_some(),
+            "shadow_inactive" => parse_color(value).map(|v| shadow.inactive = v).is_some(),
+            "blur_enabled" => value.parse().map(|v| blur.enabled = v).is_ok(),
+            "blur_passes" => value.parse().map(|v| blur.passes = v).is_ok(),
+            "blur_size" => value.parse().map(|v| blur.size = v).is_ok(),
+            "dim_enabled" => value.parse().map(|v| dim.enabled = v).is_ok(),
+            "dim_strength" => value.parse::<f32>().map(|v| dim.strength = v.clamp(0.0, 1.0)).is_ok(),
+            "dim_desaturate" => value.parse::<f32>().map(|v| dim.desaturate = v.clamp(0.0, 1.0)).is_ok(),
+            "titlebar_enabled" => value.parse().map(|v| title_bar.enabled = v).is_ok(),
+            "titlebar_height" => value.parse::<i32>().map(|v| title_bar.height = v.max(0)).is_ok(),
+            "titlebar_font" => {
+                title_bar.font = value.to_string();
+                true
+            }
+            "titlebar_font_size" => value.parse().map(|v| title_bar.font_size = v).is_ok(),
+            "titlebar_active" => parse_color(value).map(|v| title_bar.active = v).is_some(),
+            "titlebar_inactive" => parse_color(value).map(|v| title_bar.inactive = v).is_some(),
+            "titlebar_text_active" => parse_color(value).map(|v| title_bar.text_active = v).is_some(),
+            "titlebar_text_inactive" => parse_color(value).map(|v| title_bar.text_inactive = v).is_some(),
+            "titlebar_close" => parse_color(value).map(|v| title_bar.close = v).is_some(),
+            "titlebar_float" => parse_color(value).map(|v| title_bar.float = v).is_some(),
+            _ => false,
+        }
+    }
+}
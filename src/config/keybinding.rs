@@ -3,12 +3,15 @@ use std::{collections::HashMap, fs};
 use itertools::Itertools;
 use regex::Regex;
 
-use crate::layout::ResizeEdge;
+use smithay::utils::{Logical, Rectangle};
+
+use crate::layout::{ContainerMode, Presel, ResizeEdge, TiledScheme};
 
 #[derive(Debug, Clone)]
 pub enum FunctionEnum {
     InvertWindow,
-    Expansion,
+    // bool: include windows from all workspaces
+    Expansion(bool),
     Recover,
     Quit,
     Kill,
@@ -19,9 +22,33 @@ pub enum FunctionEnum {
     Right(ResizeEdge),
 
     SwitchLayout,
+    // lay out the current workspace with this scheme
+    SetScheme(TiledScheme),
+    // split of the focused window for the next one mapped, none cancels
+    Presel(Option<Presel>),
+    // toggle the container of the focused window between this mode and split
+    ToggleContainer(ContainerMode),
+    // bool: next tab, else the previous one
+    FocusInGroup(bool),
 
     SwitchWorkspace(usize),
     Template(usize),
+
+    FocusLast,
+    FocusUrgent,
+    // bool: include windows from all workspaces
+    CycleNext(bool),
+    CyclePrev(bool),
+
+    Screenshot(ScreenshotTarget),
+}
+
+#[derive(Debug, Clone)]
+pub enum ScreenshotTarget {
+    Output,
+    Window,
+    // relative to the current output
+    Region(Rectangle<i32, Logical>),
 }
 
 #[derive(Debug, Clone)]
@@ -33,6 +60,7 @@ pub enum KeyAction {
 #[derive(Debug)]
 pub struct KeybindingConfigs {
     pub mainmod: String,
+    pub switcher_overlay: bool,
 
     pub keybindings: HashMap<String, KeyAction>,
     pub priority_map: HashMap<String, i32>,
@@ -56,6 +84,7 @@ impl KeybindingConfigs {
 
         Self {
             mainmod: "Super".to_string(),
+            switcher_overlay: false,
             keybindings: HashMap::new(),
             priority_map,
         }
@@ -73,6 +102,11 @@ impl KeybindingConfigs {
             warn!("No mainMod found in the configuration file, use default Super");
         }
 
+        let re_switcher = Regex::new(r#"(?m)^\s*switcherOverlay\s*=\s*(true|false)"#).unwrap();
+        if let Some(cap) = re_switcher.captures(&content) {
+            self.switcher_overlay = &cap[1] == "true";
+        }
+
         let re_bindings =
             // bind = Ctrl + t, command, "kitty"
             // bind = Ctrl + 1, exec, "func1"
@@ -154,7 +188,8 @@ impl KeybindingConfigs {
                         let internal_action = match command.trim() {
                             "invert" => FunctionEnum::InvertWindow,
                             "recover" => FunctionEnum::Recover,
-                            "expansion" => FunctionEnum::Expansion,
+                            "expansion" => FunctionEnum::Expansion(false),
+                            "expansion all" => FunctionEnum::Expansion(true),
                             "quit" => FunctionEnum::Quit,
                             "kill" => FunctionEnum::Kill,
                             "json" => FunctionEnum::Json,
@@ -163,6 +198,48 @@ impl KeybindingConfigs {
                             "left" => FunctionEnum::Left(ResizeEdge::Left),
                             "right" => FunctionEnum::Right(ResizeEdge::Right),
                             "switch layout" => FunctionEnum::SwitchLayout,
+                            "toggle tabbed" => FunctionEnum::ToggleContainer(ContainerMode::Tabbed),
+                            "toggle stacked" => FunctionEnum::ToggleContainer(ContainerMode::Stacked),
+                            "focus next in group" => FunctionEnum::FocusInGroup(true),
+                            "focus prev in group" => FunctionEnum::FocusInGroup(false),
+                            "focus last" => FunctionEnum::FocusLast,
+                            "focus urgent" => FunctionEnum::FocusUrgent,
+                            "cycle next" => FunctionEnum::CycleNext(false),
+                            "cycle prev" => FunctionEnum::CyclePrev(false),
+                            "cycle next all" => FunctionEnum::CycleNext(true),
+                            "cycle prev all" => FunctionEnum::CyclePrev(true),
+                            "screenshot output" => FunctionEnum::Screenshot(ScreenshotTarget::Output),
+                            "screenshot window" => FunctionEnum::Screenshot(ScreenshotTarget::Window),
+                            region if region.starts_with("screenshot region") => {
+                                // same geometry format as slurp: "X,Y WxH"
+                                let region = region.trim_start_matches("screenshot region").trim();
+                                let Some(region) = parse_region(region) else {
+                                    tracing::info!("Warning: invalid screenshot region '{}'", region);
+                                    continue;
+                                };
+
+                                FunctionEnum::Screenshot(ScreenshotTarget::Region(region))
+                            },
+                            scheme if scheme.starts_with("scheme") => {
+                                // same format as the scheme option of workspace.conf
+                                let scheme = scheme.trim_start_matches("scheme").trim();
+                                let Some(scheme) = TiledScheme::parse(scheme) else {
+                                    tracing::info!("Warning: invalid scheme '{}'", scheme);
+                                    continue;
+                                };
+
+                                FunctionEnum::SetScheme(scheme)
+                            },
+                            "presel cancel" => FunctionEnum::Presel(None),
+                            presel if presel.starts_with("presel") => {
+                                // presel left 0.3
+                                let Some(presel) = parse_presel(presel.trim_start_matches("presel")) else {
+                                    tracing::info!("Warning: invalid preselection '{}'", presel);
+                                    continue;
+                                };
+
+                                FunctionEnum::Presel(Some(presel))
+                            },
                             "switch workspace" => {
                                 let id = key.split('+').find_map(|s| {
                                     s.trim().parse::<usize>().ok()
@@ -210,4 +287,33 @@ impl KeybindingConfigs {
     }
 }
 
+fn parse_presel(presel: &str) -> Option<Presel> {
+    let mut parts = presel.split_whitespace();
+
+    let edge = match parts.next()? {
+        "left" => ResizeEdge::Left,
+        "right" => ResizeEdge::Right,
+        "up" => ResizeEdge::Top,
+        "down" => ResizeEdge::Bottom,
+        _ => return None,
+    };
+    let ratio = match parts.next() {
+        Some(ratio) => Some(ratio.parse::<f64>().ok().filter(|ratio| (0.05..=0.95).contains(ratio))?),
+        None => None,
+    };
 
+    parts.next().is_none().then_some(Presel { edge, ratio })
+}
+
+fn parse_region(region: &str) -> Option<Rectangle<i32, Logical>> {
+    let (loc, size) = region.split_once(' ')?;
+    let (x, y) = loc.split_once(',')?;
+    let (w, h) = size.trim().split_once('x')?;
+
+    let rect = Rectangle::new(
+        (x.trim().parse().ok()?, y.trim().parse().ok()?).into(),
+        (w.parse().ok()?, h.parse().ok()?).into(),
+    );
+
+    (!rect.is_empty()).then_some(rect)
+}
@@ -2,11 +2,17 @@ use std::{fs, sync::Arc};
 
 use regex::Regex;
 
+pub mod animation;
+pub mod background;
+pub mod decoration;
 pub mod keybinding;
 pub mod workspace;
 pub mod windowrules;
 
 use crate::config::{
+    animation::AnimationConfigs,
+    background::BackgroundConfigs,
+    decoration::DecorationConfigs,
     keybinding::KeybindingConfigs, 
     workspace::WorkspaceConfigs,
     windowrules::WindowRulesConfigs,
@@ -21,7 +27,10 @@ pub struct Configs {
 
     pub conf_workspaces: Arc<WorkspaceConfigs>,
     pub conf_keybindings: Arc<KeybindingConfigs>,
-    pub conf_windowrules: Arc<WindowRulesConfigs>
+    pub conf_windowrules: Arc<WindowRulesConfigs>,
+    pub conf_animations: Arc<AnimationConfigs>,
+    pub conf_decoration: Arc<DecorationConfigs>,
+    pub conf_background: Arc<BackgroundConfigs>,
 }
 
 impl Configs {
@@ -42,6 +51,9 @@ impl Configs {
         let mut conf_workspaces = WorkspaceConfigs::default();
         let mut conf_keybindings = KeybindingConfigs::default();
         let mut conf_windowrules = WindowRulesConfigs::default();
+        let mut conf_animations = AnimationConfigs::default();
+        let mut conf_decoration = DecorationConfigs::default();
+        let mut conf_background = BackgroundConfigs::default();
 
         if let Ok(content) = content {
             info!("using config from {:?}", config_path);
@@ -110,6 +122,21 @@ impl Configs {
                             Ok(_) => info!("Loaded windowrules configs from {}", source_file),
                             Err(e) => error!("Failed to load keybindings configs: {}", e),
                         }
+                    } else if source_file.contains("animation") {
+                        match conf_animations.load_configs(source_file) {
+                            Ok(_) => info!("Loaded animations configs from {}", source_file),
+                            Err(e) => error!("Failed to load animations configs: {}", e),
+                        }
+                    } else if source_file.contains("decoration") {
+                        match conf_decoration.load_configs(source_file) {
+                            Ok(_) => info!("Loaded decoration configs from {}", source_file),
+                            Err(e) => error!("Failed to load decoration configs: {}", e),
+                        }
+                    } else if source_file.contains("background") {
+                        match conf_background.load_configs(source_file) {
+                            Ok(_) => info!("Loaded background configs from {}", source_file),
+                            Err(e) => error!("Failed to load background configs: {}", e),
+                        }
                     }
                 }
             }
@@ -123,7 +150,10 @@ impl Configs {
             exec_once_cmds,
             conf_workspaces: Arc::new(conf_workspaces),
             conf_keybindings: Arc::new(conf_keybindings),
-            conf_windowrules: Arc::new(conf_windowrules)
+            conf_windowrules: Arc::new(conf_windowrules),
+            conf_animations: Arc::new(conf_animations),
+            conf_decoration: Arc::new(conf_decoration),
+            conf_background: Arc::new(conf_background),
         }
     }
 
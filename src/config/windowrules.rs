@@ -1,101 +1,92 @@
-use std::{collections::HashMap, fs};
+use std::{collections::HashMap, fmt::Debug, fs, str::FromStr};
 
 use regex::Regex;
 
 #[derive(Debug, Clone)]
 pub struct WindowRulesConfigs {
     pub global_opacity: HashMap<String, f32>, // such: kitty, 0.95
-    pub fullscreen: HashMap<String, bool>
+    pub fullscreen: HashMap<String, bool>,
+    pub rounding: HashMap<String, bool>,
+    pub blur: HashMap<String, bool>,
+    pub dim: HashMap<String, bool>,
+    pub client_side: HashMap<String, bool>,
 }
 
 impl WindowRulesConfigs {
     pub fn default() -> Self {
         Self { 
             global_opacity: HashMap::new(),
-            fullscreen: HashMap::new() 
+            fullscreen: HashMap::new(),
+            rounding: HashMap::new(),
+            blur: HashMap::new(),
+            dim: HashMap::new(),
+            client_side: HashMap::new(),
         }
     }
 
     pub fn load_configs(&mut self, path: &str) -> anyhow::Result<()> {
         let content = fs::read_to_string(path)?;
 
-        let mut global_opacity = HashMap::<String, f32>::new();
-        let mut fullscreen = HashMap::<String, bool>::new();
+        // windowrule = opacity 1.00, app_id: ^(firefox)$
+        self.global_opacity = parse_rule(&content, "opacity", "[0-9.]+");
+        // windowrule = fullscreen true, app_id: ^(firefox)$
+        self.fullscreen = parse_rule(&content, "fullscreen", "true|false");
+        self.rounding = parse_rule(&content, "rounding", "true|false");
+        self.blur = parse_rule(&content, "blur", "true|false");
+        self.dim = parse_rule(&content, "dim", "true|false");
+        self.client_side = parse_rule(&content, "client_side", "true|false");
 
-        let re_opacity = 
-            // windowrule = opacity 1.00, app_id: ^(firefox)$
-            Regex::new(
-                r#"(?m)^\s*windowrule\s*=\s*opacity\s+([0-9.]+)\s*,\s*app_id\s*:\s*(.+?)\s*$"#
-            ).unwrap();
+        Ok(())
+    }
+}
 
-        let re_fullscreen = 
-            // windowrule = fullscreen true, app_id: ^(firefox)$
-            Regex::new(
-                r#"(?m)^\s*windowrule\s*=\s*fullscreen\s+(true|false)\s*,\s*app_id\s*:\s*(.+?)\s*$"#
-            ).unwrap();
+// windowrule = NAME VALUE, app_id: ^(firefox)$, value is a regex of what NAME accepts
+fn parse_rule<T: FromStr>(content: &str, name: &str, value: &str) -> HashMap<String, T>
+where
+    T: Clone,
+    T::Err: Debug,
+{
+    let mut rules = HashMap::new();
 
-        for cap in re_opacity.captures_iter(&content) {
-            let opacity_str = cap.get(1).unwrap().as_str();
-            let opacity = match opacity_str.parse::<f32>() {
-                Ok(val) => val,
-                Err(e) => {
-                    error!("cannot parse opacity as f32: {:?}", e);
-                    continue;
-                },
-            };
-            
-            let pattern = cap.get(2).unwrap().as_str();
+    let re_rule = Regex::new(&format!(
+        r#"(?m)^\s*windowrule\s*=\s*{}\s+({})\s*,\s*app_id\s*:\s*(.+?)\s*$"#,
+        regex::escape(name),
+        value,
+    )).unwrap();
 
-            // ^(firefox)$ → Exact
-            if let Some(caps) = Regex::new(r#"^\^\(([\w\-\.]+)\)\$$"#).unwrap().captures(pattern) {
-                global_opacity.insert(caps[1].to_string(), opacity);
+    for cap in re_rule.captures_iter(content) {
+        let value = match cap[1].parse::<T>() {
+            Ok(value) => value,
+            Err(e) => {
+                error!("cannot parse {} rule value {}: {:?}", name, &cap[1], e);
+                continue;
             }
-            // ^([Ff]irefox)$ → Expand case variants
-            else if let Some(caps) = Regex::new(r#"^\^\(\[([a-zA-Z])([a-zA-Z]*)\]([\w\-\.]+)\)\$$"#).unwrap().captures(pattern) {
-                let first_chars = vec![caps[1].to_ascii_lowercase(), caps[1].to_ascii_uppercase()];
-                let rest = caps[3].to_string();
+        };
 
-                for ch in first_chars {
-                    let full = format!("{}{}", ch, rest);
-                    global_opacity.insert(full, opacity);
-                }
-            }
+        for app_id in app_id_variants(&cap[2]) {
+            rules.insert(app_id, value.clone());
         }
-        
-        for cap in re_fullscreen.captures_iter(&content) {
-            let is_fullscreen = cap.get(1)
-                .unwrap()
-                .as_str();
+    }
 
-            let is_fullscreen = match is_fullscreen.parse::<bool>() {
-                Ok(val) => val,
-                Err(e) => {
-                    error!("cannot parse opacity as f32: {:?}", e);
-                    continue;
-                }
-            };
-            
-            let pattern = cap.get(2).unwrap().as_str();
+    rules
+}
 
-            // ^(firefox)$ → Exact
-            if let Some(caps) = Regex::new(r#"^\^\(([\w\-\.]+)\)\$$"#).unwrap().captures(pattern) {
-                fullscreen.insert(caps[1].to_string(), is_fullscreen);
-            }
-            // ^([Ff]irefox)$ → Expand case variants
-            else if let Some(caps) = Regex::new(r#"^\^\(\[([a-zA-Z])([a-zA-Z]*)\]([\w\-\.]+)\)\$$"#).unwrap().captures(pattern) {
-                let first_chars = vec![caps[1].to_ascii_lowercase(), caps[1].to_ascii_uppercase()];
-                let rest = caps[3].to_string();
+fn app_id_variants(pattern: &str) -> Vec<String> {
+    // ^(firefox)$ → Exact
+    if let Some(caps) = Regex::new(r#"^\^\(([\w\-\.]+)\)\$$"#).unwrap().captures(pattern) {
+        return vec![caps[1].to_string()];
+    }
 
-                for ch in first_chars {
-                    let full = format!("{}{}", ch, rest);
-                    fullscreen.insert(full, is_fullscreen);
-                }
-            }
-        }
-        
-        self.global_opacity = global_opacity;
-        self.fullscreen = fullscreen;
+    // ^([Ff]irefox)$ → Expand case variants
+    if let Some(caps) = Regex::new(r#"^\^\(\[([a-zA-Z])([a-zA-Z]*)\]([\w\-\.]+)\)\$$"#).unwrap().captures(pattern) {
+        let rest = caps[3].to_string();
 
-        Ok(())
+        return [caps[1].to_ascii_lowercase(), caps[1].to_ascii_uppercase()]
+            .into_iter()
+            .map(|ch| format!("{}{}", ch, rest))
+            .collect();
     }
-}
\ No newline at end of file
+
+    warn!("unsupported app_id pattern in windowrule: {}", pattern);
+    vec![]
+}
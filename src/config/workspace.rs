@@ -1,11 +1,19 @@
-use std::fs;
+use std::{collections::HashMap, fs};
 
-use crate::layout::TiledScheme;
+use regex::Regex;
+
+use crate::{layout::TiledScheme, manager::workspace::WorkspaceId};
 
 #[derive(Debug, Clone)]
 pub struct WorkspaceConfigs {
     pub gap: i32,
     pub scheme: TiledScheme,
+    // by workspace number, over the default scheme
+    pub schemes: HashMap<usize, TiledScheme>,
+    // height of the tabs of tabbed and stacked containers
+    pub tab_height: i32,
+    // new windows split the focused window instead of the one under the pointer
+    pub insert_at_focus: bool,
 }
 
 impl WorkspaceConfigs {
@@ -13,11 +21,72 @@ impl WorkspaceConfigs {
         Self {
             gap: 12,
             scheme: TiledScheme::Default,
+            schemes: HashMap::new(),
+            tab_height: 24,
+            insert_at_focus: false,
         }
     }
 
     pub fn load_configs(&mut self, path: &str) -> anyhow::Result<()> {
-        let _content = fs::read_to_string(path)?;
+        let content = fs::read_to_string(path)?;
+
+        let re_option =
+            // gap = 12
+            Regex::new(r#"(?m)^\s*(\w+)\s*=\s*(.+?)\s*(?:\s#(?:[^0-9a-fA-F\n].*)?)?$"#).unwrap();
+
+        for cap in re_option.captures_iter(&content) {
+            let key = &cap[1];
+            let value = &cap[2];
+
+            #[cfg(feature = "trace_config")]
+            info!("workspace {} = {}", key, value);
+
+            if !self.apply_option(key, value) {
+                warn!("invalid workspace option: {} = {}", key, value);
+            }
+        }
+
         Ok(())
     }
-}
\ No newline at end of file
+
+    pub fn scheme_for(&self, workspace_id: WorkspaceId) -> TiledScheme {
+        self.schemes
+            .get(&workspace_id.index())
+            .unwrap_or(&self.scheme)
+            .clone()
+    }
+
+    fn apply_option(&mut self, key: &str, value: &str) -> bool {
+        match key {
+            "gap" => value.parse().map(|v| self.gap = v).is_ok(),
+            "scheme" => TiledScheme::parse(value).map(|v| self.scheme = v).is_some(),
+            // workspace = 2, monocle
+            "workspace" => {
+                let Some((id, scheme)) = value.split_once(',') else {
+                    return false;
+                };
+
+                match (id.trim().parse::<usize>(), TiledScheme::parse(scheme)) {
+                    (Ok(id), Some(scheme)) => {
+                        self.schemes.insert(id, scheme);
+                        true
+                    }
+                    _ => false,
+                }
+            }
+            "tab_height" => value.parse().map(|v| self.tab_height = v).is_ok(),
+            "insert_at" => match value {
+                "pointer" => {
+                    self.insert_at_focus = false;
+                    true
+                }
+                "focus" => {
+                    self.insert_at_focus = true;
+                    true
+                }
+                _ => false,
+            },
+            _ => false,
+        }
+    }
+}
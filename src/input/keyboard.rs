@@ -1,9 +1,9 @@
 use smithay::{
-    backend::input::{Event, InputBackend, KeyState, KeyboardKeyEvent}, desktop::WindowSurface, input::keyboard::{xkb::keysym_get_name, FilterResult}, utils::{Serial, SERIAL_COUNTER}
+    backend::input::{Event, InputBackend, KeyState, KeyboardKeyEvent}, desktop::Window, input::keyboard::{xkb::keysym_get_name, FilterResult, Keysym}, utils::{Serial, SERIAL_COUNTER}
 };
 
 use crate::{
-    config::keybinding::{FunctionEnum, KeyAction}, input::focus::KeyboardFocusTarget, layout::WindowLayout, manager::{window::WindowExt, workspace::WorkspaceId}, state::GlobalData
+    config::keybinding::{FunctionEnum, KeyAction}, input::focus::KeyboardFocusTarget, layout::{ResizeEdge, WindowLayout}, manager::{window::WindowExt, workspace::WorkspaceId}, state::GlobalData
 };
 
 impl GlobalData {
@@ -30,7 +30,7 @@ impl GlobalData {
             event_state,
             serial,
             time,
-            |data, _modifiers, keysym_handle| {
+            |data, modifiers, keysym_handle| {
                 match event_state {
                     KeyState::Pressed => {
                         let mut pressed_keys_name: Vec<String> =
@@ -66,6 +66,12 @@ impl GlobalData {
                         if data.action_keys(keys, serial) {
                             return FilterResult::Intercept(());
                         }
+
+                        // the overview grabs the keyboard while it is shown
+                        if data.workspace_manager.overview().is_some() {
+                            data.overview_key(keysym_handle.modified_sym(), serial);
+                            return FilterResult::Intercept(());
+                        }
                     }
                     KeyState::Released => {
                         let keysym_value = keysym_handle.modified_sym();
@@ -76,6 +82,13 @@ impl GlobalData {
 
                             data.input_manager.set_mainmod(false);
                         }
+
+                        // window cycling is committed once every modifier is released
+                        if data.input_manager.window_cycle().is_some()
+                            && !(modifiers.alt || modifiers.ctrl || modifiers.logo || modifiers.shift)
+                        {
+                            data.commit_window_cycle(serial);
+                        }
                     }
                 }
                 FilterResult::Forward
@@ -135,23 +148,26 @@ impl GlobalData {
                             self.workspace_manager.invert_window(&target, &mut self.animation_manager);
                         }
                     }
-                    FunctionEnum::Expansion => {
-                        self.workspace_manager.tiled_expansion(&mut self.animation_manager);
+                    FunctionEnum::Expansion(all_workspaces) => {
+                        let all_workspaces = *all_workspaces;
+
+                        if self.workspace_manager.overview().is_some() {
+                            self.workspace_manager.tiled_recover(&mut self.animation_manager);
+                        } else {
+                            let focus = match self.input_manager.get_keyboard_focus() {
+                                Some(KeyboardFocusTarget::Window(window)) => Some(window),
+                                _ => None,
+                            };
+
+                            self.workspace_manager.tiled_expansion(all_workspaces, focus.as_ref(), &mut self.animation_manager);
+                        }
                     }
                     FunctionEnum::Recover => {
                         self.workspace_manager.tiled_recover(&mut self.animation_manager);
                     }
                     FunctionEnum::Quit => {
                         if let Some(KeyboardFocusTarget::Window(window)) = self.input_manager.get_keyboard_focus() {
-                            match window.underlying_surface() {
-                                WindowSurface::Wayland(toplevel) => {
-                                    toplevel.send_close();
-                                },
-                                #[cfg(feature = "xwayland")]
-                                WindowSurface::X11(x11_surface) => {
-                                    let _ = x11_surface.close();
-                                }
-                            }
+                            window.close();
                         }
                     }
                     FunctionEnum::Up(edge)
@@ -184,19 +200,66 @@ impl GlobalData {
                             self.switch_layout(&window, pointer_loc.to_i32_round());
                         }
                     }
-                    FunctionEnum::SwitchWorkspace(id) => {
-                        let output = self.output_manager.current_output();
-                        let output_geo = self.output_manager
-                            .output_geometry(output).unwrap();
+                    FunctionEnum::SetScheme(scheme) => {
+                        let scheme = scheme.clone();
+                        self.workspace_manager.set_scheme(scheme, &mut self.animation_manager);
 
-                        self.workspace_manager.switch_workspace(WorkspaceId::new(*id), output_geo, &mut self.animation_manager);
+                        // monocle may have put the focused window behind another
+                        if let Some(KeyboardFocusTarget::Window(window)) = self.input_manager.get_keyboard_focus() {
+                            self.workspace_manager.reveal_window(&window);
+                        }
+                    }
+                    FunctionEnum::Presel(presel) => {
+                        let presel = presel.clone();
+
+                        if let Some(KeyboardFocusTarget::Window(window)) = self.input_manager.get_keyboard_focus() {
+                            self.window_manager.set_presel(&window, presel);
+                            self.queue_redraw();
+                        }
+                    }
+                    FunctionEnum::ToggleContainer(mode) => {
+                        let mode = *mode;
+
+                        if let Some(KeyboardFocusTarget::Window(window)) = self.input_manager.get_keyboard_focus() {
+                            self.workspace_manager.set_container_mode(&window, mode, &mut self.animation_manager);
+                        }
+                    }
+                    FunctionEnum::FocusInGroup(next) => {
+                        let next = *next;
 
-                        self.update_output_working_size();
-                        self.set_keyboard_focus(None, serial);
+                        if let Some(KeyboardFocusTarget::Window(window)) = self.input_manager.get_keyboard_focus() {
+                            if let Some(window) = self.workspace_manager.focus_in_group(&window, next) {
+                                self.set_keyboard_focus(Some(window.into()), serial);
+                            }
+                        }
+                    }
+                    FunctionEnum::SwitchWorkspace(id) => {
+                        let workspace_id = WorkspaceId::new(*id);
+                        self.switch_to_workspace(workspace_id, serial);
                     }
                     FunctionEnum::Template(_id) => {
                         
                     }
+                    FunctionEnum::FocusLast => {
+                        self.focus_last(serial);
+                    }
+                    FunctionEnum::CycleNext(all_workspaces) => {
+                        let all_workspaces = *all_workspaces;
+                        self.cycle_windows(true, all_workspaces, serial);
+                    }
+                    FunctionEnum::CyclePrev(all_workspaces) => {
+                        let all_workspaces = *all_workspaces;
+                        self.cycle_windows(false, all_workspaces, serial);
+                    }
+                    FunctionEnum::FocusUrgent => {
+                        if let Some(window) = self.window_manager.last_urgent().cloned() {
+                            self.activate_window(&window, serial);
+                        }
+                    }
+                    FunctionEnum::Screenshot(target) => {
+                        let target = target.clone();
+                        self.screenshot(&target);
+                    }
                 },
             }
         
@@ -229,6 +292,9 @@ impl GlobalData {
             if matches!(window.get_layout(), WindowLayout::Floating) {
                 self.window_manager.raise_window(window);
             }
+
+            self.input_manager.record_focus(window);
+            self.window_manager.set_urgent(window, false);
         }
 
         let keyboard = self.input_manager.get_keyboard();
@@ -242,5 +308,152 @@ impl GlobalData {
 
         keyboard.set_focus(self, focus_target, serial);
     }
-}
 
+    pub fn activate_window(&mut self, window: &Window, serial: Serial) {
+        // switch to the workspace of the window first
+        if let Some(workspace_id) = self.window_manager.window_workspace.get(window).cloned() {
+            if workspace_id != self.workspace_manager.current_workspace().id() {
+                let output = self.output_manager.current_output();
+                let output_geo = self.output_manager
+                    .output_geometry(output).unwrap();
+
+                self.workspace_manager.switch_workspace(workspace_id, output_geo, &mut self.animation_manager);
+                self.update_output_working_size();
+            }
+        }
+
+        // minimized windows come back on the workspace they left
+        self.restore_minimized(window);
+
+        // bring it to the front of its tabbed or stacked containers
+        self.workspace_manager.reveal_window(window);
+
+        #[cfg(feature = "xwayland")]
+        if let Some(surface) = window.x11_surface() {
+            if let Some(xwm) = self.state.xwm.as_mut() {
+                let _ = xwm.raise_window(surface);
+            }
+        }
+
+        self.set_keyboard_focus(Some(window.clone().into()), serial);
+
+        // also reached from protocol requests, which no input redraw follows
+        self.queue_redraw();
+    }
+
+    pub fn switch_to_workspace(&mut self, workspace_id: WorkspaceId, serial: Serial) {
+        let output = self.output_manager.current_output();
+        let output_geo = self.output_manager
+            .output_geometry(output).unwrap();
+
+        self.workspace_manager.switch_workspace(workspace_id, output_geo, &mut self.animation_manager);
+
+        self.update_output_working_size();
+        self.set_keyboard_focus(None, serial);
+        self.queue_redraw();
+    }
+
+    pub fn focus_last(&mut self, serial: Serial) {
+        let focus = match self.input_manager.get_keyboard_focus() {
+            Some(KeyboardFocusTarget::Window(window)) => Some(window),
+            _ => None,
+        };
+
+        let last = self.input_manager
+            .focus_history()
+            .iter()
+            .find(|window| Some(*window) != focus.as_ref())
+            .cloned();
+
+        if let Some(window) = last {
+            self.activate_window(&window, serial);
+        }
+    }
+
+    pub fn cycle_windows(&mut self, forward: bool, all_workspaces: bool, serial: Serial) {
+        if self.input_manager.window_cycle().is_none() {
+            let workspace_id = self.workspace_manager.current_workspace().id();
+            let in_scope = |window: &Window| {
+                all_workspaces || self.window_manager.window_workspace.get(window) == Some(&workspace_id)
+            };
+
+            // most recently used first, never focused windows at the end
+            let mut candidates: Vec<Window> = self.input_manager
+                .focus_history()
+                .iter()
+                .filter(|window| in_scope(window))
+                .cloned()
+                .collect();
+
+            for window in self.window_manager.all_mapped() {
+                if in_scope(window) && !candidates.contains(window) {
+                    candidates.push(window.clone());
+                }
+            }
+
+            if candidates.is_empty() {
+                return;
+            }
+
+            self.input_manager.start_window_cycle(candidates);
+        }
+
+        if let Some(cycle) = self.input_manager.window_cycle_mut() {
+            cycle.step(forward);
+        }
+
+        // without the overlay, preview the selection by focusing it directly
+        if !self.input_manager.is_switcher_overlay_enabled() {
+            let selected = self.input_manager
+                .window_cycle()
+                .and_then(|cycle| cycle.selected().cloned());
+
+            if let Some(window) = selected {
+                self.activate_window(&window, serial);
+            }
+        }
+    }
+
+    pub fn overview_key(&mut self, keysym: Keysym, serial: Serial) {
+        match keysym {
+            Keysym::Left => self.workspace_manager.overview_move_selection(&ResizeEdge::Left),
+            Keysym::Right => self.workspace_manager.overview_move_selection(&ResizeEdge::Right),
+            Keysym::Up => self.workspace_manager.overview_move_selection(&ResizeEdge::Top),
+            Keysym::Down => self.workspace_manager.overview_move_selection(&ResizeEdge::Bottom),
+            Keysym::Return | Keysym::KP_Enter => {
+                let selected = self.workspace_manager
+                    .overview()
+                    .and_then(|overview| overview.selected().cloned());
+
+                self.exit_overview(selected, serial);
+            }
+            Keysym::Escape => {
+                self.exit_overview(None, serial);
+            }
+            Keysym::BackSpace => {
+                self.workspace_manager.overview_pop_filter(&mut self.animation_manager);
+            }
+            _ => {
+                if let Some(ch) = keysym.key_char().filter(|ch| !ch.is_control()) {
+                    self.workspace_manager.overview_push_filter(ch, &mut self.animation_manager);
+                }
+            }
+        }
+    }
+
+    pub fn exit_overview(&mut self, target: Option<Window>, serial: Serial) {
+        self.workspace_manager.tiled_recover(&mut self.animation_manager);
+
+        if let Some(window) = target {
+            self.activate_window(&window, serial);
+        }
+    }
+
+    pub fn commit_window_cycle(&mut self, serial: Serial) {
+        if let Some(cycle) = self.input_manager.take_window_cycle() {
+            if let Some(window) = cycle.selected() {
+                self.activate_window(window, serial);
+            }
+        }
+    }
+}
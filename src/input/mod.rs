@@ -54,6 +54,9 @@ impl GlobalData {
             }
             _ => {}
         }
+
+        // the cursor or the focus may have changed
+        self.queue_redraw();
     }
 }
 
@@ -16,7 +16,13 @@ use smithay::{
     },
 };
 
-use crate::{input::focus::PointerFocusTarget, manager::window::WindowExt, protocol::FullscreenSurface, state::GlobalData};
+use crate::{
+    input::focus::PointerFocusTarget,
+    manager::window::WindowExt,
+    protocol::FullscreenSurface,
+    render::title_bar::{button_under, TitleBarButton},
+    state::GlobalData,
+};
 
 const BUTTON_LEFT: u32 = 272;
 const BUTTON_RIGHT: u32 = 273;
@@ -203,6 +209,37 @@ impl GlobalData {
 
         let pointer_loc = pointer.current_location();
 
+        // clicks in the overview pick a window instead of reaching clients
+        if self.workspace_manager.overview().is_some() {
+            if button_state == ButtonState::Pressed {
+                let target = self.workspace_manager.overview_window_under(pointer_loc);
+                self.exit_overview(target, serial);
+            }
+            return;
+        }
+
+        // title bars belong to the compositor, clients never see these clicks
+        if button == BUTTON_LEFT && !self.input_manager.is_mainmod_pressed() && !pointer.is_grabbed() {
+            if let Some((window, bar)) = self.window_manager.title_bar_under(pointer_loc, self.workspace_manager.current_workspace().id()) {
+                if button_state == ButtonState::Pressed {
+                    match button_under(bar, pointer_loc) {
+                        Some(TitleBarButton::Close) => window.close(),
+                        Some(TitleBarButton::Float) => self.switch_layout(&window, pointer_loc),
+                        None => self.activate_window(&window, serial),
+                    }
+                }
+                return;
+            }
+
+            // so do the tabs of tabbed and stacked containers
+            if let Some((window, _)) = self.workspace_manager.tab_under(pointer_loc) {
+                if button_state == ButtonState::Pressed {
+                    self.activate_window(&window, serial);
+                }
+                return;
+            }
+        }
+
         if let Some((target, target_loc)) = self.focus_target_under(pointer_loc, serial, true) {
             // start grab or resize
             if button_state == ButtonState::Pressed 
@@ -357,7 +394,7 @@ impl GlobalData {
         else if let Some(window) =
             self.window_manager.window_under(pointer_loc, self.workspace_manager.current_workspace().id())
         {
-            let window_rect = window.get_rect().unwrap();
+            let window_rect = window.content_rect(window.get_rect().unwrap());
             let render_loc: Point<f64, Logical> = window_rect.loc.to_f64() - window.geometry().loc.to_f64();
 
             if update_keyboard_focus {
@@ -1,14 +1,17 @@
-use std::{cell::RefCell, time::Duration};
+use std::cell::RefCell;
 
 use smithay::{desktop::Window, utils::{Logical, Point, Rectangle}};
 
 use crate::{
+    config::animation::AnimationEvent,
     layout::{
+        overview::expansion_rects,
         tiled_tree::TiledTree, 
-        Direction, ResizeEdge, TiledScheme,
+        tiled_tree::TabStrip,
+        ContainerMode, Direction, ResizeEdge, TiledScheme,
         WindowLayout
     }, 
-    manager::{animation::{AnimationManager, AnimationType}, window::WindowExt},
+    manager::{animation::AnimationManager, window::WindowExt},
 };
 
 #[derive(Default)]
@@ -27,15 +30,17 @@ pub struct ContainerTree {
 
     root_rect: Rectangle<i32, Logical>,
     gap: i32,
+    tab_height: i32,
 }
 
 impl ContainerTree {
-    pub fn new(root_rect: Rectangle<i32, Logical>, gap: i32) -> ContainerTree {
+    pub fn new(root_rect: Rectangle<i32, Logical>, gap: i32, tab_height: i32) -> ContainerTree {
         Self { 
             tiled_tree: None,
             floating: Vec::new(),
             root_rect,
             gap,
+            tab_height,
         }
     }
 
@@ -53,6 +58,7 @@ impl ContainerTree {
         target: Option<&Window>,
         window: Window,
         edge: ResizeEdge,
+        ratio: Option<f64>,
         scheme: &TiledScheme,
         animation_manager: &mut AnimationManager,
     ) -> bool {
@@ -74,6 +80,7 @@ impl ContainerTree {
                                 direction,
                                 window.clone(),
                                 is_favour,
+                                ratio,
                                 animation_manager,
                             )
                         }
@@ -81,9 +88,27 @@ impl ContainerTree {
                             // TODO
                             false
                         }
+                        _ => {
+                            // automatic: the scheme places every window
+                            tiled_tree.push(window.clone(), self.root_rect);
+                            let from = window.get_rect().unwrap();
+
+                            tiled_tree.arrange(scheme, self.root_rect, animation_manager);
+
+                            let to = window.get_rect().unwrap();
+                            animation_manager.add_animation(window.clone(), from, to, AnimationEvent::WindowOpen);
+
+                            true
+                        }
                     }
                 } else {
-                    self.tiled_tree = Some(TiledTree::new_with_first_node(window.clone(), self.root_rect, self.gap, animation_manager));
+                    self.tiled_tree = Some(TiledTree::new_with_first_node(
+                        window.clone(),
+                        self.root_rect,
+                        self.gap,
+                        self.tab_height,
+                        animation_manager,
+                    ));
                     true
                 }
             },
@@ -101,13 +126,18 @@ impl ContainerTree {
         result
     }
 
-    pub fn remove(&mut self, target: &Window, animation_manager: &mut AnimationManager) {
+    pub fn remove(&mut self, target: &Window, scheme: &TiledScheme, animation_manager: &mut AnimationManager) {
         let _span = tracy_client::span!("container tree: remove window");
 
         match target.get_layout() {
             WindowLayout::Tiled => {
                 if let Some(tiled_tree) = &mut self.tiled_tree {
-                    tiled_tree.remove(target, animation_manager);
+                    if scheme.is_automatic() {
+                        tiled_tree.detach(target);
+                        tiled_tree.arrange(scheme, self.root_rect, animation_manager);
+                    } else {
+                        tiled_tree.remove(target, animation_manager);
+                    }
                 } else {
                     error!("the tiled_tree is none");
                 }
@@ -232,73 +262,89 @@ impl ContainerTree {
         self.print_tree();
     }    
 
-    pub fn expansion(&self, animation_manager: &mut AnimationManager) {
-        let _span = tracy_client::span!("container tree: expansion window");
+    /// Lay out the tiled windows again, for a scheme switched at runtime.
+    pub fn arrange(&mut self, scheme: &TiledScheme, animation_manager: &mut AnimationManager) {
+        let _span = tracy_client::span!("container tree: arrange");
 
-        let total = self.windows().count();
-        let max_per_row = 4;
-        let gap = self.gap;
-        let screen = self.root_rect;
+        if let Some(tiled_tree) = &mut self.tiled_tree {
+            tiled_tree.arrange(scheme, self.root_rect, animation_manager);
+        }
 
-        let row_counts = split_rows(total, max_per_row);
-        
         #[cfg(feature = "trace_layout")]
-        info!("expansion row counts: {:?}", row_counts);
+        self.print_tree();
+    }
 
-        let row_count = row_counts.len();
-        let win_height = (screen.size.h - gap * (row_count - 1) as i32) / row_count as i32;
-        
-        let total_gap = gap * (max_per_row + 1 - 1) as i32;
-        let win_width = (screen.size.w - total_gap) / (max_per_row + 1) as i32;
-
-        let mut window_iter = self.windows();
-
-        let mut y = screen.loc.y;
-        for &cols in &row_counts {
-            let total_width = win_width * cols as i32 + total_gap;
-            let start_x = screen.loc.x + (screen.size.w - total_width) / 2;
-
-            for i in 0..cols {
-                let x = start_x + i as i32 * (win_width + gap);
-                let rect = Rectangle {loc: (x, y).into(), size: (win_width, win_height).into()};
-                
-                #[cfg(feature = "trace_layout")]
-                info!("expansion rect: {:?}", rect);
-
-                if let Some(window) = window_iter.next().cloned() {
-                    if window
-                        .user_data()
-                        .get::<ExpansionCache>()
-                        .map(|guard| guard.0.borrow().is_none())
-                        .unwrap_or(true) 
-                    {
-                        let window_rect = window.get_rect().unwrap();
-
-                        // ExpansionCache
-                        let guard = window.user_data().get_or_insert::<ExpansionCache, _>(|| {
-                            ExpansionCache(RefCell::new(Some(rect)))
-                        });
-                        *guard.0.borrow_mut() = Some(rect);
-
-                        animation_manager.add_animation(
-                            window,
-                            window_rect,
-                            rect,
-                            Duration::from_millis(30),
-                            AnimationType::EaseInOutQuad,
-                        );
-                    }
+    pub fn set_mode(&mut self, target: &Window, mode: ContainerMode, animation_manager: &mut AnimationManager) {
+        let _span = tracy_client::span!("container tree: set container mode");
+
+        match target.get_layout() {
+            WindowLayout::Tiled => {
+                if let Some(tiled_tree) = &mut self.tiled_tree {
+                    tiled_tree.set_mode(target, mode, animation_manager);
+                } else {
+                    error!("the tiled_tree is none");
                 }
+            },
+            WindowLayout::Floating => { }
+        }
+
+        #[cfg(feature = "trace_layout")]
+        self.print_tree();
+    }
+
+    pub fn focus_in_group(&mut self, target: &Window, next: bool) -> Option<Window> {
+        match target.get_layout() {
+            WindowLayout::Tiled => self.tiled_tree.as_mut()?.focus_in_group(target, next),
+            WindowLayout::Floating => None,
+        }
+    }
+
+    pub fn reveal(&mut self, target: &Window) {
+        if let Some(tiled_tree) = &mut self.tiled_tree {
+            tiled_tree.reveal(target);
+        }
+    }
+
+    pub fn tab_strips(&self) -> Vec<TabStrip> {
+        self.tiled_tree
+            .as_ref()
+            .map(|tree| tree.tab_strips())
+            .unwrap_or_default()
+    }
+
+    pub fn expansion(&self, windows: &[Window], animation_manager: &mut AnimationManager) {
+        let _span = tracy_client::span!("container tree: expansion window");
+
+        let rects = expansion_rects(windows.len(), self.root_rect, self.gap);
+
+        for (window, rect) in windows.iter().zip(rects) {
+            // ExpansionCache
+            let guard = window.user_data().get_or_insert::<ExpansionCache, _>(ExpansionCache::default);
+
+            let from = match guard.get().or_else(|| window.get_rect()) {
+                Some(from) => from,
+                None => continue,
+            };
+            *guard.0.borrow_mut() = Some(rect);
+
+            if from == rect {
+                continue;
             }
 
-            y += win_height + gap;
+            // clients keep their size, the overview only scales their buffers
+            animation_manager.add_scaled_animation(
+                window.clone(),
+                from,
+                rect,
+                AnimationEvent::Expansion,
+            );
         }
     }
 
-    pub fn recover(&self, animation_manager: &mut AnimationManager) {
+    pub fn recover<'a>(&self, windows: impl Iterator<Item = &'a Window>, animation_manager: &mut AnimationManager) {
         let _span = tracy_client::span!("container tree: recover window");
 
-        for window in self.windows() {
+        for window in windows {
             // set expansion cache
             if let Some(guard) = window.user_data().get::<ExpansionCache>() {
                 if let Some(from) = guard.get() {
@@ -306,12 +352,11 @@ impl ContainerTree {
     
                     guard.0.borrow_mut().take();
     
-                    animation_manager.add_animation(
+                    animation_manager.add_scaled_animation(
                         window.clone(), 
                         from, 
                         to, 
-                        Duration::from_millis(30),
-                        AnimationType::EaseInOutQuad,
+                        AnimationEvent::Expansion,
                     );
                 }
             }
@@ -339,21 +384,3 @@ impl ContainerTree {
         let _ = self.floating.iter().map(|window| info!("Float window: Rect: {:?}", window.get_rect()));
     }
 }
-
-fn split_rows(total: usize, max_per_row: usize) -> Vec<usize> {
-    let rows = (total + max_per_row - 1) / max_per_row;
-    let base = total / rows;
-    let mut remainder = total % rows;
-    let mut result = Vec::new();
-
-    for _ in 0..rows {
-        if remainder > 0 {
-            result.push(base + 1);
-            remainder -= 1;
-        } else {
-            result.push(base);
-        }
-    }
-
-    result
-}
\ No newline at end of file
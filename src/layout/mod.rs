@@ -5,6 +5,7 @@
 use smithay::utils::{Logical, Rectangle};
 
 pub mod container_tree;
+pub mod overview;
 pub mod tiled_tree;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -22,11 +23,121 @@ impl Direction {
     }
 }
 
-#[derive(Debug, Clone)]
+/// How a container lays out its children.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContainerMode {
+    // side by side along the direction
+    Split,
+    // one child shown below a row of tabs
+    Tabbed,
+    // one child shown below a column of title rows
+    Stacked,
+    // one child shown over the whole area
+    Monocle,
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub enum TiledScheme {
+    // split the window under the pointer
     Default,
     #[allow(dead_code)]
     Scroll,
+
+    // the schemes below lay out all windows from their order
+    MasterStack {
+        position: MasterPosition,
+        // windows in the master area
+        count: usize,
+        // share of the master area
+        ratio: f64,
+    },
+    Dwindle,
+    Grid,
+    Monocle,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MasterPosition {
+    Left,
+    Right,
+    Top,
+    // stack windows alternate between both sides
+    Center,
+}
+
+impl TiledScheme {
+    /// Whether the scheme places windows by itself rather than at the pointer.
+    pub fn is_automatic(&self) -> bool {
+        !matches!(self, TiledScheme::Default | TiledScheme::Scroll)
+    }
+
+    // master_stack center 2 0.5
+    pub fn parse(value: &str) -> Option<Self> {
+        let mut parts = value.split_whitespace();
+
+        let scheme = match parts.next()? {
+            "default" => TiledScheme::Default,
+            "dwindle" => TiledScheme::Dwindle,
+            "grid" => TiledScheme::Grid,
+            "monocle" => TiledScheme::Monocle,
+            "master_stack" => {
+                let position = match parts.next().unwrap_or("left") {
+                    "left" => MasterPosition::Left,
+                    "right" => MasterPosition::Right,
+                    "top" => MasterPosition::Top,
+                    "center" => MasterPosition::Center,
+                    _ => return None,
+                };
+                let count = parts.next().map_or(Ok(1), |v| v.parse()).ok()?;
+                let ratio = parts.next().map_or(Ok(0.55), |v| v.parse::<f64>()).ok()?;
+
+                if !(0.05..=0.95).contains(&ratio) {
+                    return None;
+                }
+
+                TiledScheme::MasterStack { position, count, ratio }
+            }
+            _ => return None,
+        };
+
+        // trailing words are a typo rather than something to ignore
+        parts.next().is_none().then_some(scheme)
+    }
+}
+
+/// A split chosen ahead of time on a tiled window, taken by the next window mapped.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Presel {
+    // Left, Right, Top or Bottom of the target
+    pub edge: ResizeEdge,
+    // share of the target's area the new window gets, half if none
+    pub ratio: Option<f64>,
+}
+
+impl Presel {
+    /// The part of `rect` the new window will take.
+    pub fn preview(&self, rect: Rectangle<i32, Logical>) -> Rectangle<i32, Logical> {
+        let ratio = self.ratio.unwrap_or(0.5);
+        let width = (rect.size.w as f64 * ratio).round() as i32;
+        let height = (rect.size.h as f64 * ratio).round() as i32;
+
+        let mut preview = rect;
+        match self.edge {
+            ResizeEdge::Left => preview.size.w = width,
+            ResizeEdge::Right => {
+                preview.loc.x += rect.size.w - width;
+                preview.size.w = width;
+            }
+            ResizeEdge::Top => preview.size.h = height,
+            ResizeEdge::Bottom => {
+                preview.loc.y += rect.size.h - height;
+                preview.size.h = height;
+            }
+            _ => {}
+        }
+
+        preview
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -41,7 +152,7 @@ impl Default for WindowLayout {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum ResizeEdge {
     None,
 
@@ -0,0 +1,155 @@
+use smithay::{desktop::Window, utils::{Logical, Point, Rectangle}};
+
+use crate::{
+    layout::{container_tree::ExpansionCache, ResizeEdge},
+    manager::window::WindowExt,
+};
+
+/// State of the interactive expansion (exposé) mode.
+#[derive(Debug)]
+pub struct Overview {
+    pub all_workspaces: bool,
+    pub filter: String,
+    pub selected: usize,
+
+    // visible windows after filtering, in grid order
+    pub windows: Vec<Window>,
+}
+
+impl Overview {
+    pub fn new(all_workspaces: bool) -> Self {
+        Self {
+            all_workspaces,
+            filter: String::new(),
+            selected: 0,
+            windows: Vec::new(),
+        }
+    }
+
+    pub fn matches(&self, window: &Window) -> bool {
+        if self.filter.is_empty() {
+            return true;
+        }
+
+        let filter = self.filter.to_lowercase();
+        let (title, app_id) = window.get_title_and_id();
+
+        [title, app_id]
+            .into_iter()
+            .flatten()
+            .any(|s| s.to_lowercase().contains(&filter))
+    }
+
+    pub fn selected(&self) -> Option<&Window> {
+        self.windows.get(self.selected)
+    }
+
+    pub fn select(&mut self, window: &Window) {
+        if let Some(idx) = self.windows.iter().position(|w| w == window) {
+            self.selected = idx;
+        }
+    }
+
+    pub fn move_selection(&mut self, edge: &ResizeEdge) {
+        let Some(current) = self.selected().and_then(overview_rect) else {
+            return;
+        };
+        let center = current.loc + current.size.to_point() / 2;
+
+        // nearest window in the given direction, same row or column first
+        let target = self.windows
+            .iter()
+            .enumerate()
+            .filter_map(|(idx, window)| {
+                let rect = overview_rect(window)?;
+                let delta = rect.loc + rect.size.to_point() / 2 - center;
+
+                let (main, cross) = match edge {
+                    ResizeEdge::Left => (-delta.x, delta.y.abs()),
+                    ResizeEdge::Right => (delta.x, delta.y.abs()),
+                    ResizeEdge::Top => (-delta.y, delta.x.abs()),
+                    ResizeEdge::Bottom => (delta.y, delta.x.abs()),
+                    _ => return None,
+                };
+
+                (main > 0).then_some((idx, cross, main))
+            })
+            .min_by_key(|(_, cross, main)| (*cross, *main))
+            .map(|(idx, _, _)| idx);
+
+        if let Some(idx) = target {
+            self.selected = idx;
+        }
+    }
+
+    pub fn window_under(&self, point: Point<f64, Logical>) -> Option<&Window> {
+        self.windows.iter().find(|window| {
+            overview_rect(window)
+                .map(|rect| rect.to_f64().contains(point))
+                .unwrap_or(false)
+        })
+    }
+}
+
+fn overview_rect(window: &Window) -> Option<Rectangle<i32, Logical>> {
+    window.user_data().get::<ExpansionCache>().and_then(|cache| cache.get())
+}
+
+/// Grid rects for `total` windows inside `screen`, at most four per row.
+pub fn expansion_rects(total: usize, screen: Rectangle<i32, Logical>, gap: i32) -> Vec<Rectangle<i32, Logical>> {
+    let mut rects = Vec::with_capacity(total);
+
+    if total == 0 {
+        return rects;
+    }
+
+    let max_per_row = 4;
+    let row_counts = split_rows(total, max_per_row);
+
+    #[cfg(feature = "trace_layout")]
+    info!("expansion row counts: {:?}", row_counts);
+
+    let row_count = row_counts.len();
+    let win_height = (screen.size.h - gap * (row_count - 1) as i32) / row_count as i32;
+
+    let total_gap = gap * (max_per_row + 1 - 1) as i32;
+    let win_width = (screen.size.w - total_gap) / (max_per_row + 1) as i32;
+
+    let mut y = screen.loc.y;
+    for &cols in &row_counts {
+        let total_width = win_width * cols as i32 + total_gap;
+        let start_x = screen.loc.x + (screen.size.w - total_width) / 2;
+
+        for i in 0..cols {
+            let x = start_x + i as i32 * (win_width + gap);
+            let rect = Rectangle { loc: (x, y).into(), size: (win_width, win_height).into() };
+
+            #[cfg(feature = "trace_layout")]
+            info!("expansion rect: {:?}", rect);
+
+            rects.push(rect);
+        }
+
+        y += win_height + gap;
+    }
+
+    rects
+}
+
+fn split_rows(total: usize, max_per_row: usize) -> Vec<usize> {
+    let rows = (total + max_per_row - 1) / max_per_row;
+    let base = total / rows;
+    let mut remainder = total % rows;
+    let mut result = Vec::new();
+
+    for _ in 0..rows {
+        if remainder > 0 {
+            result.push(base + 1);
+            remainder -= 1;
+        } else {
+            result.push(base);
+        }
+    }
+
+    result
+}
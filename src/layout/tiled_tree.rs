@@ -1,15 +1,12 @@
-use std::time::Duration;
-
 use indexmap::IndexMap;
 use slotmap::{new_key_type, SlotMap};
 use smithay::{desktop::Window, utils::{Logical, Rectangle}};
 
 use crate::{
-    layout::Direction, 
+    config::animation::AnimationEvent,
+    layout::{ContainerMode, Direction, MasterPosition, TiledScheme}, 
     manager::{
-        animation::{
-            AnimationManager, AnimationType
-        }, 
+        animation::AnimationManager, 
         window::WindowExt
     }
 };
@@ -23,21 +20,30 @@ pub enum NodeData {
     Node {
         window: Window,
 
-        sibling: NodeId,
         parent: NodeId,
     },
     Container {
         elements: Vec<NodeId>,
+        // share of each element along the direction
+        weights: Vec<f64>,
         rect: Rectangle<i32, Logical>,
-        offset: i32,
 
-        sibling: NodeId,
         parent: NodeId,
 
         direction: Direction,
+        mode: ContainerMode,
+        // the child shown in tabbed and stacked mode
+        active: usize,
     },
 }
 
+/// The tabs of a visible tabbed or stacked container, one per child.
+#[derive(Debug, Clone)]
+pub struct TabStrip {
+    pub tabs: Vec<(Window, Rectangle<i32, Logical>)>,
+    pub active: usize,
+}
+
 
 #[derive(Debug)]
 pub struct TiledTree {
@@ -46,11 +52,19 @@ pub struct TiledTree {
 
     windows: IndexMap<Window, NodeId>,
 
-    gap: i32
+    gap: i32,
+    // height of one tab row
+    tab_height: i32,
 }
 
 impl TiledTree {
-    pub fn new_with_first_node(window: Window, root_rect: Rectangle<i32, Logical>, gap: i32, animation_manager: &mut AnimationManager) -> TiledTree {
+    pub fn new_with_first_node(
+        window: Window,
+        root_rect: Rectangle<i32, Logical>,
+        gap: i32,
+        tab_height: i32,
+        animation_manager: &mut AnimationManager,
+    ) -> TiledTree {
         window.set_rect_cache(root_rect);
         window.send_rect(root_rect);
 
@@ -59,16 +73,14 @@ impl TiledTree {
 
         let first_node = NodeData::Node { 
             window: window.clone(), 
-            sibling: NodeId::default(), 
             parent: NodeId::default()
         };
 
         let first_id = nodes.insert(first_node);
         windows.insert(window.clone(), first_id);
 
-        // set sibling and parent to itself
-        if let Some(NodeData::Node { sibling, parent, .. }) = nodes.get_mut(first_id) {
-            *sibling = first_id;
+        // set parent to itself
+        if let Some(NodeData::Node { parent, .. }) = nodes.get_mut(first_id) {
             *parent = first_id;
         }
 
@@ -80,8 +92,7 @@ impl TiledTree {
             window,
             from,
             root_rect,
-            Duration::from_millis(30),
-            AnimationType::EaseInOutQuad,
+            AnimationEvent::WindowOpen,
         );
 
         Self { 
@@ -90,6 +101,7 @@ impl TiledTree {
 
             windows,
             gap,
+            tab_height,
         }
     }
 
@@ -99,212 +111,194 @@ impl TiledTree {
         direction: Direction, 
         window: Window, 
         is_favour: bool, 
+        ratio: Option<f64>,
         animation_manager: &mut AnimationManager
     ) -> bool {
         /*
-            split new_rect from target nodes,
-            convert target (nodes) into parent (container),
-            insert new_target and old_target
+            ratio is the share of target's area the new node takes, else
+            join the container of target if it already splits in direction,
+            or as a new tab if it is tabbed or stacked,
+            otherwise convert target (node) into a container (target and new node)
         */
 
         let _span = tracy_client::span!("tiled tree: insert new window");
 
-        if let Some(target_id) = self.find_node_id(target).cloned() {
-            if let Some(NodeData::Node { window: old_window, sibling: old_sibling, parent: old_parent }) = self.nodes.get(target_id) {
-                let old_window = old_window.clone();
-                let old_sibling = old_sibling.clone();
-                let old_parent = old_parent.clone();
-
-                let old_rect = old_window.get_rect().unwrap();
-
-                // get new rect
-                let (target_rect, new_rect) = split_rect(old_rect, direction, 0, self.gap, is_favour);
-                old_window.set_rect_cache(target_rect);
-                window.set_rect_cache(new_rect);
-
-                // insert target_copy and new nodes
-                let target_copy_id = self.nodes.insert(
-                    NodeData::Node { 
-                        window: old_window.clone(), 
-                        sibling: NodeId::default(), 
-                        parent: target_id
-                    }  
-                );
-
-                let new_id = self.nodes.insert(
-                    NodeData::Node { 
-                        window: window.clone(), 
-                        sibling: target_copy_id, 
-                        parent: target_id 
-                    }
-                );
-
-                self.windows.insert(old_window.clone(), target_copy_id);
-                self.windows.insert(window.clone(), new_id);
+        let Some(target_id) = self.find_node_id(target).cloned() else {
+            return false;
+        };
+        let Some(NodeData::Node { window: old_window, parent: old_parent }) = self.nodes.get(target_id) else {
+            return false;
+        };
+        let old_window = old_window.clone();
+        let old_parent = old_parent.clone();
 
-                if let Some(NodeData::Node { sibling, .. }) = self.nodes.get_mut(target_copy_id) {
-                    *sibling = new_id;
-                }
+        let old_rect = old_window.get_rect().unwrap();
+        window.set_rect_cache(old_rect);
 
-                // convert target from node to container inplace
-                let mut elements = vec![];
+        let joins = target_id != self.root && matches!(
+            self.nodes.get(old_parent),
+            Some(NodeData::Container { direction: parent_direction, mode, .. })
+                if *mode != ContainerMode::Split || *parent_direction == direction
+        );
 
-                if is_favour {
-                    elements.push(new_id);
-                    elements.push(target_copy_id);
-                }else {
-                    elements.push(target_copy_id);
-                    elements.push(new_id);
+        let container_id = if joins {
+            let new_id = self.nodes.insert(
+                NodeData::Node { 
+                    window: window.clone(), 
+                    parent: old_parent 
                 }
+            );
+            self.windows.insert(window.clone(), new_id);
 
-                if let Some(target_data) = self.nodes.get_mut(target_id) {
-                    *target_data = NodeData::Container { 
-                        elements, 
-                        rect: old_rect,
-                        offset: 0,
-                        sibling: old_sibling, 
-                        parent: old_parent, 
-                        direction 
-                    };
-                }
+            if let Some(NodeData::Container { elements, weights, mode, active, .. }) = self.nodes.get_mut(old_parent) {
+                let target_idx = elements.iter().position(|id| *id == target_id).unwrap_or(0);
 
-                // add animation
-                {
-                    // target node
-                    animation_manager.add_animation(
-                        old_window,
-                        old_rect,
-                        target_rect,
-                        Duration::from_millis(15),
-                        AnimationType::EaseInOutQuad,
-                    );
+                // tabs always follow the target
+                let idx = if is_favour && *mode == ContainerMode::Split { target_idx } else { target_idx + 1 };
 
-                    // new node
-                    let mut from = new_rect;
-                    if matches!(direction, Direction::Horizontal) {
-                        if is_favour {
-                            from.loc.x -= from.size.w;
-                        } else {
-                            from.loc.x += from.size.w;
-                        }
-                    } else if matches!(direction, Direction::Vertical){
-                        if is_favour {
-                            from.loc.y -= from.size.h;
-                        } else {
-                            from.loc.y += from.size.h;
-                        }
+                // as large as the target, so equal siblings stay equal
+                let target_weight = weights[target_idx];
+                let weight = match ratio {
+                    Some(ratio) => {
+                        weights[target_idx] = target_weight * (1.0 - ratio);
+                        target_weight * ratio
                     }
+                    None => target_weight,
+                };
+                elements.insert(idx, new_id);
+                weights.insert(idx, weight);
+                *active = idx;
+            }
 
-                    animation_manager.add_animation(
-                        window,
-                        from,
-                        new_rect,
-                        Duration::from_millis(45),
-                        AnimationType::OvershootBounce,
-                    );
-                    
+            old_parent
+        } else {
+            // insert target_copy and new nodes
+            let target_copy_id = self.nodes.insert(
+                NodeData::Node { 
+                    window: old_window.clone(), 
+                    parent: target_id
+                }  
+            );
+
+            let new_id = self.nodes.insert(
+                NodeData::Node { 
+                    window: window.clone(), 
+                    parent: target_id 
                 }
+            );
+
+            self.windows.insert(old_window.clone(), target_copy_id);
+            self.windows.insert(window.clone(), new_id);
+
+            // convert target from node to container inplace
+            let ratio = ratio.unwrap_or(0.5);
+            let (elements, weights) = if is_favour {
+                (vec![new_id, target_copy_id], vec![ratio, 1.0 - ratio])
+            } else {
+                (vec![target_copy_id, new_id], vec![1.0 - ratio, ratio])
+            };
+
+            // the new window is the active one
+            let active = elements.iter().position(|id| *id == new_id).unwrap_or(0);
+
+            self.nodes[target_id] = NodeData::Container { 
+                elements, 
+                weights,
+                rect: old_rect,
+                parent: old_parent, 
+                direction,
+                mode: ContainerMode::Split,
+                active,
+            };
+
+            target_id
+        };
+
+        let container_rect = match self.nodes.get(container_id) {
+            Some(NodeData::Container { rect, .. }) => *rect,
+            _ => old_rect,
+        };
+        self.update_rect_recursive(container_id, container_rect, animation_manager);
+
+        // the new node slides in from its side
+        if let Some(new_rect) = window.get_rect() {
+            let mut from = new_rect;
+            match (direction, is_favour) {
+                (Direction::Horizontal, true) => from.loc.x -= from.size.w,
+                (Direction::Horizontal, false) => from.loc.x += from.size.w,
+                (Direction::Vertical, true) => from.loc.y -= from.size.h,
+                (Direction::Vertical, false) => from.loc.y += from.size.h,
             }
-            return true;
+
+            animation_manager.add_animation(
+                window,
+                from,
+                new_rect,
+                AnimationEvent::WindowOpen,
+            );
         }
 
-        false
+        self.refresh_visibility();
+
+        true
     }
     
     pub fn remove(&mut self, target: &Window, animation_manager: &mut AnimationManager) {
         /*
-            convert parent (Container) into sibling (Node),
-            inherit parent's parent and sibling,
-            use parent's rect,
-            delete target and old_sibling node
+            remove target from parent (Container),
+            a container left with one element is replaced by it,
+            which merges into the parent's parent if both split the same way,
+            relayout what is left
         */
 
         let _span = tracy_client::span!("tiled tree: remove window");
 
-        if let Some(target_id) = self.find_node_id(target).cloned() {
-            if let Some(NodeData::Node { sibling: target_sibling, parent: target_parent, .. }) = self.nodes.get(target_id) {
-                // only root node
-                if target_id == self.root {
-                    self.nodes.remove(target_id);
-                    self.windows.shift_remove(target);
+        // a window leaving the tree is never behind a tab
+        target.set_hidden(false);
 
-                    return;
-                }
+        let Some(target_id) = self.find_node_id(target).cloned() else {
+            return;
+        };
+        self.windows.shift_remove(target);
 
-                let target_parent = target_parent.clone();
-                let target_sibling = target_sibling.clone();
-
-                // convert parent into sibling node
-                // use container's sibling & parent
-                if let Some(sibling_data) = self.nodes.get(target_sibling) {
-                    match sibling_data {
-                        NodeData::Node { window: sibling_window, .. } => {
-                            let sibling_window = sibling_window.clone();
-
-                            if let Some(NodeData::Container { rect: parent_rect, sibling: parent_sibling, parent: parent_parent, .. }) = self.nodes.get(target_parent) {
-                                let sibling_window = sibling_window.clone();
-                                let parent_rect = parent_rect.clone();
-
-                                // merge target rect and sibling rect
-                                let sibling_rect = sibling_window.get_rect().unwrap();
-                                sibling_window.set_rect_cache(parent_rect.clone());
-
-                                self.windows.insert(sibling_window.clone(), target_parent);
-                                self.nodes[target_parent] = NodeData::Node {
-                                    window: sibling_window.clone(),
-                                    sibling: parent_sibling.clone(),
-                                    parent: parent_parent.clone(),
-                                };
-
-                                // add animation
-                                animation_manager.add_animation(
-                                    sibling_window.clone(),
-                                    sibling_rect,
-                                    parent_rect,
-                                    Duration::from_millis(30),
-                                    AnimationType::EaseInOutQuad,
-                                );
-                            }
-
-                            // remove old_sibling nodes
-                            self.windows.insert(sibling_window.clone(), target_parent);
-                        }
-                        NodeData::Container { offset: sibling_offset, elements: sibling_elements, direction: sibling_direction, .. } => {
-                            if let Some(NodeData::Container { rect: parent_rect, sibling: parent_sibling, parent: parent_parent, .. }) = self.nodes.get(target_parent) {
-                                let sibling_elements = sibling_elements.clone();
-                                let parent_rect = parent_rect.clone();
-                                
-                                self.nodes[target_parent] = NodeData::Container { 
-                                    elements: sibling_elements.clone(), 
-                                    rect: parent_rect.clone(), 
-                                    offset: sibling_offset.clone(),
-
-                                    sibling: parent_sibling.clone(), 
-                                    parent: parent_parent.clone(), 
-                                    direction: sibling_direction.clone()
-                                };
-
-                                self.update_rect_recursive(target_parent, parent_rect, animation_manager);
-
-                                sibling_elements.iter().for_each(|node_id| {
-                                    if let Some(node_data) = self.nodes.get_mut(*node_id) {
-                                        match node_data {
-                                            NodeData::Node { parent, .. } => *parent = target_parent,
-                                            NodeData::Container { parent, .. } => *parent = target_parent,
-                                        }
-                                    }
-                                });
-                            }
-                        }
-                    }
-                }
+        // only root node
+        if target_id == self.root {
+            self.nodes.remove(target_id);
+            return;
+        }
+
+        let parent_id = self.parent_of(target_id);
+        self.nodes.remove(target_id);
 
-                // remove from nodes and windows
-                self.nodes.remove(target_id);
-                self.nodes.remove(target_sibling);
-                self.windows.shift_remove(target);
+        let Some(NodeData::Container { elements, weights, rect, active, .. }) = self.nodes.get_mut(parent_id) else {
+            return;
+        };
+        let parent_rect = *rect;
+
+        if let Some(idx) = elements.iter().position(|id| *id == target_id) {
+            elements.remove(idx);
+            weights.remove(idx);
+
+            // the next tab takes the place of a removed active one
+            if *active > idx || *active >= elements.len() {
+                *active = active.saturating_sub(1);
             }
         }
+
+        let only = (elements.len() == 1).then(|| elements[0]);
+        let relayout = match only {
+            Some(only) => self.collapse(parent_id, only),
+            None => parent_id,
+        };
+
+        // a collapsed container takes the rect of the one it replaced
+        let rect = match self.nodes.get(relayout) {
+            Some(NodeData::Container { rect, .. }) if relayout != parent_id => *rect,
+            _ => parent_rect,
+        };
+        self.update_rect_recursive(relayout, rect, animation_manager);
+
+        self.refresh_visibility();
     }
 
     pub fn windows(&self) -> impl Iterator<Item = &Window> {
@@ -369,6 +363,11 @@ impl TiledTree {
                         self.windows.insert(target_window_copy.clone(), neighbor_id);
                         self.windows.insert(neighbor_window.clone(), target_id);
 
+                        // automatic schemes lay out by this order
+                        if let (Some(a), Some(b)) = (self.windows.get_index_of(&target_window_copy), self.windows.get_index_of(neighbor_window)) {
+                            self.windows.swap_indices(a, b);
+                        }
+
                         neighbor_window_copy = Some(neighbor_window.clone());
                         *neighbor_window = target_window_copy.clone();
                     }
@@ -392,16 +391,17 @@ impl TiledTree {
                         target_window_copy,
                         target_rect,
                         neighbor_rect,
-                        Duration::from_millis(30),
-                        AnimationType::EaseInOutQuad,
+                        AnimationEvent::WindowMove,
                     );
                     animation_manager.add_animation(
                         neighbor_window_copy,
                         neighbor_rect,
                         target_rect,
-                        Duration::from_millis(30),
-                        AnimationType::EaseInOutQuad,
+                        AnimationEvent::WindowMove,
                     );
+
+                    // the moved window stays in sight
+                    self.reveal(&target_window_copy);
                 }
             }
         }
@@ -411,7 +411,7 @@ impl TiledTree {
         /*
             find the target nodes and resize target nodes,
             get the max container,
-            resize the max container's elements
+            move the border between the target's element and its neighbor
         */
 
         let _span = tracy_client::span!("tiled tree: resize window");
@@ -421,21 +421,38 @@ impl TiledTree {
                 return;
             }
 
-            if let Some((max_parent_id, _)) = self.find_neighbor(target_id, direction, is_favour) {
-                if let Some(NodeData::Container { rect, offset: parent_offset, .. }) = self.nodes.get_mut(max_parent_id) {
+            if let Some((max_parent_id, neighbor_id)) = self.find_neighbor(target_id, direction, is_favour) {
+                if let Some(NodeData::Container { elements, weights, rect, .. }) = self.nodes.get_mut(max_parent_id) {
                     let rect = rect.clone();
-                    // TODO: use client's given
-                    let min = 175;
+                    let Some(neighbor_idx) = elements.iter().position(|id| *id == neighbor_id) else {
+                        return;
+                    };
 
-                    let half = match direction {
-                        Direction::Horizontal => {
-                            (rect.size.w - self.gap) / 2 - min
-                        }
-                        Direction::Vertical => {
-                            (rect.size.h - self.gap) / 2 - min
-                        }
+                    // elements on both sides of the border
+                    let (first, second) = if is_favour {
+                        (neighbor_idx, neighbor_idx + 1)
+                    } else {
+                        (neighbor_idx - 1, neighbor_idx)
                     };
-                    *parent_offset = (*parent_offset + offset).clamp(-half, half);
+
+                    let length = match direction {
+                        Direction::Horizontal => rect.size.w,
+                        Direction::Vertical => rect.size.h,
+                    } - self.gap * (elements.len() as i32 - 1);
+                    let pixels_per_weight = length as f64 / weights.iter().sum::<f64>();
+
+                    // TODO: use client's given
+                    let min = 175.0;
+
+                    let first_size = weights[first] * pixels_per_weight;
+                    let both = first_size + weights[second] * pixels_per_weight;
+                    if both < min * 2.0 {
+                        return;
+                    }
+
+                    let first_size = (first_size + offset as f64).clamp(min, both - min);
+                    weights[first] = first_size / pixels_per_weight;
+                    weights[second] = (both - first_size) / pixels_per_weight;
 
                     self.update_rect_recursive_without_animation(max_parent_id, rect);
                 }
@@ -467,8 +484,9 @@ impl TiledTree {
                 }  
             };
 
-            if let Some(NodeData::Container { elements, direction: parent_direction, .. }) = self.nodes.get(parent) {
-                if direction == *parent_direction {
+            if let Some(NodeData::Container { elements, direction: parent_direction, mode, .. }) = self.nodes.get(parent) {
+                // tabs have no neighbors on screen
+                if direction == *parent_direction && *mode == ContainerMode::Split {
                     if let Some(idx) = elements.iter().position(|id| *id == node_id) {
                         let neighbor = if is_favour {
                             idx.checked_sub(1).and_then(|i| elements.get(i))
@@ -511,17 +529,17 @@ impl TiledTree {
             return Some(node_id);
         }
 
-        else if let Some(NodeData::Container { elements, direction: container_direction, .. }) = self.nodes.get(node_id) {
-            if &direction == container_direction {
-                if is_favour {
-                    // invert because we need neighbor
-                    return self.find_node_in_container(elements[1], direction, origin_idx, is_favour);
-                } else {
-                    return self.find_node_in_container(elements[0], direction, origin_idx, is_favour);
-                }
+        else if let Some(NodeData::Container { elements, direction: container_direction, mode, active, .. }) = self.nodes.get(node_id) {
+            let child = if *mode != ContainerMode::Split {
+                elements.get(*active)
+            } else if &direction == container_direction {
+                // invert because we need neighbor
+                if is_favour { elements.last() } else { elements.first() }
             } else {
-                return self.find_node_in_container(elements[origin_idx], direction, origin_idx, is_favour);
-            }
+                elements.get(origin_idx).or(elements.last())
+            };
+
+            return child.and_then(|child| self.find_node_in_container(*child, direction, origin_idx, is_favour));
         }
 
         None
@@ -546,25 +564,43 @@ impl TiledTree {
                         window,
                         old_rect,
                         new_rect,
-                        Duration::from_millis(30),
-                        AnimationType::EaseInOutQuad,
+                        AnimationEvent::WindowResize,
                     );
                 }
 
-                NodeData::Container { elements, rect, offset, direction, .. } => {
+                NodeData::Container { elements, weights, rect, direction, mode, active, .. } => {
                     *rect = new_rect;
 
-                    let (rect_1, rect_2) = split_rect(new_rect, direction.clone(), offset.clone(), self.gap, false);
-                    
+                    let sub_rects = child_rects(new_rect, *direction, weights, *mode, self.gap, self.tab_height);
+                    let (mode, active) = (*mode, *active);
+
                     let children = elements.clone();
-                    for (child_id, sub_rect) in children.into_iter().zip([rect_1, rect_2]) {
-                        self.update_rect_recursive(child_id, sub_rect, animation_manager);
+                    for (idx, (child_id, sub_rect)) in children.into_iter().zip(sub_rects).enumerate() {
+                        // windows behind the active tab are not drawn, so their animations never tick
+                        if mode == ContainerMode::Split || idx == active {
+                            self.update_rect_recursive(child_id, sub_rect, animation_manager);
+                        } else {
+                            self.stop_animation_recursive(child_id, animation_manager);
+                            self.update_rect_recursive_without_animation(child_id, sub_rect);
+                        }
                     }
                 }
             }
         }
     }
 
+    fn stop_animation_recursive(&self, node_id: NodeId, animation_manager: &mut AnimationManager) {
+        match self.nodes.get(node_id) {
+            Some(NodeData::Node { window, .. }) => animation_manager.stop_animation(window),
+            Some(NodeData::Container { elements, .. }) => {
+                for child_id in elements {
+                    self.stop_animation_recursive(*child_id, animation_manager);
+                }
+            }
+            None => {}
+        }
+    }
+
     fn update_rect_recursive_without_animation(&mut self, node_id: NodeId, new_rect: Rectangle<i32, Logical>) {
         let _span = tracy_client::span!("tiled tree: update_rect_recursive_without_animation");
 
@@ -575,13 +611,13 @@ impl TiledTree {
                     window.send_rect(new_rect);
                 }
 
-                NodeData::Container { elements, rect, offset, direction, .. } => {
+                NodeData::Container { elements, weights, rect, direction, mode, .. } => {
                     *rect = new_rect;
 
-                    let (rect_1, rect_2) = split_rect(new_rect, direction.clone(), offset.clone(), self.gap, false);
+                    let sub_rects = child_rects(new_rect, *direction, weights, *mode, self.gap, self.tab_height);
                     
                     let children = elements.clone();
-                    for (child_id, sub_rect) in children.into_iter().zip([rect_1, rect_2]) {
+                    for (child_id, sub_rect) in children.into_iter().zip(sub_rects) {
                         self.update_rect_recursive_without_animation(child_id, sub_rect);
                     }
                 }
@@ -593,19 +629,333 @@ impl TiledTree {
         self.windows.get(target)
     }
 
+    fn parent_of(&self, node_id: NodeId) -> NodeId {
+        match self.nodes.get(node_id) {
+            Some(NodeData::Node { parent, .. }) | Some(NodeData::Container { parent, .. }) => *parent,
+            None => self.root,
+        }
+    }
+
+    fn set_parent(&mut self, node_id: NodeId, new_parent: NodeId) {
+        match self.nodes.get_mut(node_id) {
+            Some(NodeData::Node { parent, .. }) | Some(NodeData::Container { parent, .. }) => *parent = new_parent,
+            None => {}
+        }
+    }
+
+    /// Replace a container by its only child in place, returning the node to relayout.
+    fn collapse(&mut self, container_id: NodeId, child_id: NodeId) -> NodeId {
+        let container_parent = self.parent_of(container_id);
+
+        let Some(mut child) = self.nodes.remove(child_id) else {
+            return container_id;
+        };
+
+        match &mut child {
+            NodeData::Node { window, parent } => {
+                *parent = container_parent;
+                self.windows.insert(window.clone(), container_id);
+            }
+            NodeData::Container { elements, parent, .. } => {
+                *parent = container_parent;
+                for element in elements.clone() {
+                    self.set_parent(element, container_id);
+                }
+            }
+        }
+        self.nodes[container_id] = child;
+
+        self.merge_into_parent(container_id)
+    }
+
+    /// Splice a split container into its parent when both split in the same direction.
+    fn merge_into_parent(&mut self, container_id: NodeId) -> NodeId {
+        if container_id == self.root {
+            return container_id;
+        }
+
+        let parent_id = self.parent_of(container_id);
+        let same_split = matches!(
+            (self.nodes.get(container_id), self.nodes.get(parent_id)),
+            (
+                Some(NodeData::Container { direction: a, mode: ContainerMode::Split, .. }),
+                Some(NodeData::Container { direction: b, mode: ContainerMode::Split, .. }),
+            ) if a == b
+        );
+        if !same_split {
+            return container_id;
+        }
+
+        let Some(NodeData::Container { elements: children, weights: child_weights, .. }) = self.nodes.remove(container_id) else {
+            return container_id;
+        };
+        for child in &children {
+            self.set_parent(*child, parent_id);
+        }
+
+        if let Some(NodeData::Container { elements, weights, .. }) = self.nodes.get_mut(parent_id) {
+            if let Some(idx) = elements.iter().position(|id| *id == container_id) {
+                // the children share the weight of the container they leave
+                let share = weights[idx] / child_weights.iter().sum::<f64>();
+
+                elements.splice(idx..=idx, children);
+                weights.splice(idx..=idx, child_weights.into_iter().map(|weight| weight * share));
+            }
+        }
+
+        parent_id
+    }
+
+    /// The first window in the subtree of `node_id`.
+    fn first_window(&self, node_id: NodeId) -> Option<Window> {
+        match self.nodes.get(node_id)? {
+            NodeData::Node { window, .. } => Some(window.clone()),
+            NodeData::Container { elements, active, mode, .. } => {
+                // a group is entered at the tab it shows
+                let child = match mode {
+                    ContainerMode::Split => elements.first(),
+                    _ => elements.get(*active),
+                };
+                self.first_window(*child?)
+            }
+        }
+    }
+
+    pub fn set_mode(&mut self, target: &Window, mode: ContainerMode, animation_manager: &mut AnimationManager) {
+        /*
+            switch the parent container of target into mode,
+            or back to split if it already is,
+            the target becomes the shown child
+        */
+
+        let _span = tracy_client::span!("tiled tree: set container mode");
+
+        let Some(target_id) = self.find_node_id(target).cloned() else {
+            return;
+        };
+        if target_id == self.root {
+            return;
+        }
+
+        let parent_id = self.parent_of(target_id);
+        if let Some(NodeData::Container { elements, rect, mode: current, active, .. }) = self.nodes.get_mut(parent_id) {
+            *current = if *current == mode { ContainerMode::Split } else { mode };
+            if let Some(idx) = elements.iter().position(|id| *id == target_id) {
+                *active = idx;
+            }
+
+            let rect = *rect;
+            self.update_rect_recursive(parent_id, rect, animation_manager);
+        }
+
+        self.refresh_visibility();
+    }
+
+    /// Show the next or previous tab of the nearest tabbed or stacked container around
+    /// target, returning the window to focus.
+    pub fn focus_in_group(&mut self, target: &Window, next: bool) -> Option<Window> {
+        let _span = tracy_client::span!("tiled tree: focus in group");
+
+        let mut child_id = self.find_node_id(target).cloned()?;
+        while child_id != self.root {
+            let parent_id = self.parent_of(child_id);
+
+            if let Some(NodeData::Container { elements, mode, active, .. }) = self.nodes.get_mut(parent_id) {
+                if *mode != ContainerMode::Split {
+                    let idx = elements.iter().position(|id| *id == child_id)?;
+                    let count = elements.len();
+                    *active = if next { (idx + 1) % count } else { (idx + count - 1) % count };
+
+                    let shown = elements[*active];
+                    self.refresh_visibility();
+                    return self.first_window(shown);
+                }
+            }
+
+            child_id = parent_id;
+        }
+
+        None
+    }
+
+    /// Make every tabbed or stacked container around target show it.
+    pub fn reveal(&mut self, target: &Window) {
+        let Some(mut child_id) = self.find_node_id(target).cloned() else {
+            return;
+        };
+
+        while child_id != self.root {
+            let parent_id = self.parent_of(child_id);
+
+            if let Some(NodeData::Container { elements, active, .. }) = self.nodes.get_mut(parent_id) {
+                if let Some(idx) = elements.iter().position(|id| *id == child_id) {
+                    *active = idx;
+                }
+            }
+
+            child_id = parent_id;
+        }
+
+        self.refresh_visibility();
+    }
+
+    /// Rebuild the tree from the window order for the automatic schemes.
+    pub fn arrange(&mut self, scheme: &TiledScheme, root_rect: Rectangle<i32, Logical>, animation_manager: &mut AnimationManager) {
+        let _span = tracy_client::span!("tiled tree: arrange");
+
+        let windows: Vec<Window> = self.windows.keys().cloned().collect();
+        if windows.is_empty() {
+            return;
+        }
+
+        let Some(shape) = Shape::of(scheme, windows) else {
+            // manual schemes keep the tree, only monocle means nothing there
+            for node in self.nodes.values_mut() {
+                if let NodeData::Container { mode: mode @ ContainerMode::Monocle, .. } = node {
+                    *mode = ContainerMode::Split;
+                }
+            }
+
+            self.update_rect_recursive(self.root, root_rect, animation_manager);
+            self.refresh_visibility();
+            return;
+        };
+
+        self.nodes.clear();
+        self.root = self.build(shape, None);
+        self.update_rect_recursive(self.root, root_rect, animation_manager);
+
+        self.refresh_visibility();
+    }
+
+    /// Append a window to the order, `arrange` places it.
+    pub fn push(&mut self, window: Window, root_rect: Rectangle<i32, Logical>) {
+        // where the open animation starts from
+        let mut from = root_rect;
+        from.loc.y += from.size.h;
+        window.set_rect_cache(from);
+
+        self.windows.insert(window, NodeId::default());
+    }
+
+    /// Drop a window from the order, `arrange` closes the gap.
+    pub fn detach(&mut self, target: &Window) {
+        target.set_hidden(false);
+        self.windows.shift_remove(target);
+    }
+
+    fn build(&mut self, shape: Shape, parent: Option<NodeId>) -> NodeId {
+        let node_id = match shape {
+            Shape::Leaf(window) => {
+                let node_id = self.nodes.insert(NodeData::Node { window: window.clone(), parent: NodeId::default() });
+                self.windows.insert(window, node_id);
+                node_id
+            }
+            Shape::Container(direction, mode, children) => {
+                let node_id = self.nodes.insert(NodeData::Container {
+                    elements: vec![],
+                    weights: vec![],
+                    rect: Rectangle::default(),
+                    parent: NodeId::default(),
+                    direction,
+                    mode,
+                    // the newest window is shown
+                    active: children.len().saturating_sub(1),
+                });
+
+                let mut ids = vec![];
+                let mut shares = vec![];
+                for (child, weight) in children {
+                    ids.push(self.build(child, Some(node_id)));
+                    shares.push(weight);
+                }
+
+                if let Some(NodeData::Container { elements, weights, .. }) = self.nodes.get_mut(node_id) {
+                    *elements = ids;
+                    *weights = shares;
+                }
+
+                node_id
+            }
+        };
+
+        // the root is its own parent
+        self.set_parent(node_id, parent.unwrap_or(node_id));
+        node_id
+    }
+
+    /// Tabs of the visible tabbed and stacked containers.
+    pub fn tab_strips(&self) -> Vec<TabStrip> {
+        let mut strips = vec![];
+        self.collect_tab_strips(self.root, &mut strips);
+        strips
+    }
+
+    fn collect_tab_strips(&self, node_id: NodeId, strips: &mut Vec<TabStrip>) {
+        let Some(NodeData::Container { elements, rect, mode, active, .. }) = self.nodes.get(node_id) else {
+            return;
+        };
+
+        match mode {
+            ContainerMode::Split => {
+                for child_id in elements {
+                    self.collect_tab_strips(*child_id, strips);
+                }
+            }
+            ContainerMode::Monocle => {
+                if let Some(child_id) = elements.get(*active) {
+                    self.collect_tab_strips(*child_id, strips);
+                }
+            }
+            ContainerMode::Tabbed | ContainerMode::Stacked => {
+                let tabs = elements
+                    .iter()
+                    .enumerate()
+                    .filter_map(|(idx, child_id)| {
+                        let window = self.first_window(*child_id)?;
+                        Some((window, tab_rect(*rect, *mode, elements.len(), idx, self.tab_height)))
+                    })
+                    .collect();
+                strips.push(TabStrip { tabs, active: *active });
+
+                if let Some(child_id) = elements.get(*active) {
+                    self.collect_tab_strips(*child_id, strips);
+                }
+            }
+        }
+    }
+
+    /// Hide the windows behind the active tab of their containers.
+    fn refresh_visibility(&self) {
+        fn visit(nodes: &SlotMap<NodeId, NodeData>, node_id: NodeId, visible: bool) {
+            match nodes.get(node_id) {
+                Some(NodeData::Node { window, .. }) => window.set_hidden(!visible),
+                Some(NodeData::Container { elements, mode, active, .. }) => {
+                    for (idx, child_id) in elements.iter().enumerate() {
+                        let shown = *mode == ContainerMode::Split || idx == *active;
+                        visit(nodes, *child_id, visible && shown);
+                    }
+                }
+                None => {}
+            }
+        }
+
+        visit(&self.nodes, self.root, true);
+    }
+
     #[cfg(feature = "trace_layout")]
     pub fn print_tree(&self) {
         fn print(nodes: &SlotMap<NodeId, NodeData>, windows: &IndexMap<Window, NodeId>, id: NodeId, depth: usize) {
             let indent = "  ".repeat(depth);
 
             match &nodes[id] {
-                NodeData::Node { window, sibling, parent, .. } => {
+                NodeData::Node { window, parent, .. } => {
                     let window_rect = window.get_rect();
-                    info!("{indent}- Leaf: {:?} - Rect: {:?} - Sib: {:?} - Parent: {:?}", id, window_rect, sibling, parent);
+                    info!("{indent}- Leaf: {:?} - Rect: {:?} - Parent: {:?}", id, window_rect, parent);
                 }
 
-                NodeData::Container { elements, direction, .. } => {
-                    info!("{indent}- Split: {:?} - Direction: {:?}", id, direction);
+                NodeData::Container { elements, weights, direction, mode, .. } => {
+                    info!("{indent}- {:?}: {:?} - Direction: {:?} - Weights: {:?}", mode, id, direction, weights);
 
                     for child_id in elements {
                         print(nodes, windows, *child_id, depth + 1);
@@ -618,54 +968,189 @@ impl TiledTree {
     }
 }
 
-fn split_rect(
-    target_rect: Rectangle<i32, Logical>, 
-    direction: Direction, 
-    offset: i32, 
-    gap: i32, 
-    is_favour: bool
-) -> (Rectangle<i32, Logical>, Rectangle<i32, Logical>) {
-    let mut target_rect = target_rect.clone();
-    let mut new_rect = target_rect.clone();
+/// The tree an automatic scheme gives its windows, weights are relative shares.
+enum Shape {
+    Leaf(Window),
+    Container(Direction, ContainerMode, Vec<(Shape, f64)>),
+}
 
-    match direction {
-        Direction::Horizontal => {
-            let half = (target_rect.size.w - gap) / 2;
+impl Shape {
+    fn of(scheme: &TiledScheme, windows: Vec<Window>) -> Option<Shape> {
+        let shape = match scheme {
+            TiledScheme::Default | TiledScheme::Scroll => return None,
+            TiledScheme::Monocle => Shape::group(Direction::Horizontal, ContainerMode::Monocle, windows),
+            TiledScheme::Dwindle => Shape::dwindle(windows, Direction::Horizontal),
+            TiledScheme::Grid => {
+                let columns = (windows.len() as f64).sqrt().ceil() as usize;
+
+                let rows = windows
+                    .chunks(columns)
+                    .map(|row| Shape::group(Direction::Horizontal, ContainerMode::Split, row.to_vec()))
+                    .collect();
+                Shape::split(Direction::Vertical, rows)
+            }
+            TiledScheme::MasterStack { position, count, ratio } => {
+                let mut stack = windows;
+                let count = (*count).clamp(1, stack.len());
+                let masters: Vec<Window> = stack.drain(..count).collect();
+
+                let (outer, inner) = match position {
+                    MasterPosition::Top => (Direction::Vertical, Direction::Horizontal),
+                    _ => (Direction::Horizontal, Direction::Vertical),
+                };
+                let master = Shape::group(inner, ContainerMode::Split, masters);
+
+                if stack.is_empty() {
+                    return Some(master);
+                }
 
-            new_rect.size.w = half;
-            target_rect.size.w = half;
+                match position {
+                    MasterPosition::Left | MasterPosition::Top => Shape::Container(outer, ContainerMode::Split, vec![
+                        (master, *ratio),
+                        (Shape::group(inner, ContainerMode::Split, stack), 1.0 - ratio),
+                    ]),
+                    MasterPosition::Right => Shape::Container(outer, ContainerMode::Split, vec![
+                        (Shape::group(inner, ContainerMode::Split, stack), 1.0 - ratio),
+                        (master, *ratio),
+                    ]),
+                    MasterPosition::Center => {
+                        // the first stack window goes right, then they alternate
+                        let (right, left): (Vec<(usize, Window)>, Vec<(usize, Window)>) = stack
+                            .into_iter()
+                            .enumerate()
+                            .partition(|(idx, _)| idx % 2 == 0);
+                        let side = (1.0 - ratio) / 2.0;
+
+                        let mut children = vec![];
+                        if !left.is_empty() {
+                            let left = left.into_iter().map(|(_, window)| window).collect();
+                            children.push((Shape::group(inner, ContainerMode::Split, left), side));
+                        }
+                        children.push((master, *ratio));
+                        let right = right.into_iter().map(|(_, window)| window).collect();
+                        children.push((Shape::group(inner, ContainerMode::Split, right), side));
 
-            if is_favour {
-                target_rect.loc.x += half + gap + offset;
+                        Shape::Container(outer, ContainerMode::Split, children)
+                    }
+                }
+            }
+        };
 
-                target_rect.size.w -= offset;
-                new_rect.size.w += offset;
-            } else {
-                new_rect.loc.x += half + gap + offset;
+        Some(shape)
+    }
 
-                new_rect.size.w -= offset;
-                target_rect.size.w += offset;
-            }
+    /// Equal shares of windows, a single one is not wrapped in a container.
+    fn group(direction: Direction, mode: ContainerMode, windows: Vec<Window>) -> Shape {
+        Shape::split_with_mode(direction, mode, windows.into_iter().map(Shape::Leaf).collect())
+    }
+
+    fn split(direction: Direction, children: Vec<Shape>) -> Shape {
+        Shape::split_with_mode(direction, ContainerMode::Split, children)
+    }
+
+    fn split_with_mode(direction: Direction, mode: ContainerMode, mut children: Vec<Shape>) -> Shape {
+        if children.len() == 1 {
+            return children.remove(0);
         }
-        Direction::Vertical => {
-            let half = (target_rect.size.h - gap) / 2;
 
-            new_rect.size.h = half;
-            target_rect.size.h = half;
+        Shape::Container(direction, mode, children.into_iter().map(|child| (child, 1.0)).collect())
+    }
 
-            if is_favour {
-                target_rect.loc.y += half + gap + offset;
+    // each window takes half of what is left, turning at every step
+    fn dwindle(mut windows: Vec<Window>, direction: Direction) -> Shape {
+        let first = windows.remove(0);
+        if windows.is_empty() {
+            return Shape::Leaf(first);
+        }
 
-                target_rect.size.h -= offset;
-                new_rect.size.h += offset;
-            } else {
-                new_rect.loc.y += half + gap + offset;
+        let rest = Shape::dwindle(windows, direction.invert());
+        Shape::Container(direction, ContainerMode::Split, vec![(Shape::Leaf(first), 1.0), (rest, 1.0)])
+    }
+}
+
+/// Rects of the children of a container, tabbed and stacked ones share the area below their tabs.
+fn child_rects(
+    rect: Rectangle<i32, Logical>,
+    direction: Direction,
+    weights: &[f64],
+    mode: ContainerMode,
+    gap: i32,
+    tab_height: i32,
+) -> Vec<Rectangle<i32, Logical>> {
+    let count = weights.len();
+    let rows = match mode {
+        ContainerMode::Split => return split_rects(rect, direction, weights, gap),
+        ContainerMode::Tabbed => 1,
+        ContainerMode::Stacked => count as i32,
+        ContainerMode::Monocle => 0,
+    };
+
+    let strip = (tab_height * rows).min(rect.size.h);
+    let mut shared = rect;
+    shared.loc.y += strip;
+    shared.size.h -= strip;
+
+    vec![shared; count]
+}
+
+fn tab_rect(
+    rect: Rectangle<i32, Logical>,
+    mode: ContainerMode,
+    count: usize,
+    idx: usize,
+    tab_height: i32,
+) -> Rectangle<i32, Logical> {
+    let (count, idx) = (count.max(1) as i32, idx as i32);
+
+    match mode {
+        ContainerMode::Stacked => Rectangle::new((rect.loc.x, rect.loc.y + tab_height * idx).into(), (rect.size.w, tab_height).into()),
+        // tabs share the width, the last one takes the rounding
+        _ => {
+            let x = rect.size.w * idx / count;
+            let w = rect.size.w * (idx + 1) / count - x;
+            Rectangle::new((rect.loc.x + x, rect.loc.y).into(), (w, tab_height).into())
+        }
+    }
+}
 
-                new_rect.size.h -= offset;
-                target_rect.size.h += offset;
+fn split_rects(
+    rect: Rectangle<i32, Logical>, 
+    direction: Direction, 
+    weights: &[f64], 
+    gap: i32, 
+) -> Vec<Rectangle<i32, Logical>> {
+    let length = match direction {
+        Direction::Horizontal => rect.size.w,
+        Direction::Vertical => rect.size.h,
+    };
+    let available = (length - gap * (weights.len() as i32 - 1)).max(0) as f64;
+    let total: f64 = weights.iter().sum();
+
+    let mut rects = Vec::with_capacity(weights.len());
+    let mut covered = 0.0;
+    let mut start = 0;
+
+    for (idx, weight) in weights.iter().enumerate() {
+        // rounded from the running sum, so the last one ends at the edge
+        covered += weight;
+        let end = (available * covered / total).round() as i32;
+        let offset = start + gap * idx as i32;
+
+        let mut sub_rect = rect;
+        match direction {
+            Direction::Horizontal => {
+                sub_rect.loc.x += offset;
+                sub_rect.size.w = end - start;
+            }
+            Direction::Vertical => {
+                sub_rect.loc.y += offset;
+                sub_rect.size.h = end - start;
             }
         }
+
+        rects.push(sub_rect);
+        start = end;
     }
 
-    (target_rect, new_rect)
-}
\ No newline at end of file
+    rects
+}
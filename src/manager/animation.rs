@@ -1,14 +1,21 @@
-use smithay::{desktop::Window, utils::{Coordinate, Logical, Point, Rectangle, Size}};
+use smithay::{backend::renderer::element::memory::MemoryRenderBuffer, desktop::Window, utils::{Coordinate, IsAlive, Logical, Point, Rectangle, Serial, Size}};
 
-use std::{collections::HashMap, time::Duration};
+use std::{collections::HashMap, sync::Arc, time::Duration};
 
-use crate::manager::window::WindowExt;
+use crate::{
+    config::animation::{AnimationConfigs, AnimationEvent},
+    manager::window::WindowExt,
+};
 
+#[derive(Debug, Clone, Copy)]
 pub enum AnimationType {
-    #[allow(dead_code)]
     Linear,
     EaseInOutQuad,
     OvershootBounce,
+    // control points (x1, y1, x2, y2), like css cubic-bezier()
+    CubicBezier(f64, f64, f64, f64),
+    // damped harmonic oscillator, settles at the end of the duration
+    Spring { stiffness: f64, damping: f64, mass: f64 },
 }
 
 impl AnimationType {
@@ -16,10 +23,10 @@ impl AnimationType {
         AnimationType::Linear
     }
 
-    pub fn get_progress(&self, t: f64) -> f64 {
+    pub fn get_progress(&self, t: f64, duration: Duration) -> f64 {
         let t = t.clamp(0.0, 1.0);
 
-        match self {
+        match *self {
             AnimationType::Linear => t,
 
             AnimationType::EaseInOutQuad => {
@@ -36,7 +43,84 @@ impl AnimationType {
 
                 1.0 + c3 * (t - 1.0).powi(3) + c1 * (t - 1.0).powi(2)
             }
+
+            AnimationType::CubicBezier(x1, y1, x2, y2) => {
+                let s = solve_bezier_x(t, x1, x2);
+                bezier(s, y1, y2)
+            }
+
+            AnimationType::Spring { stiffness, damping, mass } => {
+                if t >= 1.0 {
+                    return 1.0;
+                }
+
+                spring(t * duration.as_secs_f64(), stiffness, damping, mass)
+            }
+        }
+    }
+}
+
+fn bezier(s: f64, p1: f64, p2: f64) -> f64 {
+    // B(s) with p0 = 0, p3 = 1
+    let inv = 1.0 - s;
+    3.0 * inv * inv * s * p1 + 3.0 * inv * s * s * p2 + s * s * s
+}
+
+fn solve_bezier_x(x: f64, x1: f64, x2: f64) -> f64 {
+    // newton first, bisection if the slope is too flat
+    let mut s = x;
+    for _ in 0..8 {
+        let err = bezier(s, x1, x2) - x;
+        if err.abs() < 1e-6 {
+            return s;
+        }
+
+        let inv = 1.0 - s;
+        let slope = 3.0 * inv * inv * x1 + 6.0 * inv * s * (x2 - x1) + 3.0 * s * s * (1.0 - x2);
+        if slope.abs() < 1e-6 {
+            break;
+        }
+
+        s -= err / slope;
+    }
+
+    let (mut lo, mut hi) = (0.0, 1.0);
+    s = x;
+    for _ in 0..32 {
+        let value = bezier(s, x1, x2);
+        if (value - x).abs() < 1e-6 {
+            break;
+        }
+
+        if value < x {
+            lo = s;
+        } else {
+            hi = s;
         }
+        s = (lo + hi) / 2.0;
+    }
+
+    s
+}
+
+fn spring(time: f64, stiffness: f64, damping: f64, mass: f64) -> f64 {
+    // displacement from 0 to 1 with zero initial velocity
+    let omega = (stiffness / mass).sqrt();
+    let zeta = damping / (2.0 * (stiffness * mass).sqrt());
+
+    if zeta < 1.0 {
+        let omega_d = omega * (1.0 - zeta * zeta).sqrt();
+        let envelope = (-zeta * omega * time).exp();
+
+        1.0 - envelope * ((omega_d * time).cos() + zeta * omega / omega_d * (omega_d * time).sin())
+    } else if zeta == 1.0 {
+        1.0 - (-omega * time).exp() * (1.0 + omega * time)
+    } else {
+        let root = (zeta * zeta - 1.0).sqrt();
+        let r1 = -omega * (zeta - root);
+        let r2 = -omega * (zeta + root);
+
+        1.0 - (r2 * (r1 * time).exp() - r1 * (r2 * time).exp()) / (r2 - r1)
     }
 }
 
@@ -55,9 +139,12 @@ impl AnimationState {
 pub struct Animation {
     from: Rectangle<i32, Logical>,
     to: Rectangle<i32, Logical>,
+    started_at: Duration,
     elapsed: Duration,
     duration: Duration,
     animation_type: AnimationType,
+    // true: the final rect is configured once the animation ends
+    configure: bool,
     pub state: AnimationState,
 }
 
@@ -67,25 +154,29 @@ impl Animation {
         to: Rectangle<i32, Logical>,
         duration: Duration,
         animation_type: AnimationType,
+        configure: bool,
     ) -> Self {
         Self {
             from,
             to,
+            started_at: Duration::ZERO,
             elapsed: Duration::ZERO,
             duration,
             animation_type,
+            configure,
             state: AnimationState::new(),
         }
     }
 
-    pub fn start(&mut self) -> Rectangle<i32, Logical> {
+    pub fn start(&mut self, now: Duration) -> Rectangle<i32, Logical> {
+        self.started_at = now;
         self.elapsed = Duration::ZERO;
         self.state = AnimationState::Running;
         self.from
     }
 
-    pub fn tick(&mut self) {
-        self.elapsed += Duration::from_millis(1);
+    pub fn tick(&mut self, now: Duration) {
+        self.elapsed = now.saturating_sub(self.started_at);
         if self.elapsed >= self.duration {
             self.state = AnimationState::Completed;
         }
@@ -97,22 +188,97 @@ impl Animation {
     }
 
     pub fn current_value(&self) -> Rectangle<i32, Logical> {
+        if self.duration.is_zero() {
+            return self.to;
+        }
+
         let progress = (self.elapsed.as_secs_f64() / self.duration.as_secs_f64()).clamp(0.0, 1.0);
         process_rec(
             self.from,
             self.to,
-            self.animation_type.get_progress(progress),
+            self.animation_type.get_progress(progress, self.duration),
         )
     }
 }
 
+pub struct OpacityAnimation {
+    from: f64,
+    to: f64,
+    started_at: Option<Duration>,
+    duration: Duration,
+    animation_type: AnimationType,
+}
+
+impl OpacityAnimation {
+    pub fn new(from: f64, to: f64, duration: Duration, animation_type: AnimationType) -> Self {
+        Self {
+            from,
+            to,
+            started_at: None,
+            duration,
+            animation_type,
+        }
+    }
+
+    pub fn current_value(&mut self, now: Duration) -> f64 {
+        let started_at = *self.started_at.get_or_insert(now);
+        if self.duration.is_zero() {
+            return self.to;
+        }
+
+        let elapsed = now.saturating_sub(started_at);
+
+        let progress = (elapsed.as_secs_f64() / self.duration.as_secs_f64()).clamp(0.0, 1.0);
+        let progress = self.animation_type.get_progress(progress, self.duration);
+
+        (self.from + (self.to - self.from) * progress).clamp(0.0, 1.0)
+    }
+
+    pub fn is_finished(&self, now: Duration) -> bool {
+        self.started_at
+            .map(|started_at| now.saturating_sub(started_at) >= self.duration)
+            .unwrap_or(false)
+    }
+}
+
+/// Snapshot of a closed window, kept on screen until its animation ends.
+pub struct ClosingWindow {
+    pub buffer: MemoryRenderBuffer,
+    rect: Animation,
+    opacity: OpacityAnimation,
+}
+
 pub struct AnimationManager {
     animations: HashMap<Window, Animation>,
+    // final configures of finished animations, scaled until the client commits them
+    settling: HashMap<Window, Serial>,
+    opacities: HashMap<Window, OpacityAnimation>,
+    // dim level of each window with the target it moves to
+    dims: HashMap<Window, (f64, OpacityAnimation)>,
+    closing: Vec<ClosingWindow>,
+
+    // presentation time of the frame being rendered
+    now: Duration,
+
+    configs: Arc<AnimationConfigs>,
 }
 
 impl AnimationManager {
-    pub fn new() -> Self {
-        Self { animations: HashMap::new() }
+    pub fn new(configs: Arc<AnimationConfigs>) -> Self {
+        Self { 
+            animations: HashMap::new(),
+            settling: HashMap::new(),
+            opacities: HashMap::new(),
+            dims: HashMap::new(),
+            closing: Vec::new(),
+            now: Duration::ZERO,
+            configs,
+        }
+    }
+
+    /// Set the time the next frame will be presented at, from the backend clock.
+    pub fn advance(&mut self, now: Duration) {
+        self.now = self.now.max(now);
     }
 
     pub fn add_animation(
@@ -120,28 +286,67 @@ impl AnimationManager {
         window: Window,
         from: Rectangle<i32, Logical>,
         to: Rectangle<i32, Logical>,
-        duration: Duration,
-        animation_type: AnimationType,
+        event: AnimationEvent,
     ) {
         // void conflict
         self.stop_animation(&window);
+        self.settling.remove(&window);
 
-        let animation = Animation::new(from, to, duration, animation_type);
+        let style = self.configs.style(event);
+        if !style.enabled {
+            window.send_rect(to);
+            return;
+        }
+
+        let animation = Animation::new(from, to, style.duration, style.curve, true);
         self.animations.insert(window, animation);
     }
 
+    /// Like `add_animation`, but the client is never configured to the final rect.
+    pub fn add_scaled_animation(
+        &mut self,
+        window: Window,
+        from: Rectangle<i32, Logical>,
+        to: Rectangle<i32, Logical>,
+        event: AnimationEvent,
+    ) {
+        // void conflict
+        self.stop_animation(&window);
+        self.settling.remove(&window);
+
+        let style = self.configs.style(event);
+        if !style.enabled {
+            return;
+        }
+
+        let animation = Animation::new(from, to, style.duration, style.curve, false);
+        self.animations.insert(window, animation);
+    }
+
+    /// Animated windows keep their committed buffer and are scaled into the animated rect,
+    /// until the client committed a buffer for the final configure.
+    pub fn is_animating(&self, window: &Window) -> bool {
+        self.animations.contains_key(window) || self.settling.contains_key(window)
+    }
+
     pub fn get_animation_data(&mut self, window: &Window) -> Option<Rectangle<i32, Logical>> {
+        let now = self.now;
+        let settling = &mut self.settling;
+
         self.animations.get_mut(window).and_then(|animation| match animation.state {
             AnimationState::NotStarted => {
-                let rect = animation.start();
-                window.send_rect(rect);
-
-                Some(rect)
+                Some(animation.start(now))
             }
             AnimationState::Running => {
-                animation.tick();
+                animation.tick(now);
                 let rect = animation.current_value();
-                window.send_rect(rect);
+
+                // clients only see the final size
+                if animation.configure && matches!(animation.state, AnimationState::Completed) {
+                    if let Some(serial) = window.send_rect(rect) {
+                        settling.insert(window.clone(), serial);
+                    }
+                }
 
                 Some(rect)
             }
@@ -151,15 +356,139 @@ impl AnimationManager {
 
     pub fn stop_animation(&mut self, window: &Window) {
         if let Some(animation) = self.animations.get_mut(window) {
+            if matches!(animation.state, AnimationState::Completed) {
+                return;
+            }
+
             let rect = animation.stop();
-            window.send_rect(rect);
+            if animation.configure {
+                if let Some(serial) = window.send_rect(rect) {
+                    self.settling.insert(window.clone(), serial);
+                }
+            }
+        }
+    }
+
+    pub fn add_opacity_animation(&mut self, window: Window, from: f64, to: f64, event: AnimationEvent) {
+        let style = self.configs.style(event);
+        if !style.enabled {
+            self.opacities.remove(&window);
+            return;
+        }
+
+        self.opacities.insert(window, OpacityAnimation::new(from, to, style.duration, style.curve));
+    }
+
+    /// Opacity multiplier of the window, if it is fading.
+    pub fn get_opacity(&mut self, window: &Window) -> Option<f64> {
+        let now = self.now;
+        self.opacities
+            .get_mut(window)
+            .map(|animation| animation.current_value(now))
+    }
+
+    /// Dim level of the window moving to `target`, restarting the transition when the target changes.
+    pub fn get_dim(&mut self, window: &Window, target: f64) -> f64 {
+        let now = self.now;
+        let style = self.configs.style(AnimationEvent::FocusDim);
+
+        match self.dims.get_mut(window) {
+            Some((current_target, animation)) if *current_target == target => animation.current_value(now),
+            Some((current_target, animation)) => {
+                let from = animation.current_value(now);
+                let duration = if style.enabled { style.duration } else { Duration::ZERO };
+
+                *current_target = target;
+                *animation = OpacityAnimation::new(from, target, duration, style.curve);
+                animation.current_value(now)
+            }
+            None => {
+                // windows appear at their level without a transition
+                self.dims.insert(window.clone(), (target, OpacityAnimation::new(target, target, style.duration, style.curve)));
+                target
+            }
+        }
+    }
+
+    /// Fade out and shrink a snapshot at `rect`, where the window was last shown.
+    pub fn add_closing_window(&mut self, buffer: MemoryRenderBuffer, rect: Rectangle<i32, Logical>) {
+        let style = self.configs.style(AnimationEvent::WindowClose);
+        if !style.enabled {
+            return;
         }
+
+        let mut to = rect;
+        to.size = rect.size.to_f64().upscale(0.8).to_i32_round();
+        to.loc += (rect.size - to.size).to_point() / 2;
+
+        self.closing.push(ClosingWindow {
+            buffer,
+            rect: Animation::new(rect, to, style.duration, style.curve, false),
+            opacity: OpacityAnimation::new(1.0, 0.0, style.duration, style.curve),
+        });
     }
 
-    pub fn refresh(&mut self) {
+    /// Advance closing windows, returning their snapshot, rect and alpha.
+    pub fn closing_windows(&mut self) -> Vec<(&MemoryRenderBuffer, Rectangle<i32, Logical>, f64)> {
+        let now = self.now;
+
+        self.closing
+            .iter_mut()
+            .map(|closing| {
+                if matches!(closing.rect.state, AnimationState::NotStarted) {
+                    closing.rect.start(now);
+                } else {
+                    closing.rect.tick(now);
+                }
+
+                let rect = closing.rect.current_value();
+                let alpha = closing.opacity.current_value(now);
+
+                (&closing.buffer, rect, alpha)
+            })
+            .collect()
+    }
+
+    /// Whether any animation still needs frames.
+    pub fn is_running(&self) -> bool {
+        let now = self.now;
+
+        !self.animations.is_empty()
+            || !self.opacities.is_empty()
+            || !self.closing.is_empty()
+            || self.dims.values().any(|(_, animation)| !animation.is_finished(now))
+    }
+
+    /// Drop finished animations, and finish the ones of windows that are not drawn,
+    /// since those only advance while rendering.
+    pub fn refresh(&mut self, drawn: &[Window]) {
+        let now = self.now;
+
         // clean dead animations
-        self.animations
-            .retain(|_, animation| !matches!(animation.state, AnimationState::Completed));
+        self.animations.retain(|window, animation| {
+            if matches!(animation.state, AnimationState::Completed) {
+                return false;
+            }
+
+            if drawn.contains(window) {
+                return true;
+            }
+
+            let rect = animation.stop();
+            if animation.configure && window.alive() {
+                window.send_rect(rect);
+            }
+
+            false
+        });
+        self.settling
+            .retain(|window, serial| drawn.contains(window) && !window.has_committed(*serial));
+        self.opacities
+            .retain(|window, animation| drawn.contains(window) && !animation.is_finished(now));
+        self.dims
+            .retain(|window, _| window.alive() && drawn.contains(window));
+        self.closing
+            .retain(|closing| !matches!(closing.rect.state, AnimationState::Completed));
     }
 }
 
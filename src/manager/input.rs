@@ -1,6 +1,7 @@
 use std::{collections::HashMap, sync::Arc};
 
 use smithay::{
+    desktop::Window,
     input::{
         Seat, SeatState, keyboard::KeyboardHandle, pointer::PointerHandle, touch::TouchHandle,
     },
@@ -9,6 +10,57 @@ use smithay::{
 
 use crate::{config::keybinding::{KeyAction, KeybindingConfigs}, input::focus::KeyboardFocusTarget, state::GlobalData, utils::errors::AnyHowErr};
 
+/// Most-recently-used list of focused windows, front is the latest.
+#[derive(Debug, Default)]
+pub struct FocusHistory {
+    windows: Vec<Window>,
+}
+
+impl FocusHistory {
+    pub fn touch(&mut self, window: &Window) {
+        if self.windows.first() == Some(window) {
+            return;
+        }
+
+        self.windows.retain(|w| w != window);
+        self.windows.insert(0, window.clone());
+    }
+
+    pub fn remove(&mut self, window: &Window) {
+        self.windows.retain(|w| w != window);
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Window> {
+        self.windows.iter()
+    }
+}
+
+/// An in-progress Alt-Tab style cycle, committed on modifier release.
+#[derive(Debug)]
+pub struct WindowCycle {
+    pub candidates: Vec<Window>,
+    pub selected: usize,
+}
+
+impl WindowCycle {
+    pub fn step(&mut self, forward: bool) {
+        let len = self.candidates.len();
+        if len == 0 {
+            return;
+        }
+
+        self.selected = if forward {
+            (self.selected + 1) % len
+        } else {
+            (self.selected + len - 1) % len
+        };
+    }
+
+    pub fn selected(&self) -> Option<&Window> {
+        self.candidates.get(self.selected)
+    }
+}
+
 pub struct InputManager {
     pub seat_state: SeatState<GlobalData>,
     seat: Seat<GlobalData>,
@@ -16,6 +68,10 @@ pub struct InputManager {
     // global data
     is_mainmod_pressed: bool,
 
+    // focus
+    focus_history: FocusHistory,
+    window_cycle: Option<WindowCycle>,
+
     // keybindings
     configs: Arc<KeybindingConfigs>,
 }
@@ -38,6 +94,8 @@ impl InputManager {
             seat_state,
             seat,
             is_mainmod_pressed: false,
+            focus_history: FocusHistory::default(),
+            window_cycle: None,
             configs,
         })
     }
@@ -62,6 +120,54 @@ impl InputManager {
         &self.configs.mainmod
     }
 
+    pub fn is_switcher_overlay_enabled(&self) -> bool {
+        self.configs.switcher_overlay
+    }
+
+    pub fn focus_history(&self) -> &FocusHistory {
+        &self.focus_history
+    }
+
+    pub fn record_focus(&mut self, window: &Window) {
+        // the history is frozen while cycling, it is updated once on commit
+        if self.window_cycle.is_none() {
+            self.focus_history.touch(window);
+        }
+    }
+
+    pub fn forget_window(&mut self, window: &Window) {
+        self.focus_history.remove(window);
+
+        if let Some(cycle) = &mut self.window_cycle {
+            if let Some(pos) = cycle.candidates.iter().position(|w| w == window) {
+                cycle.candidates.remove(pos);
+                if pos < cycle.selected || cycle.selected >= cycle.candidates.len() {
+                    cycle.selected = cycle.selected.saturating_sub(1);
+                }
+            }
+
+            if cycle.candidates.is_empty() {
+                self.window_cycle = None;
+            }
+        }
+    }
+
+    pub fn window_cycle(&self) -> Option<&WindowCycle> {
+        self.window_cycle.as_ref()
+    }
+
+    pub fn window_cycle_mut(&mut self) -> Option<&mut WindowCycle> {
+        self.window_cycle.as_mut()
+    }
+
+    pub fn start_window_cycle(&mut self, candidates: Vec<Window>) {
+        self.window_cycle = Some(WindowCycle { candidates, selected: 0 });
+    }
+
+    pub fn take_window_cycle(&mut self) -> Option<WindowCycle> {
+        self.window_cycle.take()
+    }
+
     pub fn get_keyboard(&self) -> Option<KeyboardHandle<GlobalData>> {
         self.seat.get_keyboard()
     }
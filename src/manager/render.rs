@@ -1,19 +1,23 @@
-use std::time::Instant;
+use std::{cell::OnceCell, collections::{HashMap, HashSet}, sync::Arc, time::Instant};
 
+use ab_glyph::FontArc;
 use smithay::{
     backend::renderer::{
         element::{
-            memory::MemoryRenderBufferRenderElement, surface::{render_elements_from_surface_tree, WaylandSurfaceRenderElement}, AsRenderElements, Kind
-        }, gles::{GlesRenderer, Uniform}, Color32F
+            memory::MemoryRenderBufferRenderElement, solid::{SolidColorBuffer, SolidColorRenderElement}, surface::{render_elements_from_surface_tree, WaylandSurfaceRenderElement}, utils::RescaleRenderElement, AsRenderElements, Element, Id, Kind
+        }, gles::{GlesRenderer, GlesTexture, Uniform}, utils::CommitCounter
     },
-    desktop::{layer_map_for_output},
-    utils::{Logical, Point, Rectangle, Scale},
-    wayland::shell::wlr_layer::Layer,
+    desktop::{layer_map_for_output, PopupManager, Window},
+    output::Output,
+    reexports::{calloop::{channel::{self, Sender}, LoopHandle}, wayland_server::protocol::wl_surface::WlSurface},
+    utils::{IsAlive, Logical, Physical, Point, Rectangle, Scale, Size},
+    wayland::{seat::WaylandFocus, shell::wlr_layer::Layer},
 };
 
 use crate::{
-    input::focus::KeyboardFocusTarget, layout::{container_tree::ExpansionCache, WindowLayout}, manager::{animation::AnimationManager, window::{WindowExt, WindowManager}}, protocol::FullscreenSurface, render::{
-        background::{Background, BackgroundRenderElement}, border::{BorderRenderElement, BorderShader}, elements::{CustomRenderElements, OutputRenderElements, ShaderRenderElement}, MondrianRenderer
+    config::{background::{BackgroundConfigs, BackgroundSource}, decoration::{DecorationConfigs, GradientColor}},
+    input::focus::KeyboardFocusTarget, layout::{container_tree::ExpansionCache, TiledScheme, WindowLayout}, manager::{animation::AnimationManager, window::{WindowExt, WindowManager}}, protocol::FullscreenSurface, state::GlobalData, render::{
+        background::{decode_image, image_buffer, BackgroundRenderElement, BackgroundShaders, CachedBackground, DecodedImage, OutputBackground}, blur::{BlurCache, BlurRenderElement, BlurShaders}, clipped_surface::{ClipDamage, ClippedSurfaceRenderElement}, border::{BorderRenderElement, BorderShader}, elements::{CustomRenderElements, OutputRenderElements, ShaderRenderElement}, shadow::{ShadowRenderElement, ShadowShader}, title_bar::{button_rects, load_font, title_bar_rect, TitleBar}, MondrianRenderer
     }
 };
 
@@ -24,21 +28,96 @@ use super::{
     workspace::WorkspaceManager,
 };
 
+const SWITCHER_CELL_WIDTH: i32 = 240;
+const SWITCHER_CELL_HEIGHT: i32 = 160;
+const SWITCHER_PADDING: i32 = 16;
+
 pub struct RenderManager {
     // no need now
     start_time: Instant,
+
+    switcher_background: SolidColorBuffer,
+
+    // persistent, so unchanged shadows and borders are not damaged again
+    shadows: HashMap<Window, ShadowRenderElement>,
+    // keyed by surface element, so changed effects damage a surface without a commit
+    clip_damage: HashMap<Id, ClipDamage>,
+    borders: HashMap<Window, BorderRenderElement>,
+    // around the selected cell of the window switcher
+    switcher_border: Option<BorderRenderElement>,
+
+    // blurred background per output name, and what is drawn of it per surface
+    blur_caches: HashMap<String, BlurCache>,
+    blurs: HashMap<WlSurface, BlurRenderElement>,
+
+    // per output name
+    backgrounds: HashMap<String, OutputBackground>,
+    // background images are decoded on threads and come back through here
+    decoded_images: Sender<DecodedImage>,
+
+    // preview of a preselected split
+    presel: SolidColorBuffer,
+
+    // text is only rasterized again when a title changes
+    title_bars: HashMap<Window, TitleBar>,
+    // keyed by strip, a window can head tabs of nested containers
+    tabs: HashMap<(usize, Window), TitleBar>,
+    // shared by title bars and tabs, only read once one is drawn
+    title_font: OnceCell<Option<FontArc>>,
+
+    configs: Arc<DecorationConfigs>,
+    background_configs: Arc<BackgroundConfigs>,
 }
 
 impl RenderManager {
-    pub fn new() -> Self {
+    pub fn new(
+        configs: Arc<DecorationConfigs>,
+        background_configs: Arc<BackgroundConfigs>,
+        loop_handle: &LoopHandle<'static, GlobalData>,
+    ) -> Self {
+        let (decoded_images, receiver) = channel::channel::<DecodedImage>();
+        loop_handle
+            .insert_source(receiver, |event, _, data| {
+                if let channel::Event::Msg(decoded) = event {
+                    if data.render_manager.set_decoded_image(decoded) {
+                        data.queue_redraw();
+                    }
+                }
+            })
+            .expect("Failed to init background decoding");
+
         Self {
             start_time: Instant::now(),
+            switcher_background: SolidColorBuffer::new((0, 0), [0.1, 0.1, 0.12, 0.85]),
+            shadows: HashMap::new(),
+            clip_damage: HashMap::new(),
+            borders: HashMap::new(),
+            switcher_border: None,
+            blur_caches: HashMap::new(),
+            blurs: HashMap::new(),
+            backgrounds: HashMap::new(),
+            decoded_images,
+            presel: SolidColorBuffer::default(),
+            title_bars: HashMap::new(),
+            tabs: HashMap::new(),
+            title_font: OnceCell::new(),
+            configs,
+            background_configs,
         }
     }
 
+    /// Whether time driven shaders change the output every frame.
+    pub fn is_animated(&self) -> bool {
+        self.configs.border.pulse
+            || self.backgrounds.values().any(|cached| matches!(cached.background, CachedBackground::Shader { animate: true, .. }))
+    }
+
     pub fn compile_shaders(&self, renderer: &mut GlesRenderer) {
         BorderRenderElement::complie_shaders(renderer);
         BackgroundRenderElement::complie_shaders(renderer);
+        ClippedSurfaceRenderElement::<GlesRenderer>::complie_shaders(renderer);
+        ShadowRenderElement::complie_shaders(renderer);
+        BlurShaders::complie_shaders(renderer);
     }
 
     pub fn get_render_elements<R: MondrianRenderer>(
@@ -49,25 +128,40 @@ impl RenderManager {
         window_manager: &WindowManager,
         cursor_manager: &mut CursorManager,
         input_manager: &InputManager,
-        animation_manager: &mut AnimationManager
+        animation_manager: &mut AnimationManager,
+        with_cursor: bool,
     ) -> Vec<OutputRenderElements<R>> {
         let _span = tracy_client::span!("get_render_elements");
 
         let mut output_elements = vec![];
 
-        // First is Cursor
+        // First is Cursor, screen captures may leave it out
+        if with_cursor {
+            output_elements.extend(
+                self.get_cursor_render_elements(
+                    renderer,
+                    output_manager,
+                    cursor_manager,
+                    input_manager,
+                )
+                .into_iter()
+                .map(OutputRenderElements::Custom),
+            );
+        }
+
+        // Then Some Control elements
         output_elements.extend(
-            self.get_cursor_render_elements(
-                renderer,
-                output_manager,
-                cursor_manager,
-                input_manager,
-            )
-            .into_iter()
-            .map(OutputRenderElements::Custom),
+            self.get_switcher_render_elements(renderer, output_manager, input_manager)
+                .into_iter()
+                .map(OutputRenderElements::Custom),
         );
 
-        // Then Some Control elements
+        // the bottom layers and the background go last, built first since windows blur them
+        let output = output_manager.current_output();
+        let output_geo = output_manager.output_geometry(output).unwrap();
+        let mut background = self.get_bottom_layers_render_elements(renderer, output, output_geo);
+        background.extend(self.get_background_render_elements(renderer, output, output_geo));
+        let blurred = self.update_blur(renderer, output, output_geo, &background);
 
         // Then Windows, Borders and Layer-shell
         output_elements.extend(
@@ -78,16 +172,14 @@ impl RenderManager {
                     window_manager, 
                     input_manager,
                     animation_manager,
+                    blurred,
                 )
                 .into_iter()
                 .map(OutputRenderElements::Custom),
         );
 
-        // output_elements.extend(
-        //     self.get_background_render_elements(renderer, output_manager)
-        //         .into_iter()
-        //         .map(OutputRenderElements::Custom),
-        // );
+        // Last the background behind everything
+        output_elements.extend(background.into_iter().map(OutputRenderElements::Custom));
 
         output_elements
     }
@@ -99,7 +191,8 @@ impl RenderManager {
         workspace_manager: &WorkspaceManager,
         window_manager: &WindowManager,
         input_manager: &InputManager,
-        animation_manager: &mut AnimationManager
+        animation_manager: &mut AnimationManager,
+        blurred: Option<(GlesTexture, usize)>,
     ) -> Vec<CustomRenderElements<R>> {
         let _span = tracy_client::span!("get_windows_render_elements");
 
@@ -152,8 +245,54 @@ impl RenderManager {
                 _ => None,
             });
 
+        // the overview may also show windows from other workspaces
+        let overview = workspace_manager.overview();
+        let windows = workspace_manager.drawn_windows(window_manager);
+        let highlight = match overview {
+            Some(overview) => overview.selected().cloned(),
+            None => focus,
+        };
+
+        // the preselected split over the window it splits, automatic schemes ignore it
+        let preselectable = *workspace_manager.current_scheme() == TiledScheme::Default;
+        if let (None, Some((target, presel))) = (overview, window_manager.presel()) {
+            if let (true, true, Some(rect)) = (preselectable, windows.contains(target), target.get_rect()) {
+                let preview = presel.preview(rect);
+                self.presel.update(preview.size, self.configs.presel);
+
+                elements.push(CustomRenderElements::Solid(SolidColorRenderElement::from_buffer(
+                    &self.presel,
+                    preview.loc.to_physical_precise_round(output_scale),
+                    output_scale,
+                    1.0,
+                    Kind::Unspecified,
+                )));
+            }
+        }
+
+        // closing windows stay above the reflowing siblings
+        for (buffer, rect, alpha) in animation_manager.closing_windows() {
+            let location = rect.loc.to_f64().to_physical(output_scale);
+
+            match MemoryRenderBufferRenderElement::from_buffer(
+                renderer,
+                location,
+                buffer,
+                Some(alpha as f32),
+                None,
+                Some(rect.size),
+                Kind::Unspecified,
+            ) {
+                Ok(element) => elements.push(CustomRenderElements::Memory(element)),
+                Err(err) => warn!("failed to render closing window: {:?}", err),
+            }
+        }
+
+        // surfaces clipped this frame, the others drop their damage state
+        let mut clipped = HashSet::new();
+
         // windows
-        for window in window_manager.mapped_windows(workspace_manager.current_workspace().id()) {
+        for window in windows.iter() {
             let rect = match animation_manager.get_animation_data(window) {
                 Some(rect) => {
                     rect
@@ -173,13 +312,41 @@ impl RenderManager {
             };
 
             // windows border
-            if let Some(focus) = &focus {
-                if focus == window {
-                    elements.extend(self.get_border_render_elements(renderer, rect));
-                }
+            let border = &self.configs.border;
+            let color = if highlight.as_ref() == Some(window) {
+                Some(border.active)
+            } else if window_manager.is_urgent(window) {
+                Some(border.urgent)
+            } else if !border.unfocused {
+                None
+            } else if matches!(window.get_layout(), WindowLayout::Floating) {
+                Some(border.floating)
+            } else {
+                Some(border.inactive)
+            };
+
+            if let Some(color) = color {
+                elements.extend(self.get_border_render_elements(renderer, Some(window), rect, &color));
             }
-            
-            let render_loc = (rect.loc - window.geometry().loc).to_physical_precise_round(output_scale);
+
+            // the overview shows bare windows
+            let content = if overview.is_some() {
+                rect
+            } else {
+                let title_height = window.title_bar_height();
+                if title_height > 0 {
+                    let bar = title_bar_rect(rect, title_height);
+                    let focused = highlight.as_ref() == Some(window);
+                    elements.extend(self.get_title_bar_render_elements(renderer, window, bar, focused, output_scale));
+                }
+
+                window.content_rect(rect)
+            };
+
+            let render_loc = (content.loc - window.geometry().loc).to_physical_precise_round(output_scale);
+
+            // overview and animations scale buffers instead of reconfiguring
+            let scaled = overview.is_some() || animation_manager.is_animating(window);
             
             // set alpha
             let mut alpha  = 0.85;
@@ -189,17 +356,134 @@ impl RenderManager {
                 alpha = val;
             }
 
-            elements.extend(window
-                .render_elements::<WaylandSurfaceRenderElement<R>>(
+            let fade = animation_manager.get_opacity(window).unwrap_or(1.0) as f32;
+            alpha *= fade;
+
+            let (popup_elements, surface_elements) = window_surface_elements(renderer, window, render_loc, output_scale, alpha);
+
+            let geometry = window.geometry();
+            let rescale = (scaled && !geometry.size.is_empty()).then(|| {
+                let origin = content.loc.to_physical_precise_round(output_scale);
+                let scale = Scale {
+                    x: content.size.w as f64 / geometry.size.w as f64,
+                    y: content.size.h as f64 / geometry.size.h as f64,
+                };
+
+                (origin, scale)
+            });
+
+            // popups are never clipped
+            elements.extend(popup_elements.into_iter().map(|element| match rescale {
+                Some((origin, scale)) => CustomRenderElements::Rescaled(RescaleRenderElement::from_element(element, origin, scale)),
+                None => CustomRenderElements::Surface(element),
+            }));
+
+            // rounded corners, unless a window rule opts out
+            let radius = self.configs.border.radius;
+            let rounded = radius > 0.0 && window_manager.get_rounding(window);
+
+            // inactive windows fade into the dim, unless a window rule exempts them
+            let dim = &self.configs.dim;
+            let inactive = dim.enabled && highlight.as_ref() != Some(window) && window_manager.get_dim(window);
+            let level = animation_manager.get_dim(window, if inactive { 1.0 } else { 0.0 }) as f32;
+            let effects = (dim.strength * level, dim.desaturate * level);
+
+            let clip = (rounded || level > 0.0).then(|| {
+                let program = ClippedSurfaceRenderElement::<R>::shader(renderer);
+                let geometry = Rectangle::new(content.loc.to_f64(), geometry.size.to_f64());
+
+                (program, geometry)
+            });
+
+            let clip_damage = &mut self.clip_damage;
+            elements.extend(surface_elements.into_iter().map(|element| match (&clip, rescale) {
+                (Some((program, geometry)), Some((origin, scale))) => {
+                    clipped.insert(element.id().clone());
+                    let damage = clip_damage.entry(element.id().clone()).or_default();
+                    let element = ClippedSurfaceRenderElement::new(element, program.clone(), *geometry, rounded.then_some(radius), Scale::from(output_scale), effects, damage);
+                    CustomRenderElements::RescaledClipped(RescaleRenderElement::from_element(element, origin, scale))
+                }
+                (Some((program, geometry)), None) => {
+                    clipped.insert(element.id().clone());
+                    let damage = clip_damage.entry(element.id().clone()).or_default();
+                    CustomRenderElements::Clipped(ClippedSurfaceRenderElement::new(element, program.clone(), *geometry, rounded.then_some(radius), Scale::from(output_scale), effects, damage))
+                }
+                (None, Some((origin, scale))) => {
+                    CustomRenderElements::Rescaled(RescaleRenderElement::from_element(element, origin, scale))
+                }
+                (None, None) => CustomRenderElements::Surface(element),
+            }));
+
+            // the blur only shows the wallpaper, floating windows may be over other windows
+            let is_floating = matches!(window.get_layout(), WindowLayout::Floating);
+            let blur = !is_floating && window_manager.get_blur(window).unwrap_or(alpha < 1.0);
+            if let (Some(blurred), Some(surface), true) = (blurred.as_ref(), window.wl_surface(), blur) {
+                let corner_radius = if rounded { radius } else { 0.0 };
+                let element = self.get_blur_render_element(
                     renderer,
-                    render_loc,
-                    Scale::from(output_scale),
-                    alpha,
-                ).into_iter().map(CustomRenderElements::Surface)
-            );
+                    &surface,
+                    blurred.clone(),
+                    rect,
+                    output_geo.loc,
+                    output_scale,
+                    corner_radius,
+                    fade,
+                );
+                elements.push(CustomRenderElements::Shader(ShaderRenderElement::Blur(element)));
+            }
+
+            // shadow behind the window
+            let shadow = &self.configs.shadow;
+            if shadow.enabled && (is_floating || shadow.tiled) && overview.is_none() {
+                let focused = highlight.as_ref() == Some(window);
+                let element = self.get_shadow_render_element(renderer, window, rect, focused, fade);
+                elements.push(CustomRenderElements::Shader(ShaderRenderElement::Shadow(element)));
+            }
+        }
+
+        // tabs of tabbed and stacked containers
+        if overview.is_none() {
+            let strips = workspace_manager.tab_strips();
+            for (idx, strip) in strips.iter().enumerate() {
+                for (tab, (window, rect)) in strip.tabs.iter().enumerate() {
+                    let active = tab == strip.active;
+                    elements.extend(self.get_tab_render_elements(renderer, idx, window, *rect, active, output_scale));
+                }
+            }
+        }
+
+        self.shadows.retain(|window, _| window.alive());
+        self.clip_damage.retain(|id, _| clipped.contains(id));
+        self.borders.retain(|window, _| window.alive());
+        self.title_bars.retain(|window, _| window.alive());
+        self.tabs.retain(|(_, window), _| window.alive());
+        self.blurs.retain(|surface, _| surface.alive());
+
+        elements
+    }
+
+    /// The bottom and background layers, hidden under a fullscreen window.
+    pub fn get_bottom_layers_render_elements<R: MondrianRenderer>(
+        &mut self,
+        renderer: &mut R,
+        output: &Output,
+        output_geo: Rectangle<i32, Logical>,
+    ) -> Vec<CustomRenderElements<R>> {
+        let _span = tracy_client::span!("get_bottom_layers_render_elements");
+
+        let mut elements: Vec<CustomRenderElements<R>> = vec![];
+
+        let fullscreen = output
+            .user_data()
+            .get::<FullscreenSurface>()
+            .and_then(|f| f.get())
+            .is_some();
+        if fullscreen {
+            return elements;
         }
 
-        // layer shell bottom and background
+        let output_scale = output.current_scale().fractional_scale();
+        let layer_map = layer_map_for_output(output);
         for layer in [Layer::Bottom, Layer::Background] {
             for layer_surface in layer_map.layers_on(layer) {
                 let layout_rec = layer_map.layer_geometry(layer_surface).unwrap();
@@ -217,6 +501,182 @@ impl RenderManager {
         elements
     }
 
+    fn get_title_bar_render_elements<R: MondrianRenderer>(
+        &mut self,
+        renderer: &mut R,
+        window: &Window,
+        bar: Rectangle<i32, Logical>,
+        focused: bool,
+        output_scale: f64,
+    ) -> Vec<CustomRenderElements<R>> {
+        let _span = tracy_client::span!("get_title_bar_render_elements");
+
+        let mut elements: Vec<CustomRenderElements<R>> = vec![];
+
+        let text = match window.get_title_and_id() {
+            (Some(title), Some(app_id)) if !app_id.is_empty() => format!("{} — {}", title, app_id),
+            (Some(title), _) => title,
+            (None, app_id) => app_id.unwrap_or_default(),
+        };
+
+        // text is rasterized at the next integer scale and scaled down
+        let buffer_scale = output_scale.ceil().max(1.0) as i32;
+
+        let font = self.title_font.get_or_init(|| load_font(&self.configs.title_bar.font));
+        let title_bar = self.title_bars.entry(window.clone()).or_insert_with(TitleBar::new);
+        title_bar.update(&self.configs.title_bar, font.as_ref(), &text, focused, bar, buffer_scale);
+
+        // front to back: text, buttons, background
+        if let Some(buffer) = title_bar.text() {
+            let location = title_bar.text_location(bar).to_f64().to_physical(output_scale);
+            match MemoryRenderBufferRenderElement::from_buffer(renderer, location, buffer, None, None, None, Kind::Unspecified) {
+                Ok(element) => elements.push(CustomRenderElements::Memory(element)),
+                Err(err) => warn!("failed to render title: {:?}", err),
+            }
+        }
+
+        let [(_, close), (_, float)] = button_rects(bar);
+        for (buffer, rect) in [(&title_bar.close, close), (&title_bar.float, float), (&title_bar.background, bar)] {
+            elements.push(CustomRenderElements::Solid(SolidColorRenderElement::from_buffer(
+                buffer,
+                rect.loc.to_physical_precise_round(output_scale),
+                output_scale,
+                1.0,
+                Kind::Unspecified,
+            )));
+        }
+
+        elements
+    }
+
+    fn get_tab_render_elements<R: MondrianRenderer>(
+        &mut self,
+        renderer: &mut R,
+        strip: usize,
+        window: &Window,
+        rect: Rectangle<i32, Logical>,
+        active: bool,
+        output_scale: f64,
+    ) -> Vec<CustomRenderElements<R>> {
+        let _span = tracy_client::span!("get_tab_render_elements");
+
+        let mut elements: Vec<CustomRenderElements<R>> = vec![];
+
+        let text = window.get_title_and_id().0.unwrap_or_default();
+        let buffer_scale = output_scale.ceil().max(1.0) as i32;
+
+        let font = self.title_font.get_or_init(|| load_font(&self.configs.title_bar.font));
+        let tab = self.tabs.entry((strip, window.clone())).or_insert_with(TitleBar::tab);
+        tab.update(&self.configs.title_bar, font.as_ref(), &text, active, rect, buffer_scale);
+
+        if let Some(buffer) = tab.text() {
+            let location = tab.text_location(rect).to_f64().to_physical(output_scale);
+            match MemoryRenderBufferRenderElement::from_buffer(renderer, location, buffer, None, None, None, Kind::Unspecified) {
+                Ok(element) => elements.push(CustomRenderElements::Memory(element)),
+                Err(err) => warn!("failed to render tab: {:?}", err),
+            }
+        }
+
+        elements.push(CustomRenderElements::Solid(SolidColorRenderElement::from_buffer(
+            &tab.background,
+            rect.loc.to_physical_precise_round(output_scale),
+            output_scale,
+            1.0,
+            Kind::Unspecified,
+        )));
+
+        elements
+    }
+
+    pub fn get_switcher_render_elements<R: MondrianRenderer>(
+        &mut self,
+        renderer: &mut R,
+        output_manager: &OutputManager,
+        input_manager: &InputManager,
+    ) -> Vec<CustomRenderElements<R>> {
+        let _span = tracy_client::span!("get_switcher_render_elements");
+
+        let mut elements: Vec<CustomRenderElements<R>> = vec![];
+
+        if !input_manager.is_switcher_overlay_enabled() {
+            return elements;
+        }
+
+        let cycle = match input_manager.window_cycle() {
+            Some(cycle) => cycle,
+            None => return elements,
+        };
+
+        let output = output_manager.current_output();
+        let output_geo = output_manager.output_geometry(output).unwrap();
+        let output_scale = output.current_scale().fractional_scale();
+
+        // shrink the cells if the row does not fit on the output
+        let count = cycle.candidates.len() as i32;
+        let max_width = (output_geo.size.w - SWITCHER_PADDING * (count + 1)) / count.max(1);
+        let cell_w = SWITCHER_CELL_WIDTH.min(max_width).max(1);
+        let cell_h = SWITCHER_CELL_HEIGHT * cell_w / SWITCHER_CELL_WIDTH;
+
+        let panel_size: Size<i32, Logical> = (
+            cell_w * count + SWITCHER_PADDING * (count + 1),
+            cell_h + SWITCHER_PADDING * 2,
+        ).into();
+        let panel_loc = output_geo.loc + ((output_geo.size - panel_size) / 2).to_point();
+
+        for (idx, window) in cycle.candidates.iter().enumerate() {
+            let cell = Rectangle::new(
+                panel_loc + Point::from((SWITCHER_PADDING + idx as i32 * (cell_w + SWITCHER_PADDING), SWITCHER_PADDING)),
+                (cell_w, cell_h).into(),
+            );
+
+            if idx == cycle.selected {
+                let color = self.configs.border.active;
+                elements.extend(self.get_border_render_elements(renderer, None, cell, &color));
+            }
+
+            // scale the committed buffer into the cell, keeping its aspect ratio
+            let geometry = window.geometry();
+            if geometry.size.w <= 0 || geometry.size.h <= 0 {
+                continue;
+            }
+
+            let thumbnail_scale = (cell_w as f64 / geometry.size.w as f64)
+                .min(cell_h as f64 / geometry.size.h as f64);
+            let thumbnail_size = geometry.size.to_f64().upscale(thumbnail_scale).to_i32_round();
+            let thumbnail_loc = cell.loc + ((cell.size - thumbnail_size) / 2).to_point();
+
+            let origin = thumbnail_loc.to_physical_precise_round(output_scale);
+            let render_loc = (thumbnail_loc - geometry.loc).to_physical_precise_round(output_scale);
+
+            elements.extend(window
+                .render_elements::<WaylandSurfaceRenderElement<R>>(
+                    renderer,
+                    render_loc,
+                    Scale::from(output_scale),
+                    1.0,
+                ).into_iter()
+                .map(|element| {
+                    CustomRenderElements::Rescaled(RescaleRenderElement::from_element(
+                        element,
+                        origin,
+                        thumbnail_scale,
+                    ))
+                })
+            );
+        }
+
+        self.switcher_background.resize(panel_size);
+        elements.push(CustomRenderElements::Solid(SolidColorRenderElement::from_buffer(
+            &self.switcher_background,
+            panel_loc.to_physical_precise_round(output_scale),
+            output_scale,
+            1.0,
+            Kind::Unspecified,
+        )));
+
+        elements
+    }
+
     pub fn get_cursor_render_elements<R: MondrianRenderer>(
         &self,
         renderer: &mut R,
@@ -301,7 +761,7 @@ impl RenderManager {
                     }
                 };
                 if let Some(element) = pointer_element {
-                    pointer_elements.push(CustomRenderElements::NamedPointer(element));
+                    pointer_elements.push(CustomRenderElements::Memory(element));
                 }
                 pointer_elements
             }
@@ -309,99 +769,387 @@ impl RenderManager {
         pointer_render_elements
     }
 
-    pub fn get_border_render_elements<R: MondrianRenderer>(
-        &self,
+    pub fn get_shadow_render_element<R: MondrianRenderer>(
+        &mut self,
         renderer: &mut R,
-        rect: Rectangle<i32, Logical>
-    ) -> Vec<CustomRenderElements<R>> {
-        let _span = tracy_client::span!("get_border_render_elements");
+        window: &Window,
+        rect: Rectangle<i32, Logical>,
+        focused: bool,
+        alpha: f32,
+    ) -> ShadowRenderElement {
+        let _span = tracy_client::span!("get_shadow_render_element");
 
-        let mut elements: Vec<CustomRenderElements<R>> = vec![];
+        let shadow = &self.configs.shadow;
+
+        // the shadow box is the window moved by the offset and grown by the spread,
+        // the area also leaves room for the blur
+        let extent = shadow.spread + shadow.blur_radius;
+        let mut area = rect;
+        area.loc += Point::from(shadow.offset) - Point::from((extent, extent));
+        area.size += Size::from((extent * 2, extent * 2));
+
+        let window_loc = rect.loc - area.loc;
+        let color = if focused { shadow.active } else { shadow.inactive };
+        let point = area.size.to_point();
+
+        let uniforms = vec![
+            Uniform::new("u_resolution", (point.x as f32, point.y as f32)),
+            Uniform::new("shadow_color", rgba(color)),
+            Uniform::new("blur_radius", shadow.blur_radius as f32),
+            Uniform::new("corner_radius", self.configs.border.radius),
+            Uniform::new(
+                "window_rect",
+                (window_loc.x as f32, window_loc.y as f32, rect.size.w as f32, rect.size.h as f32),
+            ),
+        ];
+
+        if let Some(element) = self.shadows.get_mut(window) {
+            element.update(area, alpha, uniforms);
+            return element.clone();
+        }
 
         let program = renderer
             .as_gles_renderer()
             .egl_context()
             .user_data()
-            .get::<BorderShader>()
+            .get::<ShadowShader>()
             .unwrap()
             .0
             .clone();
 
-        let point = rect.size.to_point();
+        let element = ShadowRenderElement::new(program, area, alpha, uniforms, Kind::Unspecified);
+        self.shadows.insert(window.clone(), element.clone());
 
-        // Colors are 24 bits with 8 bits for each red, green, blue value.
-        // To get each color, shift the bits over by the offset and zero
-        // out the other colors. The bitwise AND 255 does this because it will
-        // zero out everything but the last 8 bits. This is where the color
-        // has been shifted to.
+        element
+    }
 
-        let border_color: Color32F = Color32F::from([0.0, 0.0, 1.0, 1.0]);
-        let border_thickness = 5.0;
+    /// Blur the wallpaper and bottom layers of `output` if they changed since the last frame.
+    ///
+    /// `background` are the elements drawn on screen, they are only built again for the
+    /// blur renderer when one of them committed.
+    fn update_blur<R: MondrianRenderer>(
+        &mut self,
+        renderer: &mut R,
+        output: &Output,
+        output_geo: Rectangle<i32, Logical>,
+        background: &[CustomRenderElements<R>],
+    ) -> Option<(GlesTexture, usize)> {
+        let _span = tracy_client::span!("update_blur");
 
-        elements.push(CustomRenderElements::Shader(ShaderRenderElement::Border(
-            BorderRenderElement::new(
-                program,
-                rect,
-                None,
-                1.0,
-                vec![
-                    Uniform::new("u_resolution", (point.x as f32, point.y as f32)),
-                    Uniform::new(
-                        "border_color",
-                        (border_color.r(), border_color.g(), border_color.b()),
-                    ),
-                    Uniform::new("border_thickness", border_thickness),
-                    Uniform::new(
-                        "u_time",
-                        self.start_time.elapsed().as_secs_f32() % (2.0 * 3.1415926),
-                    ), // TODO: just a test
-                    Uniform::new("corner_radius", 10.0),
-                ],
-                Kind::Unspecified,
+        let (passes, offset) = match &self.configs.blur {
+            blur if blur.enabled => (blur.passes, blur.size),
+            _ => return None,
+        };
+
+        let Some(mode) = output.current_mode() else {
+            return None;
+        };
+        let size = output.current_transform().transform_size(mode.size);
+        let output_scale = output.current_scale().fractional_scale();
+        let origin = output_geo.loc.to_physical_precise_round(output_scale);
+
+        let commits: Vec<(Id, CommitCounter)> = background
+            .iter()
+            .map(|element| (element.id().clone(), element.current_commit()))
+            .collect();
+
+        let cache = self.blur_caches.entry(output.name()).or_default();
+        if cache.is_current(&commits, origin, size, passes, offset) {
+            return cache.texture();
+        }
+
+        // the same elements for the gles renderer, the cache moves them to the output origin
+        let renderer = renderer.as_gles_renderer();
+        let mut elements = self.get_bottom_layers_render_elements(renderer, output, output_geo);
+        elements.extend(self.get_background_render_elements(renderer, output, output_geo));
+
+        let cache = self.blur_caches.entry(output.name()).or_default();
+        if let Err(err) = cache.update(renderer, &elements, commits, origin, size, output_scale, passes, offset) {
+            warn!("failed to blur the background: {:?}", err);
+        }
+
+        cache.texture()
+    }
+
+    pub fn get_blur_render_element<R: MondrianRenderer>(
+        &mut self,
+        renderer: &mut R,
+        surface: &WlSurface,
+        blurred: (GlesTexture, usize),
+        area: Rectangle<i32, Logical>,
+        output_loc: Point<i32, Logical>,
+        scale: f64,
+        corner_radius: f32,
+        alpha: f32,
+    ) -> BlurRenderElement {
+        let _span = tracy_client::span!("get_blur_render_element");
+
+        if let Some(element) = self.blurs.get_mut(surface) {
+            element.update(blurred, area, output_loc, scale, corner_radius, alpha);
+            return element.clone();
+        }
+
+        let program = ClippedSurfaceRenderElement::<R>::shader(renderer);
+        let element = BlurRenderElement::new(program, blurred, area, output_loc, scale, corner_radius, alpha);
+        self.blurs.insert(surface.clone(), element.clone());
+
+        element
+    }
+
+    /// The border around `rect`, kept for `window`, or for the switcher selection without one.
+    pub fn get_border_render_elements<R: MondrianRenderer>(
+        &mut self,
+        renderer: &mut R,
+        window: Option<&Window>,
+        rect: Rectangle<i32, Logical>,
+        color: &GradientColor,
+    ) -> Vec<CustomRenderElements<R>> {
+        let _span = tracy_client::span!("get_border_render_elements");
+
+        let mut elements: Vec<CustomRenderElements<R>> = vec![];
+
+        let border = &self.configs.border;
+        if border.thickness <= 0 {
+            return elements;
+        }
+
+        // the border surrounds the window instead of covering it
+        let mut area = rect;
+        area.loc -= Point::from((border.thickness, border.thickness));
+        area.size += Size::from((border.thickness * 2, border.thickness * 2));
+
+        let point = area.size.to_point();
+
+        let uniforms = vec![
+            Uniform::new("u_resolution", (point.x as f32, point.y as f32)),
+            Uniform::new("color_start", rgba(color.start)),
+            Uniform::new("color_end", rgba(color.end)),
+            Uniform::new("gradient_angle", color.angle),
+            Uniform::new("border_thickness", border.thickness as f32),
+            Uniform::new("corner_radius", border.radius),
+            // only ticks while pulsing, so static borders stay undamaged
+            Uniform::new(
+                "u_time",
+                if border.pulse { self.start_time.elapsed().as_secs_f32() % (2.0 * 3.1415926) } else { 0.0 },
             ),
-        )));
-        
+            Uniform::new("pulse", if border.pulse { 1.0 } else { 0.0 }),
+        ];
+
+        let cached = match window {
+            Some(window) => self.borders.get_mut(window),
+            None => self.switcher_border.as_mut(),
+        };
+
+        let element = match cached {
+            Some(element) => {
+                element.update(area, uniforms);
+                element.clone()
+            }
+            None => {
+                let program = renderer
+                    .as_gles_renderer()
+                    .egl_context()
+                    .user_data()
+                    .get::<BorderShader>()
+                    .unwrap()
+                    .0
+                    .clone();
+
+                let element = BorderRenderElement::new(program, area, None, 1.0, uniforms, Kind::Unspecified);
+                match window {
+                    Some(window) => { self.borders.insert(window.clone(), element.clone()); }
+                    None => self.switcher_border = Some(element.clone()),
+                }
+
+                element
+            }
+        };
+
+        elements.push(CustomRenderElements::Shader(ShaderRenderElement::Border(element)));
+
         elements
     }
 
-    pub fn _get_background_render_elements<R: MondrianRenderer>(
-        &self,
+    /// The configured background of `output`, built once and kept until the config or the output size changes.
+    pub fn get_background_render_elements<R: MondrianRenderer>(
+        &mut self,
         renderer: &mut R,
-        output_manager: &OutputManager,
+        output: &Output,
+        output_geo: Rectangle<i32, Logical>,
     ) -> Vec<CustomRenderElements<R>> {
-        let mut elements: Vec<CustomRenderElements<R>> = vec![];
+        let _span = tracy_client::span!("get_background_render_elements");
 
-        let program = renderer
-            .as_gles_renderer()
-            .egl_context()
-            .user_data()
-            .get::<Background>()
-            .unwrap()
-            .0
-            .clone();
+        let mut elements: Vec<CustomRenderElements<R>> = vec![];
 
-        let output_geo = output_manager
-            .output_geometry(output_manager.current_output())
-            .unwrap();
+        let Some(source) = self.background_configs.source(&output.name()).cloned() else {
+            return elements;
+        };
+        let Some(mode) = output.current_mode() else {
+            return elements;
+        };
+        let size = output.current_transform().transform_size(mode.size);
+        let output_scale = output.current_scale().fractional_scale();
         let point = output_geo.size.to_point();
 
-        elements.push(CustomRenderElements::Shader(
-            ShaderRenderElement::Background(BackgroundRenderElement::new(
-                program,
-                output_geo,
-                None,
-                1.0,
-                vec![
-                    Uniform::new("u_resolution", (point.x as f32, point.y as f32)),
-                    Uniform::new(
-                        "u_time",
-                        self.start_time.elapsed().as_secs_f32() % (2.0 * 3.1415926),
-                    ), // TODO: just a test
-                ],
-                Kind::Unspecified,
-            )),
-        ));
+        let rebuild = self.backgrounds
+            .get(&output.name())
+            .map(|cached| cached.source != source || cached.size != size)
+            .unwrap_or(true);
+
+        if rebuild {
+            let background = match &source {
+                BackgroundSource::Image { .. } => {
+                    decode_image(self.decoded_images.clone(), output.name(), source.clone(), size);
+                    CachedBackground::Loading
+                }
+                BackgroundSource::Color(color) => {
+                    let [r, g, b, a] = *color;
+                    CachedBackground::Color(SolidColorBuffer::new(output_geo.size, [r * a, g * a, b * a, a]))
+                }
+                BackgroundSource::Shader { shader, animate } => {
+                    let program = renderer
+                        .as_gles_renderer()
+                        .egl_context()
+                        .user_data()
+                        .get::<BackgroundShaders>()
+                        .and_then(|shaders| shaders.0.get(shader).cloned());
+
+                    match program {
+                        Some(program) => CachedBackground::Shader {
+                            element: BackgroundRenderElement::new(
+                                program,
+                                output_geo,
+                                Some(vec![output_geo]),
+                                1.0,
+                                vec![
+                                    Uniform::new("u_resolution", (point.x as f32, point.y as f32)),
+                                    Uniform::new("u_time", 0.0f32),
+                                ],
+                                Kind::Unspecified,
+                            ),
+                            animate: *animate,
+                        },
+                        None => CachedBackground::Failed,
+                    }
+                }
+            };
+
+            self.backgrounds.insert(output.name(), OutputBackground { source, size, background });
+        }
+
+        let Some(cached) = self.backgrounds.get_mut(&output.name()) else {
+            return elements;
+        };
+
+        match &mut cached.background {
+            CachedBackground::Image(buffer) => {
+                match MemoryRenderBufferRenderElement::from_buffer(
+                    renderer,
+                    output_geo.loc.to_f64().to_physical(output_scale),
+                    buffer,
+                    None,
+                    None,
+                    Some(output_geo.size),
+                    Kind::Unspecified,
+                ) {
+                    Ok(element) => elements.push(CustomRenderElements::Memory(element)),
+                    Err(err) => warn!("failed to render background: {:?}", err),
+                }
+            }
+            CachedBackground::Color(buffer) => {
+                buffer.resize(output_geo.size);
+                elements.push(CustomRenderElements::Solid(SolidColorRenderElement::from_buffer(
+                    buffer,
+                    output_geo.loc.to_physical_precise_round(output_scale),
+                    output_scale,
+                    1.0,
+                    Kind::Unspecified,
+                )));
+            }
+            CachedBackground::Shader { element, animate } => {
+                element.resize(output_geo, Some(vec![output_geo]));
+
+                // static shaders keep their commit and are never redrawn
+                if *animate {
+                    element.update_uniforms(vec![
+                        Uniform::new("u_resolution", (point.x as f32, point.y as f32)),
+                        Uniform::new("u_time", self.start_time.elapsed().as_secs_f32()),
+                    ]);
+                }
+
+                elements.push(CustomRenderElements::Shader(ShaderRenderElement::Background(element.clone())));
+            }
+            CachedBackground::Loading | CachedBackground::Failed => {}
+        }
 
         elements
     }
+
+    /// Store an image decoded for `output`, unless its background changed meanwhile.
+    pub fn set_decoded_image(&mut self, decoded: DecodedImage) -> bool {
+        let Some(cached) = self.backgrounds.get_mut(&decoded.output) else {
+            return false;
+        };
+        if cached.source != decoded.source || cached.size != decoded.size || !matches!(cached.background, CachedBackground::Loading) {
+            return false;
+        }
+
+        cached.background = match decoded.image {
+            Ok(image) => CachedBackground::Image(image_buffer(&image)),
+            Err(err) => {
+                if let BackgroundSource::Image { path, .. } = &decoded.source {
+                    warn!("failed to load background {}: {:?}", path, err);
+                }
+                CachedBackground::Failed
+            }
+        };
+
+        true
+    }
+}
+
+fn rgba(color: [f32; 4]) -> (f32, f32, f32, f32) {
+    (color[0], color[1], color[2], color[3])
+}
+
+/// Elements of the window surface tree (with subsurfaces) and of its popups, kept apart
+/// so that only the window itself gets clipped.
+fn window_surface_elements<R: MondrianRenderer>(
+    renderer: &mut R,
+    window: &Window,
+    location: Point<i32, Physical>,
+    scale: f64,
+    alpha: f32,
+) -> (Vec<WaylandSurfaceRenderElement<R>>, Vec<WaylandSurfaceRenderElement<R>>) {
+    let surface = match window.wl_surface() {
+        Some(surface) => surface,
+        None => return (vec![], vec![]),
+    };
+
+    let popup_elements = PopupManager::popups_for_surface(&surface)
+        .flat_map(|(popup, popup_offset)| {
+            let offset = (window.geometry().loc + popup_offset - popup.geometry().loc)
+                .to_physical_precise_round(scale);
+
+            render_elements_from_surface_tree(
+                renderer,
+                popup.wl_surface(),
+                location + offset,
+                scale,
+                alpha,
+                Kind::Unspecified,
+            )
+        })
+        .collect();
+
+    let surface_elements = render_elements_from_surface_tree(
+        renderer,
+        &surface,
+        location,
+        scale,
+        alpha,
+        Kind::Unspecified,
+    );
+
+    (popup_elements, surface_elements)
 }
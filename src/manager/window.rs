@@ -2,14 +2,17 @@ use std::{cell::RefCell, collections::HashMap, rc::Rc, sync::Arc};
 
 use smithay::{
     desktop::{Window, WindowSurface},
-    reexports::wayland_server::protocol::wl_surface::WlSurface,
-    utils::{Logical, Point, Rectangle, Size},
+    reexports::{
+        wayland_protocols::xdg::shell::server::xdg_toplevel,
+        wayland_server::protocol::wl_surface::WlSurface,
+    },
+    utils::{Logical, Point, Rectangle, Serial, Size},
     wayland::{
         compositor::{self, with_states}, foreign_toplevel_list::ForeignToplevelHandle, shell::xdg::{ToplevelSurface, XdgToplevelSurfaceData}
     }, xwayland::X11Surface,
 };
 
-use crate::{config::windowrules::WindowRulesConfigs, layout::{container_tree::ExpansionCache, WindowLayout}, state::{GlobalData, State}};
+use crate::{config::windowrules::WindowRulesConfigs, layout::{container_tree::ExpansionCache, Presel, WindowLayout}, render::title_bar::title_bar_rect, state::{GlobalData, State}};
 
 use super::workspace::WorkspaceId;
 
@@ -36,13 +39,28 @@ impl From<X11Surface> for CustomWindowSurface {
     }
 }
 
+/// Height of the compositor drawn title bar, 0 without one.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TitleBarHeight(pub i32);
+
+/// Set for tiled windows behind the active tab of a tabbed or stacked container.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Hidden(pub bool);
+
 pub trait WindowExt {
     fn set_layout(&self, layout: WindowLayout);
     fn get_layout(&self) -> WindowLayout;
     fn set_rect_cache(&self, rect: Rectangle<i32, Logical>);
-    fn send_rect(&self, rect: Rectangle<i32, Logical>);
+    fn send_rect(&self, rect: Rectangle<i32, Logical>) -> Option<Serial>;
+    fn has_committed(&self, serial: Serial) -> bool;
     fn get_rect(&self) -> Option<Rectangle<i32, Logical>>;
+    fn set_title_bar_height(&self, height: i32);
+    fn title_bar_height(&self) -> i32;
+    fn content_rect(&self, rect: Rectangle<i32, Logical>) -> Rectangle<i32, Logical>;
+    fn set_hidden(&self, hidden: bool);
+    fn is_hidden(&self) -> bool;
     fn get_title_and_id(&self) -> (Option<String>, Option<String>);
+    fn close(&self);
 }
 
 impl WindowExt for Window {
@@ -53,6 +71,25 @@ impl WindowExt for Window {
                 Rc::new(RefCell::new(layout.clone()))
             });
 
+        // tiled windows drop their shadows and rounded corners, sent with the next configure
+        if let Some(toplevel) = self.toplevel() {
+            let tiled = matches!(layout, WindowLayout::Tiled);
+            toplevel.with_pending_state(|state| {
+                for edge in [
+                    xdg_toplevel::State::TiledLeft,
+                    xdg_toplevel::State::TiledRight,
+                    xdg_toplevel::State::TiledTop,
+                    xdg_toplevel::State::TiledBottom,
+                ] {
+                    if tiled {
+                        state.states.set(edge);
+                    } else {
+                        state.states.unset(edge);
+                    }
+                }
+            });
+        }
+
         *layout_ref.borrow_mut() = layout;
     }
 
@@ -82,7 +119,8 @@ impl WindowExt for Window {
                 });
 
                 if is_initial {
-                    toplevel.with_pending_state(|state| state.size = Some(rect.size));
+                    let size = self.content_rect(rect).size;
+                    toplevel.with_pending_state(|state| state.size = Some(size));
                     toplevel.send_configure();
                 }
             },
@@ -90,17 +128,37 @@ impl WindowExt for Window {
         };
     }
 
-    fn send_rect(&self, rect: Rectangle<i32, Logical>) {
+    fn send_rect(&self, rect: Rectangle<i32, Logical>) -> Option<Serial> {
         // for animation, the final rect is rect_cache
+        let rect = self.content_rect(rect);
         match self.underlying_surface() {
             WindowSurface::Wayland(toplevel) => {
                 toplevel.with_pending_state(|state| state.size = Some(rect.size));
-                toplevel.send_pending_configure();
+                toplevel.send_pending_configure()
             },
             WindowSurface::X11(x11) => {
                 let _ = x11.configure(rect);
+                None
             }
+        }
+    }
+
+    fn has_committed(&self, serial: Serial) -> bool {
+        // x11 windows are configured without a serial
+        let Some(toplevel) = self.toplevel() else {
+            return true;
         };
+
+        with_states(toplevel.wl_surface(), |states| {
+            states
+                .data_map
+                .get::<XdgToplevelSurfaceData>()
+                .unwrap()
+                .lock()
+                .unwrap()
+                .current_serial
+                .is_some_and(|current| current.is_no_older_than(&serial))
+        })
     }
 
     fn get_rect(&self) -> Option<Rectangle<i32, Logical>>{
@@ -108,6 +166,48 @@ impl WindowExt for Window {
         self.user_data().get::<Rc<RefCell<Rectangle<i32, Logical>>>>().and_then(|rect| Some(rect.borrow().clone()))
     }
 
+    fn set_title_bar_height(&self, height: i32) {
+        let height_ref = self
+            .user_data()
+            .get_or_insert::<Rc<RefCell<TitleBarHeight>>, _>(|| {
+                Rc::new(RefCell::new(TitleBarHeight(height)))
+            });
+
+        height_ref.borrow_mut().0 = height;
+    }
+
+    fn title_bar_height(&self) -> i32 {
+        self.user_data()
+            .get::<Rc<RefCell<TitleBarHeight>>>()
+            .map_or(0, |height| height.borrow().0)
+    }
+
+    fn content_rect(&self, rect: Rectangle<i32, Logical>) -> Rectangle<i32, Logical> {
+        // the title bar takes the top of the rect, the client gets the rest
+        let height = self.title_bar_height().min(rect.size.h);
+
+        let mut content = rect;
+        content.loc.y += height;
+        content.size.h -= height;
+        content
+    }
+
+    fn set_hidden(&self, hidden: bool) {
+        let hidden_ref = self
+            .user_data()
+            .get_or_insert::<Rc<RefCell<Hidden>>, _>(|| {
+                Rc::new(RefCell::new(Hidden(hidden)))
+            });
+
+        hidden_ref.borrow_mut().0 = hidden;
+    }
+
+    fn is_hidden(&self) -> bool {
+        self.user_data()
+            .get::<Rc<RefCell<Hidden>>>()
+            .map_or(false, |hidden| hidden.borrow().0)
+    }
+
     fn get_title_and_id(&self) -> (Option<String>, Option<String>) {
         match self.underlying_surface() {
             WindowSurface::Wayland(toplevel) => {
@@ -136,6 +236,18 @@ impl WindowExt for Window {
             }
         }
     }
+
+    fn close(&self) {
+        match self.underlying_surface() {
+            WindowSurface::Wayland(toplevel) => {
+                toplevel.send_close();
+            },
+            #[cfg(feature = "xwayland")]
+            WindowSurface::X11(x11_surface) => {
+                let _ = x11_surface.close();
+            }
+        }
+    }
 }
 
 pub struct WindowManager {
@@ -144,6 +256,15 @@ pub struct WindowManager {
     pub window_workspace: HashMap<Window, WorkspaceId>,
     pub foreign_handle: HashMap<WlSurface, ForeignToplevelHandle>,
 
+    // mapped, but taken out of the layout until restored
+    minimized: Vec<Window>,
+    // layout and rect to restore after unmaximize
+    maximized: HashMap<Window, (WindowLayout, Rectangle<i32, Logical>)>,
+    // asked for attention, oldest first, cleared once focused
+    urgent: Vec<Window>,
+    // split chosen for the next window mapped
+    presel: Option<(Window, Presel)>,
+
     configs: Arc<WindowRulesConfigs>,
 }
 
@@ -154,6 +275,10 @@ impl WindowManager {
             unmapped: Vec::new(),
             window_workspace: HashMap::new(),
             foreign_handle: HashMap::new(),
+            minimized: Vec::new(),
+            maximized: HashMap::new(),
+            urgent: Vec::new(),
+            presel: None,
             configs
         }
     }
@@ -269,6 +394,13 @@ impl WindowManager {
                 WindowSurface::X11(_) => { }
             }
 
+            self.minimized.retain(|w| w != mapped);
+            self.maximized.remove(mapped);
+            self.urgent.retain(|w| w != mapped);
+            if self.presel.as_ref().is_some_and(|(window, _)| window == mapped) {
+                self.presel = None;
+            }
+
             if let Some(pos) = self.mapped.iter().position(|w| w == mapped) {
                 let window = self.mapped.remove(pos);
                 self.unmapped.push(window);
@@ -282,9 +414,15 @@ impl WindowManager {
     pub fn mapped_windows(&self, workspace_id: WorkspaceId) -> impl Iterator<Item = &Window> {
         self.mapped.iter().filter(move |window| {
             self.window_workspace.get(*window) == Some(&workspace_id)
+                && !self.minimized.contains(window)
+                && !window.is_hidden()
         })
     }
 
+    pub fn all_mapped(&self) -> impl Iterator<Item = &Window> {
+        self.mapped.iter()
+    }
+
     pub fn remove_unmapped(&mut self, unmapped: &Window) -> Option<Window> {
         if self.unmapped.contains(unmapped) {
             self.window_workspace.remove(unmapped);
@@ -308,10 +446,7 @@ impl WindowManager {
     }
 
     pub fn window_under(&self, pointer_loc: Point<f64, Logical>, workspace_id: WorkspaceId) -> Option<Window> {
-        for window in &self.mapped {
-            if Some(&workspace_id) != self.window_workspace.get(window) {
-                continue;
-            }
+        for window in self.mapped_windows(workspace_id) {
 
             // expansion window
             if let Some(guard) = window.user_data().get::<ExpansionCache>() {
@@ -333,12 +468,17 @@ impl WindowManager {
         None
     }
 
-    pub fn window_under_tiled(&self, pointer_loc: Point<f64, Logical>, workspace_id: WorkspaceId) -> Option<Window> {
-        for window in &self.mapped {
-            if Some(&workspace_id) != self.window_workspace.get(window) {
-                continue;
-            }
+    /// The title bar under the pointer, unless another window covers it.
+    pub fn title_bar_under(&self, pointer_loc: Point<f64, Logical>, workspace_id: WorkspaceId) -> Option<(Window, Rectangle<i32, Logical>)> {
+        let window = self.window_under(pointer_loc, workspace_id)?;
+        let rect = window.get_rect()?;
+
+        let bar = title_bar_rect(rect, window.title_bar_height());
+        bar.to_f64().contains(pointer_loc).then(|| (window, bar))
+    }
 
+    pub fn window_under_tiled(&self, pointer_loc: Point<f64, Logical>, workspace_id: WorkspaceId) -> Option<Window> {
+        for window in self.mapped_windows(workspace_id) {
             if matches!(window.get_layout(), WindowLayout::Floating) {
                 continue;
             }
@@ -371,6 +511,86 @@ impl WindowManager {
         }
     }
 
+    pub fn is_minimized(&self, window: &Window) -> bool {
+        self.minimized.contains(window)
+    }
+
+    /// Returns false if the window already was in that state.
+    pub fn set_minimized(&mut self, window: &Window, minimized: bool) -> bool {
+        match (minimized, self.is_minimized(window)) {
+            (true, false) => self.minimized.push(window.clone()),
+            (false, true) => self.minimized.retain(|w| w != window),
+            _ => return false,
+        }
+
+        true
+    }
+
+    pub fn is_maximized(&self, window: &Window) -> bool {
+        self.maximized.contains_key(window)
+    }
+
+    pub fn set_maximized(&mut self, window: &Window, restore: (WindowLayout, Rectangle<i32, Logical>)) {
+        self.maximized.insert(window.clone(), restore);
+    }
+
+    pub fn take_maximized(&mut self, window: &Window) -> Option<(WindowLayout, Rectangle<i32, Logical>)> {
+        self.maximized.remove(window)
+    }
+
+    pub fn is_urgent(&self, window: &Window) -> bool {
+        self.urgent.contains(window)
+    }
+
+    pub fn set_urgent(&mut self, window: &Window, urgent: bool) {
+        self.urgent.retain(|w| w != window);
+        if urgent && self.mapped.contains(window) {
+            self.urgent.push(window.clone());
+        }
+    }
+
+    /// The window that most recently asked for attention.
+    pub fn last_urgent(&self) -> Option<&Window> {
+        self.urgent.last()
+    }
+
+    pub fn has_urgent(&self, workspace_id: WorkspaceId) -> bool {
+        self.urgent.iter().any(|window| self.window_workspace.get(window) == Some(&workspace_id))
+    }
+
+    pub fn presel(&self) -> Option<&(Window, Presel)> {
+        self.presel.as_ref()
+    }
+
+    /// Preselect on a tiled window, the same edge again or none cancels.
+    pub fn set_presel(&mut self, window: &Window, presel: Option<Presel>) {
+        let same_edge = match (&self.presel, &presel) {
+            (Some((target, current)), Some(presel)) => target == window && current.edge == presel.edge,
+            _ => false,
+        };
+
+        self.presel = match presel {
+            Some(presel) if !same_edge && matches!(window.get_layout(), WindowLayout::Tiled) => {
+                Some((window.clone(), presel))
+            }
+            _ => None,
+        };
+    }
+
+    /// Take the preselection if its window is tiled on screen of the workspace.
+    pub fn take_presel(&mut self, workspace_id: WorkspaceId) -> Option<(Window, Presel)> {
+        let on_screen = self.presel.as_ref().is_some_and(|(target, _)| {
+            matches!(target.get_layout(), WindowLayout::Tiled)
+                && self.mapped_windows(workspace_id).any(|window| window == target)
+        });
+
+        if on_screen {
+            self.presel.take()
+        } else {
+            None
+        }
+    }
+
     pub fn raise_window(&mut self, window: &Window) {
         if self.mapped.contains(window) {
             self.mapped.retain(|w| w != window);
@@ -387,6 +607,34 @@ impl WindowManager {
         }
     }
 
+    /// Whether the window gets rounded corners, true unless a window rule opts out.
+    pub fn get_rounding(&self, window: &Window) -> bool {
+        let (_, app_id) = window.get_title_and_id();
+        app_id
+            .and_then(|app_id| self.configs.rounding.get(&app_id).cloned())
+            .unwrap_or(true)
+    }
+
+    /// Whether a window rule forces the background blur on or off.
+    pub fn get_blur(&self, window: &Window) -> Option<bool> {
+        let (_, app_id) = window.get_title_and_id();
+        app_id.and_then(|app_id| self.configs.blur.get(&app_id).cloned())
+    }
+
+    /// Whether a window rule forces client side decorations on or off, by app_id
+    /// since decorations are negotiated before the window is mapped.
+    pub fn get_client_side(&self, app_id: &str) -> Option<bool> {
+        self.configs.client_side.get(app_id).cloned()
+    }
+
+    /// Whether the window is dimmed while inactive, true unless a window rule exempts it.
+    pub fn get_dim(&self, window: &Window) -> bool {
+        let (_, app_id) = window.get_title_and_id();
+        app_id
+            .and_then(|app_id| self.configs.dim.get(&app_id).cloned())
+            .unwrap_or(true)
+    }
+
     pub fn get_fullscreen(&self, window: &Window) -> Option<bool> {
         let (_, app_id) = window.get_title_and_id();
         if let Some(app_id) = app_id {
@@ -399,6 +647,14 @@ impl WindowManager {
     pub fn get_foreign_handle(&self, surface: &WlSurface) -> Option<&ForeignToplevelHandle> {
         self.foreign_handle.get(surface)
     }
+
+    pub fn get_foreign_window(&self, identifier: &str) -> Option<&Window> {
+        let (surface, _) = self.foreign_handle
+            .iter()
+            .find(|(_, handle)| handle.identifier() == identifier)?;
+
+        self.get_mapped(&CustomWindowSurface::WlSurface(surface.clone()))
+    }
 }
 
 fn compute_child_rect(parent_window: &Window, size_opt: Option<Size<i32, Logical>>) -> Option<Rectangle<i32, Logical>>{
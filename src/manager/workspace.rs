@@ -1,7 +1,7 @@
 use std::{
     collections::HashMap, hash::Hash, sync::{
         atomic::{AtomicUsize, Ordering}, Arc
-    }, time::Duration
+    }
 };
 
 use smithay::{
@@ -10,13 +10,14 @@ use smithay::{
 };
 
 use crate::{
-    config::workspace::WorkspaceConfigs, layout::{
-        container_tree::ContainerTree, Direction, ResizeEdge, TiledScheme
+    config::{animation::AnimationEvent, workspace::WorkspaceConfigs}, layout::{
+        container_tree::{ContainerTree, ExpansionCache}, overview::Overview, tiled_tree::TabStrip,
+        ContainerMode, Direction, Presel, ResizeEdge, TiledScheme
     }, 
-    manager::animation::{AnimationManager, AnimationType}
+    manager::animation::AnimationManager
 };
 
-use super::window::WindowExt;
+use super::window::{WindowExt, WindowManager};
 
 static NEXT_ID: AtomicUsize = AtomicUsize::new(1);
 
@@ -32,6 +33,11 @@ impl WorkspaceId {
     pub fn next() -> Self {
         Self(NEXT_ID.fetch_add(1, Ordering::Relaxed))
     }
+
+    /// The number shown to users, as in "switch workspace".
+    pub fn index(&self) -> usize {
+        self.0
+    }
 }
 
 #[derive(Debug)]
@@ -69,7 +75,7 @@ impl Workspace {
         Self {
             workspace_id,
             scheme,
-            container_tree: ContainerTree::new(root_rect, gap),
+            container_tree: ContainerTree::new(root_rect, gap, configs.tab_height),
             output_working_geometry: output_geometry,
 
             configs,
@@ -93,19 +99,29 @@ impl Workspace {
         target: Option<&Window>,
         window: Window,
         edge: ResizeEdge, 
+        ratio: Option<f64>,
         animation_manager: &mut AnimationManager,
     ) -> bool {
-        self.container_tree.insert(target, window, edge, &self.scheme, animation_manager)
+        self.container_tree.insert(target, window, edge, ratio, &self.scheme, animation_manager)
     }
 
     pub fn unmap_window(&mut self, target: &Window, animation_manager: &mut AnimationManager) {
-        self.container_tree.remove(target, animation_manager);
+        self.container_tree.remove(target, &self.scheme, animation_manager);
     }
 
     pub fn invert_window(&mut self, target: &Window, animation_manager: &mut AnimationManager) {
         self.container_tree.invert(target, animation_manager);
     }
 
+    pub fn scheme(&self) -> &TiledScheme {
+        &self.scheme
+    }
+
+    pub fn set_scheme(&mut self, scheme: TiledScheme, animation_manager: &mut AnimationManager) {
+        self.scheme = scheme;
+        self.container_tree.arrange(&self.scheme, animation_manager);
+    }
+
     pub fn exchange_window(
         &mut self,
         target: &Window,
@@ -118,12 +134,28 @@ impl Workspace {
         self.container_tree.exchange(target, direction, is_favour, animation_manager);
     }
 
-    pub fn expansion(&self, animation_manager: &mut AnimationManager) {
-        self.container_tree.expansion(animation_manager);
+    pub fn set_container_mode(&mut self, target: &Window, mode: ContainerMode, animation_manager: &mut AnimationManager) {
+        self.container_tree.set_mode(target, mode, animation_manager);
+    }
+
+    pub fn focus_in_group(&mut self, target: &Window, next: bool) -> Option<Window> {
+        self.container_tree.focus_in_group(target, next)
     }
 
-    pub fn recover(&mut self, animation_manager: &mut AnimationManager) {
-        self.container_tree.recover(animation_manager);
+    pub fn reveal_window(&mut self, target: &Window) {
+        self.container_tree.reveal(target);
+    }
+
+    pub fn tab_strips(&self) -> Vec<TabStrip> {
+        self.container_tree.tab_strips()
+    }
+
+    pub fn expansion(&self, windows: &[Window], animation_manager: &mut AnimationManager) {
+        self.container_tree.expansion(windows, animation_manager);
+    }
+
+    pub fn recover(&self, animation_manager: &mut AnimationManager) {
+        self.container_tree.recover(self.windows(), animation_manager);
     }
 
     pub fn grab_move(&mut self, target: &Window, offset: Point<i32, Logical>, animation_manager: &mut AnimationManager) {
@@ -192,6 +224,7 @@ impl Workspace {
 pub struct WorkspaceManager {
     workspaces: HashMap<WorkspaceId, Workspace>,
     activated_workspace: Option<WorkspaceId>,
+    overview: Option<Overview>,
     configs: Arc<WorkspaceConfigs>,
 }
 
@@ -200,6 +233,7 @@ impl WorkspaceManager {
         Self {
             workspaces: HashMap::new(),
             activated_workspace: None,
+            overview: None,
             configs,
         }
     }
@@ -214,7 +248,7 @@ impl WorkspaceManager {
         let workspace = Workspace::new(
             workspace_id,
             output_geometry,
-            scheme.unwrap_or_else(|| self.configs.scheme.clone()),
+            scheme.unwrap_or_else(|| self.configs.scheme_for(workspace_id)),
             self.configs.clone(),
         );
 
@@ -237,6 +271,8 @@ impl WorkspaceManager {
     }
 
     pub fn switch_workspace(&mut self, workspace_id: WorkspaceId, output_geometry: Rectangle<i32, Logical>, animation_manager: &mut AnimationManager) {
+        self.tiled_recover(animation_manager);
+
         if !self.workspaces.contains_key(&workspace_id) {
             self.add_workspace(
                 workspace_id,
@@ -265,8 +301,7 @@ impl WorkspaceManager {
                         window.clone(), 
                         from, 
                         to, 
-                        Duration::from_millis(30), 
-                        AnimationType::EaseInOutQuad,
+                        AnimationEvent::WorkspaceSwitch,
                     );
                 }
             }
@@ -315,6 +350,21 @@ impl WorkspaceManager {
             .expect("no current_workspace")
     }
 
+    /// All workspaces ordered by their number.
+    pub fn workspace_ids(&self) -> Vec<WorkspaceId> {
+        let mut ids: Vec<WorkspaceId> = self.workspaces.keys().cloned().collect();
+        ids.sort_by_key(|id| id.0);
+        ids
+    }
+
+    pub fn contains_workspace(&self, workspace_id: WorkspaceId) -> bool {
+        self.workspaces.contains_key(&workspace_id)
+    }
+
+    pub fn is_workspace_empty(&self, workspace_id: WorkspaceId) -> bool {
+        self.workspaces.get(&workspace_id).map_or(true, |workspace| workspace.is_empty())
+    }
+
     pub fn _workspaces_counts(&self) -> usize {
         self.workspaces.iter().count()
     }
@@ -331,7 +381,19 @@ impl WorkspaceManager {
         animation_manager: &mut AnimationManager,
     ) -> bool {
         self.current_workspace_mut()
-            .map_window(target, window, edge, animation_manager)
+            .map_window(target, window, edge, None, animation_manager)
+    }
+
+    /// Map beside target as preselected, ratio is the share of target's area the window takes.
+    pub fn map_window_preselected(
+        &mut self,
+        target: &Window,
+        window: Window,
+        presel: Presel,
+        animation_manager: &mut AnimationManager,
+    ) -> bool {
+        self.current_workspace_mut()
+            .map_window(Some(target), window, presel.edge, presel.ratio, animation_manager)
     }
 
     pub fn unmap_window(&mut self, target: &Window, animation_manager: &mut AnimationManager) {
@@ -343,6 +405,14 @@ impl WorkspaceManager {
         self.current_workspace_mut().invert_window(target, animation_manager);
     }
 
+    pub fn current_scheme(&self) -> &TiledScheme {
+        self.current_workspace().scheme()
+    }
+
+    pub fn set_scheme(&mut self, scheme: TiledScheme, animation_manager: &mut AnimationManager) {
+        self.current_workspace_mut().set_scheme(scheme, animation_manager);
+    }
+
     pub fn exchange_window(
         &mut self,
         target: &Window,
@@ -353,12 +423,157 @@ impl WorkspaceManager {
             .exchange_window(target, edge, animation_manager);
     }
 
-    pub fn tiled_expansion(&mut self, animation_manager: &mut AnimationManager) {
-        self.current_workspace_mut().expansion(animation_manager);
+    pub fn set_container_mode(&mut self, target: &Window, mode: ContainerMode, animation_manager: &mut AnimationManager) {
+        self.current_workspace_mut()
+            .set_container_mode(target, mode, animation_manager);
+    }
+
+    pub fn focus_in_group(&mut self, target: &Window, next: bool) -> Option<Window> {
+        self.current_workspace_mut().focus_in_group(target, next)
+    }
+
+    pub fn reveal_window(&mut self, target: &Window) {
+        self.current_workspace_mut().reveal_window(target);
+    }
+
+    pub fn tab_strips(&self) -> Vec<TabStrip> {
+        self.current_workspace().tab_strips()
+    }
+
+    /// The tab under point and the window it stands for.
+    pub fn tab_under(&self, point: Point<f64, Logical>) -> Option<(Window, Rectangle<i32, Logical>)> {
+        self.tab_strips()
+            .into_iter()
+            .flat_map(|strip| strip.tabs)
+            .find(|(_, rect)| rect.to_f64().contains(point))
+    }
+
+    pub fn overview(&self) -> Option<&Overview> {
+        self.overview.as_ref()
+    }
+
+    /// Windows shown on screen, back to front.
+    pub fn drawn_windows(&self, window_manager: &WindowManager) -> Vec<Window> {
+        match &self.overview {
+            Some(overview) => overview.windows.clone(),
+            None => window_manager
+                .mapped_windows(self.current_workspace().id())
+                .cloned()
+                .collect(),
+        }
+    }
+
+    pub fn tiled_expansion(&mut self, all_workspaces: bool, focus: Option<&Window>, animation_manager: &mut AnimationManager) {
+        if self.overview.is_some() {
+            return;
+        }
+
+        self.overview = Some(Overview::new(all_workspaces));
+        self.layout_overview(None, animation_manager);
+
+        if let (Some(overview), Some(focus)) = (self.overview.as_mut(), focus) {
+            overview.select(focus);
+        }
     }
 
     pub fn tiled_recover(&mut self, animation_manager: &mut AnimationManager) {
-        self.current_workspace_mut().recover(animation_manager);
+        if self.overview.take().is_none() {
+            return;
+        }
+
+        self.current_workspace().recover(animation_manager);
+
+        // windows from other workspaces just leave the overview
+        for window in self.workspaces.values().flat_map(|workspace| workspace.windows()) {
+            if let Some(guard) = window.user_data().get::<ExpansionCache>() {
+                guard.0.borrow_mut().take();
+            }
+        }
+    }
+
+    pub fn overview_push_filter(&mut self, ch: char, animation_manager: &mut AnimationManager) {
+        if let Some(overview) = self.overview.as_mut() {
+            overview.filter.push(ch);
+            overview.selected = 0;
+            self.layout_overview(None, animation_manager);
+        }
+    }
+
+    pub fn overview_pop_filter(&mut self, animation_manager: &mut AnimationManager) {
+        if let Some(overview) = self.overview.as_mut() {
+            if overview.filter.pop().is_some() {
+                overview.selected = 0;
+                self.layout_overview(None, animation_manager);
+            }
+        }
+    }
+
+    pub fn overview_move_selection(&mut self, edge: &ResizeEdge) {
+        if let Some(overview) = self.overview.as_mut() {
+            overview.move_selection(edge);
+        }
+    }
+
+    pub fn overview_window_under(&self, point: Point<f64, Logical>) -> Option<Window> {
+        self.overview
+            .as_ref()
+            .and_then(|overview| overview.window_under(point).cloned())
+    }
+
+    pub fn overview_remove(&mut self, window: &Window, animation_manager: &mut AnimationManager) {
+        if self.overview.as_ref().is_some_and(|overview| overview.windows.contains(window)) {
+            if let Some(guard) = window.user_data().get::<ExpansionCache>() {
+                guard.0.borrow_mut().take();
+            }
+
+            // the window may still be in its workspace at this point
+            self.layout_overview(Some(window), animation_manager);
+        }
+    }
+
+    fn layout_overview(&mut self, removed: Option<&Window>, animation_manager: &mut AnimationManager) {
+        let Some(current_id) = self.activated_workspace else {
+            return;
+        };
+        let all_workspaces = match &self.overview {
+            Some(overview) => overview.all_workspaces,
+            None => return,
+        };
+
+        // current workspace first, then the others by id
+        let mut ids: Vec<WorkspaceId> = self.workspaces
+            .keys()
+            .filter(|id| all_workspaces && **id != current_id)
+            .cloned()
+            .collect();
+        ids.sort_by_key(|id| id.0);
+        ids.insert(0, current_id);
+
+        let candidates: Vec<Window> = ids
+            .iter()
+            .filter_map(|id| self.workspaces.get(id))
+            .flat_map(|workspace| workspace.windows())
+            .filter(|window| Some(*window) != removed)
+            .cloned()
+            .collect();
+
+        let overview = self.overview.as_mut().unwrap();
+        let (visible, hidden): (Vec<Window>, Vec<Window>) = candidates
+            .into_iter()
+            .partition(|window| overview.matches(window));
+
+        for window in &hidden {
+            if let Some(guard) = window.user_data().get::<ExpansionCache>() {
+                guard.0.borrow_mut().take();
+            }
+        }
+
+        overview.windows = visible;
+        overview.selected = overview.selected.min(overview.windows.len().saturating_sub(1));
+
+        if let Some(workspace) = self.workspaces.get(&current_id) {
+            workspace.expansion(&overview.windows, animation_manager);
+        }
     }
 
     pub fn grab_move(&mut self, target: &Window, offset: Point<i32, Logical>, animation_manager: &mut AnimationManager) {
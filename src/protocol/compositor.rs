@@ -95,6 +95,7 @@ impl CompositorHandler for GlobalData {
 
         on_commit_buffer_handler::<Self>(surface);
         self.backend.early_import(surface);
+        self.queue_redraw();
         if !is_sync_subsurface(surface) {
             let mut root = surface.clone();
             while let Some(parent) = get_parent(&root) {
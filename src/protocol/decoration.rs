@@ -0,0 +1,138 @@
+use smithay::{
+    delegate_kde_decoration, delegate_xdg_decoration,
+    desktop::{Window, WindowSurface},
+    reexports::{
+        wayland_protocols::xdg::decoration::zv1::server::zxdg_toplevel_decoration_v1::Mode as DecorationMode,
+        wayland_protocols_misc::server_decoration::server::org_kde_kwin_server_decoration::{
+            Mode as KdeMode, OrgKdeKwinServerDecoration,
+        },
+        wayland_server::{protocol::wl_surface::WlSurface, WEnum},
+    },
+    wayland::{
+        compositor::with_states,
+        shell::{
+            kde::decoration::{KdeDecorationHandler, KdeDecorationState},
+            xdg::{decoration::XdgDecorationHandler, ToplevelSurface, XdgToplevelSurfaceData},
+        },
+    },
+};
+
+use crate::{manager::window::WindowExt, state::GlobalData};
+
+impl XdgDecorationHandler for GlobalData {
+    fn new_decoration(&mut self, toplevel: ToplevelSurface) {
+        let mode = self.decoration_mode(toplevel.wl_surface());
+        set_decoration_mode(&toplevel, mode);
+        self.refresh_title_bar_of(&toplevel);
+    }
+
+    fn request_mode(&mut self, toplevel: ToplevelSurface, _mode: DecorationMode) {
+        // the mode is ours to pick, clients only get a say through the config
+        let mode = self.decoration_mode(toplevel.wl_surface());
+        set_decoration_mode(&toplevel, mode);
+        self.refresh_title_bar_of(&toplevel);
+    }
+
+    fn unset_mode(&mut self, toplevel: ToplevelSurface) {
+        let mode = self.decoration_mode(toplevel.wl_surface());
+        set_decoration_mode(&toplevel, mode);
+        self.refresh_title_bar_of(&toplevel);
+    }
+}
+delegate_xdg_decoration!(GlobalData);
+
+impl KdeDecorationHandler for GlobalData {
+    fn kde_decoration_state(&self) -> &KdeDecorationState {
+        &self.state.kde_decoration_state
+    }
+
+    fn new_decoration(&mut self, surface: &WlSurface, decoration: &OrgKdeKwinServerDecoration) {
+        decoration.mode(kde_mode(self.decoration_mode(surface)));
+    }
+
+    fn request_mode(&mut self, surface: &WlSurface, decoration: &OrgKdeKwinServerDecoration, _mode: WEnum<KdeMode>) {
+        decoration.mode(kde_mode(self.decoration_mode(surface)));
+    }
+}
+delegate_kde_decoration!(GlobalData);
+
+fn set_decoration_mode(toplevel: &ToplevelSurface, mode: DecorationMode) {
+    toplevel.with_pending_state(|state| state.decoration_mode = Some(mode));
+
+    // before the initial configure the mode goes out with it
+    if toplevel.is_initial_configure_sent() {
+        toplevel.send_pending_configure();
+    }
+}
+
+fn kde_mode(mode: DecorationMode) -> KdeMode {
+    match mode {
+        DecorationMode::ClientSide => KdeMode::Client,
+        _ => KdeMode::Server,
+    }
+}
+
+impl GlobalData {
+    /// Give server side decorated windows a title bar when they are enabled, and resize
+    /// the client if that changed its area.
+    pub fn refresh_title_bar(&mut self, window: &Window) {
+        let title_bar = &self.configs.conf_decoration.title_bar;
+
+        let server_side = match window.underlying_surface() {
+            WindowSurface::Wayland(toplevel) => toplevel
+                .with_pending_state(|state| state.decoration_mode)
+                .map_or(false, |mode| mode == DecorationMode::ServerSide),
+            #[cfg(feature = "xwayland")]
+            WindowSurface::X11(x11_surface) => !x11_surface.is_decorated(),
+        };
+
+        let height = if title_bar.enabled && server_side { title_bar.height } else { 0 };
+        if height == window.title_bar_height() {
+            return;
+        }
+
+        window.set_title_bar_height(height);
+        if let Some(rect) = window.get_rect() {
+            window.send_rect(rect);
+        }
+    }
+
+    /// Pick the mode again once the app_id is known, rules match on it and clients
+    /// often create the decoration before setting it.
+    pub fn refresh_decoration_mode(&mut self, toplevel: &ToplevelSurface) {
+        let negotiated = toplevel.with_pending_state(|state| state.decoration_mode.is_some());
+        if !negotiated || toplevel.is_initial_configure_sent() {
+            return;
+        }
+
+        let mode = self.decoration_mode(toplevel.wl_surface());
+        set_decoration_mode(toplevel, mode);
+    }
+
+    fn refresh_title_bar_of(&mut self, toplevel: &ToplevelSurface) {
+        if let Some(window) = self.window_manager.get_mapped(&toplevel.clone().into()).cloned() {
+            self.refresh_title_bar(&window);
+        }
+    }
+
+    /// Server side unless the config or a window rule forces client side decorations.
+    fn decoration_mode(&self, surface: &WlSurface) -> DecorationMode {
+        // the toplevel may not be mapped yet, so the rule is matched on the raw app_id
+        let app_id = with_states(surface, |states| {
+            states
+                .data_map
+                .get::<XdgToplevelSurfaceData>()
+                .and_then(|data| data.lock().unwrap().app_id.clone())
+        });
+
+        let client_side = app_id
+            .and_then(|app_id| self.window_manager.get_client_side(&app_id))
+            .unwrap_or(self.configs.conf_decoration.client_side);
+
+        if client_side {
+            DecorationMode::ClientSide
+        } else {
+            DecorationMode::ServerSide
+        }
+    }
+}
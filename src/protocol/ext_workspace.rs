@@ -0,0 +1,378 @@
+use std::collections::HashMap;
+
+use smithay::{
+    output::Output,
+    reexports::{
+        wayland_protocols::ext::workspace::v1::server::{
+            ext_workspace_group_handle_v1::{self, ExtWorkspaceGroupHandleV1, GroupCapabilities},
+            ext_workspace_handle_v1::{self, ExtWorkspaceHandleV1, WorkspaceCapabilities},
+            ext_workspace_manager_v1::{self, ExtWorkspaceManagerV1},
+        },
+        wayland_server::{
+            backend::{ClientId, GlobalId},
+            Client, DataInit, Dispatch, DisplayHandle, GlobalDispatch, New, Resource,
+        },
+    },
+    utils::SERIAL_COUNTER,
+};
+
+use crate::{manager::workspace::WorkspaceId, state::GlobalData};
+
+const VERSION: u32 = 1;
+
+#[derive(Debug, Clone, Copy)]
+enum WorkspaceAction {
+    Activate(WorkspaceId),
+    Remove(WorkspaceId),
+    Create(WorkspaceId),
+}
+
+/// What clients were last told about a workspace.
+#[derive(Debug, Clone, PartialEq)]
+struct WorkspaceInfo {
+    id: WorkspaceId,
+    state: ext_workspace_handle_v1::State,
+}
+
+struct ManagerEntry {
+    manager: ExtWorkspaceManagerV1,
+    // one group for the only output
+    group: Option<ExtWorkspaceGroupHandleV1>,
+    workspaces: HashMap<WorkspaceId, ExtWorkspaceHandleV1>,
+    // applied on commit
+    pending: Vec<WorkspaceAction>,
+}
+
+pub struct ExtWorkspaceState {
+    #[allow(dead_code)]
+    global: GlobalId,
+    managers: Vec<ManagerEntry>,
+    workspaces: Vec<WorkspaceInfo>,
+    output: Option<Output>,
+}
+
+impl ExtWorkspaceState {
+    pub fn new(display_handle: &DisplayHandle) -> Self {
+        let global = display_handle.create_global::<GlobalData, ExtWorkspaceManagerV1, _>(VERSION, ());
+
+        Self {
+            global,
+            managers: Vec::new(),
+            workspaces: Vec::new(),
+            output: None,
+        }
+    }
+
+    fn entry_mut(&mut self, manager: &ExtWorkspaceManagerV1) -> Option<&mut ManagerEntry> {
+        self.managers.iter_mut().find(|entry| &entry.manager == manager)
+    }
+
+    /// The manager a group handle was announced by.
+    fn entry_of_group(&mut self, group: &ExtWorkspaceGroupHandleV1) -> Option<&mut ManagerEntry> {
+        self.managers
+            .iter_mut()
+            .find(|entry| entry.group.as_ref() == Some(group))
+    }
+
+    /// The manager a workspace handle was announced by.
+    fn entry_of_workspace(&mut self, workspace: &ExtWorkspaceHandleV1) -> Option<&mut ManagerEntry> {
+        self.managers
+            .iter_mut()
+            .find(|entry| entry.workspaces.values().any(|handle| handle == workspace))
+    }
+}
+
+impl GlobalDispatch<ExtWorkspaceManagerV1, ()> for GlobalData {
+    fn bind(
+        state: &mut Self,
+        handle: &DisplayHandle,
+        client: &Client,
+        resource: New<ExtWorkspaceManagerV1>,
+        _global_data: &(),
+        data_init: &mut DataInit<'_, Self>,
+    ) {
+        let manager = data_init.init(resource, ());
+
+        let workspace_state = &mut state.state.ext_workspace_state;
+        let mut entry = ManagerEntry {
+            manager,
+            group: None,
+            workspaces: HashMap::new(),
+            pending: Vec::new(),
+        };
+
+        if let Some(output) = workspace_state.output.clone() {
+            entry.group = new_group(handle, client, &entry.manager, &output);
+        }
+        for info in &workspace_state.workspaces {
+            if let Some(workspace) = new_workspace(handle, client, &entry, info) {
+                entry.workspaces.insert(info.id, workspace);
+            }
+        }
+        entry.manager.done();
+
+        workspace_state.managers.push(entry);
+    }
+}
+
+impl Dispatch<ExtWorkspaceManagerV1, ()> for GlobalData {
+    fn request(
+        state: &mut Self,
+        _client: &Client,
+        manager: &ExtWorkspaceManagerV1,
+        request: ext_workspace_manager_v1::Request,
+        _data: &(),
+        _dhandle: &DisplayHandle,
+        _data_init: &mut DataInit<'_, Self>,
+    ) {
+        match request {
+            ext_workspace_manager_v1::Request::Commit => {
+                let actions = state.state.ext_workspace_state
+                    .entry_mut(manager)
+                    .map(|entry| std::mem::take(&mut entry.pending))
+                    .unwrap_or_default();
+
+                for action in actions {
+                    state.apply_workspace_action(action);
+                }
+            }
+            ext_workspace_manager_v1::Request::Stop => {
+                state.state.ext_workspace_state.managers.retain(|entry| &entry.manager != manager);
+                manager.finished();
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    fn destroyed(state: &mut Self, _client: ClientId, manager: &ExtWorkspaceManagerV1, _data: &()) {
+        state.state.ext_workspace_state.managers.retain(|entry| &entry.manager != manager);
+    }
+}
+
+impl Dispatch<ExtWorkspaceGroupHandleV1, ()> for GlobalData {
+    fn request(
+        state: &mut Self,
+        _client: &Client,
+        group: &ExtWorkspaceGroupHandleV1,
+        request: ext_workspace_group_handle_v1::Request,
+        _data: &(),
+        _dhandle: &DisplayHandle,
+        _data_init: &mut DataInit<'_, Self>,
+    ) {
+        match request {
+            ext_workspace_group_handle_v1::Request::CreateWorkspace { workspace } => {
+                // workspaces are numbered, a name that is no number takes the next free one
+                let workspace_id = workspace
+                    .trim()
+                    .parse::<usize>()
+                    .ok()
+                    .filter(|index| *index >= 1)
+                    .map(WorkspaceId::new)
+                    .unwrap_or_else(|| state.next_free_workspace());
+
+                if let Some(entry) = state.state.ext_workspace_state.entry_of_group(group) {
+                    entry.pending.push(WorkspaceAction::Create(workspace_id));
+                }
+            }
+            ext_workspace_group_handle_v1::Request::Destroy => {}
+            _ => unreachable!(),
+        }
+    }
+}
+
+impl Dispatch<ExtWorkspaceHandleV1, WorkspaceId> for GlobalData {
+    fn request(
+        state: &mut Self,
+        _client: &Client,
+        workspace: &ExtWorkspaceHandleV1,
+        request: ext_workspace_handle_v1::Request,
+        workspace_id: &WorkspaceId,
+        _dhandle: &DisplayHandle,
+        _data_init: &mut DataInit<'_, Self>,
+    ) {
+        let action = match request {
+            ext_workspace_handle_v1::Request::Activate => WorkspaceAction::Activate(*workspace_id),
+            ext_workspace_handle_v1::Request::Remove => WorkspaceAction::Remove(*workspace_id),
+            // one workspace is always active and there is only one group
+            ext_workspace_handle_v1::Request::Deactivate
+            | ext_workspace_handle_v1::Request::Assign { .. }
+            | ext_workspace_handle_v1::Request::Destroy => return,
+            _ => unreachable!(),
+        };
+
+        if let Some(entry) = state.state.ext_workspace_state.entry_of_workspace(workspace) {
+            entry.pending.push(action);
+        }
+    }
+}
+
+fn new_group(
+    display_handle: &DisplayHandle,
+    client: &Client,
+    manager: &ExtWorkspaceManagerV1,
+    output: &Output,
+) -> Option<ExtWorkspaceGroupHandleV1> {
+    let group = client
+        .create_resource::<ExtWorkspaceGroupHandleV1, _, GlobalData>(display_handle, manager.version(), ())
+        .ok()?;
+    manager.workspace_group(&group);
+
+    group.capabilities(GroupCapabilities::CreateWorkspace);
+    for wl_output in output.client_outputs(client) {
+        group.output_enter(&wl_output);
+    }
+
+    Some(group)
+}
+
+fn new_workspace(
+    display_handle: &DisplayHandle,
+    client: &Client,
+    entry: &ManagerEntry,
+    info: &WorkspaceInfo,
+) -> Option<ExtWorkspaceHandleV1> {
+    let workspace = client
+        .create_resource::<ExtWorkspaceHandleV1, _, GlobalData>(display_handle, entry.manager.version(), info.id)
+        .ok()?;
+    entry.manager.workspace(&workspace);
+
+    let index = info.id.index();
+    workspace.id(index.to_string());
+    workspace.name(index.to_string());
+    workspace.coordinates(((index - 1) as u32).to_ne_bytes().to_vec());
+    workspace.capabilities(WorkspaceCapabilities::Activate | WorkspaceCapabilities::Remove);
+    workspace.state(info.state);
+
+    if let Some(group) = entry.group.as_ref() {
+        group.workspace_enter(&workspace);
+    }
+
+    Some(workspace)
+}
+
+impl GlobalData {
+    fn next_free_workspace(&self) -> WorkspaceId {
+        (1..)
+            .map(WorkspaceId::new)
+            .find(|id| !self.workspace_manager.contains_workspace(*id))
+            .unwrap()
+    }
+
+    fn apply_workspace_action(&mut self, action: WorkspaceAction) {
+        let serial = SERIAL_COUNTER.next_serial();
+
+        match action {
+            // empty workspaces only live while they are active, so creating one switches to it
+            WorkspaceAction::Activate(workspace_id) | WorkspaceAction::Create(workspace_id) => {
+                if workspace_id != self.workspace_manager.current_workspace().id() {
+                    self.switch_to_workspace(workspace_id, serial);
+                }
+            }
+            WorkspaceAction::Remove(workspace_id) => {
+                // windows are never dropped with their workspace
+                if !self.workspace_manager.is_workspace_empty(workspace_id) {
+                    debug!("not removing workspace {} with windows on it", workspace_id.index());
+                    return;
+                }
+
+                // an empty inactive workspace is removed on its own
+                if workspace_id == self.workspace_manager.current_workspace().id() {
+                    let other = self.workspace_manager
+                        .workspace_ids()
+                        .into_iter()
+                        .find(|id| *id != workspace_id);
+
+                    if let Some(other) = other {
+                        self.switch_to_workspace(other, serial);
+                    }
+                }
+            }
+        }
+
+        self.refresh_ext_workspaces();
+    }
+
+    /// Send added, removed and changed workspaces to workspace clients.
+    pub fn refresh_ext_workspaces(&mut self) {
+        let _span = tracy_client::span!("refresh_ext_workspaces");
+
+        let current = self.workspace_manager.current_workspace().id();
+        let workspaces: Vec<WorkspaceInfo> = self.workspace_manager
+            .workspace_ids()
+            .into_iter()
+            .map(|id| {
+                let mut state = ext_workspace_handle_v1::State::empty();
+                // a single output shows nothing but the active workspace
+                if id == current {
+                    state |= ext_workspace_handle_v1::State::Active;
+                } else {
+                    state |= ext_workspace_handle_v1::State::Hidden;
+                }
+                if self.window_manager.has_urgent(id) {
+                    state |= ext_workspace_handle_v1::State::Urgent;
+                }
+                WorkspaceInfo { id, state }
+            })
+            .collect();
+        let output = self.output_manager.current_output().clone();
+
+        let workspace_state = &mut self.state.ext_workspace_state;
+        let output_changed = workspace_state.output.as_ref() != Some(&output);
+        if workspace_state.workspaces == workspaces && !output_changed {
+            return;
+        }
+
+        for entry in &mut workspace_state.managers {
+            let Some(client) = entry.manager.client() else {
+                continue;
+            };
+
+            if output_changed {
+                if let Some(group) = entry.group.take() {
+                    for workspace in entry.workspaces.values() {
+                        group.workspace_leave(workspace);
+                    }
+                    group.removed();
+                }
+                entry.group = new_group(&self.display_handle, &client, &entry.manager, &output);
+                for workspace in entry.workspaces.values() {
+                    if let Some(group) = entry.group.as_ref() {
+                        group.workspace_enter(workspace);
+                    }
+                }
+            }
+
+            entry.workspaces.retain(|id, workspace| {
+                let alive = workspaces.iter().any(|info| info.id == *id);
+                if !alive {
+                    if let Some(group) = entry.group.as_ref() {
+                        group.workspace_leave(workspace);
+                    }
+                    workspace.removed();
+                }
+                alive
+            });
+
+            for info in &workspaces {
+                match entry.workspaces.get(&info.id) {
+                    Some(workspace) => {
+                        let last = workspace_state.workspaces.iter().find(|last| last.id == info.id);
+                        if last.map(|last| last.state) != Some(info.state) {
+                            workspace.state(info.state);
+                        }
+                    }
+                    None => {
+                        if let Some(workspace) = new_workspace(&self.display_handle, &client, entry, info) {
+                            entry.workspaces.insert(info.id, workspace);
+                        }
+                    }
+                }
+            }
+
+            entry.manager.done();
+        }
+
+        workspace_state.workspaces = workspaces;
+        workspace_state.output = Some(output);
+    }
+}
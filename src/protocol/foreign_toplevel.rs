@@ -1,13 +1,328 @@
+use std::collections::HashMap;
+
 use smithay::{
-    delegate_foreign_toplevel_list, wayland::foreign_toplevel_list::ForeignToplevelListHandler,
+    delegate_foreign_toplevel_list,
+    desktop::Window,
+    output::Output,
+    reexports::{
+        wayland_protocols_wlr::foreign_toplevel::v1::server::{
+            zwlr_foreign_toplevel_handle_v1::{self, ZwlrForeignToplevelHandleV1},
+            zwlr_foreign_toplevel_manager_v1::{self, ZwlrForeignToplevelManagerV1},
+        },
+        wayland_server::{
+            backend::{ClientId, GlobalId},
+            Client, DataInit, Dispatch, DisplayHandle, GlobalDispatch, New, Resource,
+        },
+    },
+    utils::SERIAL_COUNTER,
+    wayland::{
+        foreign_toplevel_list::{ForeignToplevelListHandler, ForeignToplevelListState},
+        seat::WaylandFocus,
+        shell::xdg::XdgShellHandler,
+    },
+};
+
+use crate::{
+    input::focus::KeyboardFocusTarget,
+    manager::window::WindowExt,
+    protocol::FullscreenSurface,
+    state::GlobalData,
 };
 
-use crate::state::GlobalData;
+const VERSION: u32 = 3;
 
 impl ForeignToplevelListHandler for GlobalData {
-    fn foreign_toplevel_list_state(&mut self) -> &mut smithay::wayland::foreign_toplevel_list::ForeignToplevelListState {
+    fn foreign_toplevel_list_state(&mut self) -> &mut ForeignToplevelListState {
         &mut self.state.foreign_toplevel_state
     }
 }
 delegate_foreign_toplevel_list!(GlobalData);
 
+/// What was last sent about a toplevel, to only send changes.
+#[derive(Default, PartialEq)]
+struct ToplevelInfo {
+    title: String,
+    app_id: String,
+    states: Vec<zwlr_foreign_toplevel_handle_v1::State>,
+    output: Option<Output>,
+}
+
+struct WlrToplevel {
+    info: ToplevelInfo,
+    handles: Vec<ZwlrForeignToplevelHandleV1>,
+}
+
+pub struct WlrForeignToplevelState {
+    #[allow(dead_code)]
+    global: GlobalId,
+    managers: Vec<ZwlrForeignToplevelManagerV1>,
+    toplevels: HashMap<Window, WlrToplevel>,
+}
+
+impl WlrForeignToplevelState {
+    pub fn new(display_handle: &DisplayHandle) -> Self {
+        let global = display_handle.create_global::<GlobalData, ZwlrForeignToplevelManagerV1, _>(VERSION, ());
+
+        Self {
+            global,
+            managers: Vec::new(),
+            toplevels: HashMap::new(),
+        }
+    }
+
+    fn window_of(&self, handle: &ZwlrForeignToplevelHandleV1) -> Option<Window> {
+        self.toplevels
+            .iter()
+            .find(|(_, toplevel)| toplevel.handles.contains(handle))
+            .map(|(window, _)| window.clone())
+    }
+}
+
+impl GlobalDispatch<ZwlrForeignToplevelManagerV1, ()> for GlobalData {
+    fn bind(
+        state: &mut Self,
+        handle: &DisplayHandle,
+        client: &Client,
+        resource: New<ZwlrForeignToplevelManagerV1>,
+        _global_data: &(),
+        data_init: &mut DataInit<'_, Self>,
+    ) {
+        let manager = data_init.init(resource, ());
+
+        // announce everything that is already mapped
+        for toplevel in state.state.wlr_foreign_toplevel_state.toplevels.values_mut() {
+            if let Some(toplevel_handle) = new_handle(handle, client, &manager, &toplevel.info) {
+                toplevel.handles.push(toplevel_handle);
+            }
+        }
+
+        state.state.wlr_foreign_toplevel_state.managers.push(manager);
+    }
+}
+
+impl Dispatch<ZwlrForeignToplevelManagerV1, ()> for GlobalData {
+    fn request(
+        state: &mut Self,
+        _client: &Client,
+        manager: &ZwlrForeignToplevelManagerV1,
+        request: zwlr_foreign_toplevel_manager_v1::Request,
+        _data: &(),
+        _dhandle: &DisplayHandle,
+        _data_init: &mut DataInit<'_, Self>,
+    ) {
+        match request {
+            zwlr_foreign_toplevel_manager_v1::Request::Stop => {
+                state.state.wlr_foreign_toplevel_state.managers.retain(|m| m != manager);
+                manager.finished();
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    fn destroyed(state: &mut Self, _client: ClientId, manager: &ZwlrForeignToplevelManagerV1, _data: &()) {
+        state.state.wlr_foreign_toplevel_state.managers.retain(|m| m != manager);
+    }
+}
+
+impl Dispatch<ZwlrForeignToplevelHandleV1, ()> for GlobalData {
+    fn request(
+        state: &mut Self,
+        _client: &Client,
+        handle: &ZwlrForeignToplevelHandleV1,
+        request: zwlr_foreign_toplevel_handle_v1::Request,
+        _data: &(),
+        _dhandle: &DisplayHandle,
+        _data_init: &mut DataInit<'_, Self>,
+    ) {
+        let Some(window) = state.state.wlr_foreign_toplevel_state.window_of(handle) else {
+            return;
+        };
+
+        match request {
+            zwlr_foreign_toplevel_handle_v1::Request::SetMaximized => state.set_maximized(&window, true),
+            zwlr_foreign_toplevel_handle_v1::Request::UnsetMaximized => state.set_maximized(&window, false),
+            zwlr_foreign_toplevel_handle_v1::Request::SetMinimized => state.set_minimized(&window, true),
+            zwlr_foreign_toplevel_handle_v1::Request::UnsetMinimized => state.set_minimized(&window, false),
+            zwlr_foreign_toplevel_handle_v1::Request::Activate { .. } => {
+                state.activate_window(&window, SERIAL_COUNTER.next_serial());
+            }
+            zwlr_foreign_toplevel_handle_v1::Request::Close => window.close(),
+            zwlr_foreign_toplevel_handle_v1::Request::SetRectangle { width, height, .. } => {
+                // only a hint for minimize animations, there are none yet
+                if width < 0 || height < 0 {
+                    handle.post_error(
+                        zwlr_foreign_toplevel_handle_v1::Error::InvalidRectangle,
+                        "width and height must not be negative",
+                    );
+                }
+            }
+            zwlr_foreign_toplevel_handle_v1::Request::SetFullscreen { output } => {
+                if let Some(toplevel) = window.toplevel() {
+                    state.fullscreen_request(toplevel.clone(), output);
+                }
+            }
+            zwlr_foreign_toplevel_handle_v1::Request::UnsetFullscreen => {
+                if let Some(toplevel) = window.toplevel() {
+                    state.unfullscreen_request(toplevel.clone());
+                }
+            }
+            zwlr_foreign_toplevel_handle_v1::Request::Destroy => {}
+            _ => unreachable!(),
+        }
+
+        state.refresh_foreign_toplevels();
+    }
+
+    fn destroyed(state: &mut Self, _client: ClientId, handle: &ZwlrForeignToplevelHandleV1, _data: &()) {
+        for toplevel in state.state.wlr_foreign_toplevel_state.toplevels.values_mut() {
+            toplevel.handles.retain(|h| h != handle);
+        }
+    }
+}
+
+fn new_handle(
+    display_handle: &DisplayHandle,
+    client: &Client,
+    manager: &ZwlrForeignToplevelManagerV1,
+    info: &ToplevelInfo,
+) -> Option<ZwlrForeignToplevelHandleV1> {
+    let handle = client
+        .create_resource::<ZwlrForeignToplevelHandleV1, _, GlobalData>(display_handle, manager.version(), ())
+        .ok()?;
+    manager.toplevel(&handle);
+
+    send_info(&handle, None, info);
+
+    Some(handle)
+}
+
+/// Send what differs from `last`, everything if there is no `last`.
+fn send_info(handle: &ZwlrForeignToplevelHandleV1, last: Option<&ToplevelInfo>, info: &ToplevelInfo) {
+    if last.map_or(true, |last| last.title != info.title) {
+        handle.title(info.title.clone());
+    }
+    if last.map_or(true, |last| last.app_id != info.app_id) {
+        handle.app_id(info.app_id.clone());
+    }
+
+    if last.map_or(true, |last| last.output != info.output) {
+        if let Some(client) = handle.client() {
+            if let Some(output) = last.and_then(|last| last.output.as_ref()) {
+                for wl_output in output.client_outputs(&client) {
+                    handle.output_leave(&wl_output);
+                }
+            }
+            if let Some(output) = info.output.as_ref() {
+                for wl_output in output.client_outputs(&client) {
+                    handle.output_enter(&wl_output);
+                }
+            }
+        }
+    }
+
+    if last.map_or(true, |last| last.states != info.states) {
+        let states = info.states
+            .iter()
+            // fullscreen only exists since version 2
+            .filter(|state| handle.version() >= 2 || **state != zwlr_foreign_toplevel_handle_v1::State::Fullscreen)
+            .flat_map(|state| (*state as u32).to_ne_bytes())
+            .collect();
+        handle.state(states);
+    }
+
+    handle.done();
+}
+
+impl GlobalData {
+    /// Send title, app_id and state changes of mapped windows to both foreign toplevel protocols.
+    pub fn refresh_foreign_toplevels(&mut self) {
+        let _span = tracy_client::span!("refresh_foreign_toplevels");
+
+        let focus = match self.input_manager.get_keyboard_focus() {
+            Some(KeyboardFocusTarget::Window(window)) => Some(window),
+            _ => None,
+        };
+
+        let output = self.output_manager.current_output().clone();
+        let fullscreen = output
+            .user_data()
+            .get::<FullscreenSurface>()
+            .and_then(|fullscreen| fullscreen.get())
+            .map(|(window, _)| window);
+
+        let wlr_state = &mut self.state.wlr_foreign_toplevel_state;
+
+        // closed windows first
+        wlr_state.toplevels.retain(|window, toplevel| {
+            let alive = self.window_manager.all_mapped().any(|w| w == window);
+            if !alive {
+                for handle in &toplevel.handles {
+                    handle.closed();
+                }
+            }
+            alive
+        });
+
+        for window in self.window_manager.all_mapped() {
+            let (title, app_id) = window.get_title_and_id();
+
+            let mut states = vec![];
+            if self.window_manager.is_maximized(window) {
+                states.push(zwlr_foreign_toplevel_handle_v1::State::Maximized);
+            }
+            if self.window_manager.is_minimized(window) {
+                states.push(zwlr_foreign_toplevel_handle_v1::State::Minimized);
+            }
+            if focus.as_ref() == Some(window) {
+                states.push(zwlr_foreign_toplevel_handle_v1::State::Activated);
+            }
+            if fullscreen.as_ref() == Some(window) {
+                states.push(zwlr_foreign_toplevel_handle_v1::State::Fullscreen);
+            }
+
+            let info = ToplevelInfo {
+                title: title.unwrap_or_default(),
+                app_id: app_id.unwrap_or_default(),
+                states,
+                output: Some(output.clone()),
+            };
+
+            match wlr_state.toplevels.get_mut(window) {
+                Some(toplevel) => {
+                    if toplevel.info == info {
+                        continue;
+                    }
+
+                    for handle in &toplevel.handles {
+                        send_info(handle, Some(&toplevel.info), &info);
+                    }
+
+                    // the ext list only knows title and app_id
+                    if toplevel.info.title != info.title || toplevel.info.app_id != info.app_id {
+                        if let Some(ext_handle) = window
+                            .wl_surface()
+                            .and_then(|surface| self.window_manager.get_foreign_handle(&surface))
+                        {
+                            ext_handle.send_title(&info.title);
+                            ext_handle.send_app_id(&info.app_id);
+                            ext_handle.send_done();
+                        }
+                    }
+
+                    toplevel.info = info;
+                }
+                None => {
+                    let handles = wlr_state.managers
+                        .iter()
+                        .filter_map(|manager| {
+                            let client = manager.client()?;
+                            new_handle(&self.display_handle, &client, manager, &info)
+                        })
+                        .collect();
+
+                    wlr_state.toplevels.insert(window.clone(), WlrToplevel { info, handles });
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,603 @@
+use std::{collections::HashMap, time::Duration};
+
+use smithay::{
+    backend::renderer::{
+        buffer_type,
+        damage::OutputDamageTracker,
+        element::{
+            surface::WaylandSurfaceRenderElement,
+            utils::{Relocate, RelocateRenderElement},
+            AsRenderElements, RenderElement,
+        },
+        gles::GlesRenderer,
+        BufferType,
+    },
+    output::{Output, WeakOutput},
+    reexports::{
+        wayland_protocols::ext::{
+            image_capture_source::v1::server::{
+                ext_foreign_toplevel_image_capture_source_manager_v1::{
+                    self, ExtForeignToplevelImageCaptureSourceManagerV1,
+                },
+                ext_image_capture_source_v1::{self, ExtImageCaptureSourceV1},
+                ext_output_image_capture_source_manager_v1::{self, ExtOutputImageCaptureSourceManagerV1},
+            },
+            image_copy_capture::v1::server::{
+                ext_image_copy_capture_cursor_session_v1::{self, ExtImageCopyCaptureCursorSessionV1},
+                ext_image_copy_capture_frame_v1::{self, ExtImageCopyCaptureFrameV1, FailureReason},
+                ext_image_copy_capture_manager_v1::{self, ExtImageCopyCaptureManagerV1, Options},
+                ext_image_copy_capture_session_v1::{self, ExtImageCopyCaptureSessionV1},
+            },
+        },
+        wayland_server::{
+            backend::{ClientId, GlobalId, ObjectId},
+            protocol::{wl_buffer::WlBuffer, wl_output},
+            Client, DataInit, Dispatch, DisplayHandle, GlobalDispatch, New, Resource, WEnum,
+        },
+    },
+    utils::{Physical, Point, Rectangle, Scale, Size, Transform},
+    wayland::foreign_toplevel_list::ForeignToplevelHandle,
+};
+
+use crate::{
+    render::snapshot::capture_elements,
+    state::GlobalData,
+};
+
+use super::screencopy::{copy_to_shm, shm_buffer_matches, CAPTURE_FOURCC, SHM_FORMAT};
+
+const VERSION: u32 = 1;
+
+/// What an `ext_image_capture_source_v1` points at.
+#[derive(Clone)]
+pub enum CaptureSource {
+    Output(WeakOutput),
+    // identifier of the foreign toplevel handle
+    Toplevel(String),
+    // already gone when the source was created, sessions stop right away
+    Gone,
+}
+
+struct CaptureSession {
+    resource: ExtImageCopyCaptureSessionV1,
+    source: CaptureSource,
+    paint_cursors: bool,
+
+    // the buffer constraints last sent to the client
+    size: Size<i32, Physical>,
+    // only used to find out what changed since the last frame
+    damage_tracker: Option<OutputDamageTracker>,
+    has_frame: bool,
+    stopped: bool,
+}
+
+#[derive(Default)]
+struct CaptureFrame {
+    buffer: Option<WlBuffer>,
+    captured: bool,
+}
+
+/// Frame user data, the session it belongs to.
+pub struct CaptureFrameData {
+    session: ObjectId,
+}
+
+pub struct ImageCaptureState {
+    #[allow(dead_code)]
+    globals: Vec<GlobalId>,
+
+    sessions: HashMap<ObjectId, CaptureSession>,
+    frames: HashMap<ObjectId, CaptureFrame>,
+    // captured frames waiting for the source to change
+    pending: Vec<ExtImageCopyCaptureFrameV1>,
+}
+
+impl ImageCaptureState {
+    pub fn new(display_handle: &DisplayHandle) -> Self {
+        let globals = vec![
+            display_handle.create_global::<GlobalData, ExtOutputImageCaptureSourceManagerV1, _>(VERSION, ()),
+            display_handle.create_global::<GlobalData, ExtForeignToplevelImageCaptureSourceManagerV1, _>(VERSION, ()),
+            display_handle.create_global::<GlobalData, ExtImageCopyCaptureManagerV1, _>(VERSION, ()),
+        ];
+
+        Self {
+            globals,
+            sessions: HashMap::new(),
+            frames: HashMap::new(),
+            pending: Vec::new(),
+        }
+    }
+}
+
+impl GlobalDispatch<ExtOutputImageCaptureSourceManagerV1, ()> for GlobalData {
+    fn bind(
+        _state: &mut Self,
+        _handle: &DisplayHandle,
+        _client: &Client,
+        resource: New<ExtOutputImageCaptureSourceManagerV1>,
+        _global_data: &(),
+        data_init: &mut DataInit<'_, Self>,
+    ) {
+        data_init.init(resource, ());
+    }
+}
+
+impl Dispatch<ExtOutputImageCaptureSourceManagerV1, ()> for GlobalData {
+    fn request(
+        _state: &mut Self,
+        _client: &Client,
+        _manager: &ExtOutputImageCaptureSourceManagerV1,
+        request: ext_output_image_capture_source_manager_v1::Request,
+        _data: &(),
+        _dhandle: &DisplayHandle,
+        data_init: &mut DataInit<'_, Self>,
+    ) {
+        match request {
+            ext_output_image_capture_source_manager_v1::Request::CreateSource { source, output } => {
+                let source_data = Output::from_resource(&output)
+                    .map(|output| CaptureSource::Output(output.downgrade()))
+                    .unwrap_or(CaptureSource::Gone);
+                data_init.init(source, source_data);
+            }
+            ext_output_image_capture_source_manager_v1::Request::Destroy => {}
+            _ => unreachable!(),
+        }
+    }
+}
+
+impl GlobalDispatch<ExtForeignToplevelImageCaptureSourceManagerV1, ()> for GlobalData {
+    fn bind(
+        _state: &mut Self,
+        _handle: &DisplayHandle,
+        _client: &Client,
+        resource: New<ExtForeignToplevelImageCaptureSourceManagerV1>,
+        _global_data: &(),
+        data_init: &mut DataInit<'_, Self>,
+    ) {
+        data_init.init(resource, ());
+    }
+}
+
+impl Dispatch<ExtForeignToplevelImageCaptureSourceManagerV1, ()> for GlobalData {
+    fn request(
+        _state: &mut Self,
+        _client: &Client,
+        _manager: &ExtForeignToplevelImageCaptureSourceManagerV1,
+        request: ext_foreign_toplevel_image_capture_source_manager_v1::Request,
+        _data: &(),
+        _dhandle: &DisplayHandle,
+        data_init: &mut DataInit<'_, Self>,
+    ) {
+        match request {
+            ext_foreign_toplevel_image_capture_source_manager_v1::Request::CreateSource { source, toplevel_handle } => {
+                let source_data = ForeignToplevelHandle::from_resource(&toplevel_handle)
+                    .map(|handle| CaptureSource::Toplevel(handle.identifier()))
+                    .unwrap_or(CaptureSource::Gone);
+                data_init.init(source, source_data);
+            }
+            ext_foreign_toplevel_image_capture_source_manager_v1::Request::Destroy => {}
+            _ => unreachable!(),
+        }
+    }
+}
+
+impl Dispatch<ExtImageCaptureSourceV1, CaptureSource> for GlobalData {
+    fn request(
+        _state: &mut Self,
+        _client: &Client,
+        _source: &ExtImageCaptureSourceV1,
+        request: ext_image_capture_source_v1::Request,
+        _data: &CaptureSource,
+        _dhandle: &DisplayHandle,
+        _data_init: &mut DataInit<'_, Self>,
+    ) {
+        match request {
+            ext_image_capture_source_v1::Request::Destroy => {}
+            _ => unreachable!(),
+        }
+    }
+}
+
+impl GlobalDispatch<ExtImageCopyCaptureManagerV1, ()> for GlobalData {
+    fn bind(
+        _state: &mut Self,
+        _handle: &DisplayHandle,
+        _client: &Client,
+        resource: New<ExtImageCopyCaptureManagerV1>,
+        _global_data: &(),
+        data_init: &mut DataInit<'_, Self>,
+    ) {
+        data_init.init(resource, ());
+    }
+}
+
+impl Dispatch<ExtImageCopyCaptureManagerV1, ()> for GlobalData {
+    fn request(
+        state: &mut Self,
+        _client: &Client,
+        manager: &ExtImageCopyCaptureManagerV1,
+        request: ext_image_copy_capture_manager_v1::Request,
+        _data: &(),
+        _dhandle: &DisplayHandle,
+        data_init: &mut DataInit<'_, Self>,
+    ) {
+        match request {
+            ext_image_copy_capture_manager_v1::Request::CreateSession { session, source, options } => {
+                let paint_cursors = match options {
+                    WEnum::Value(options) => options.contains(Options::PaintCursors),
+                    WEnum::Unknown(_) => {
+                        manager.post_error(
+                            ext_image_copy_capture_manager_v1::Error::InvalidOption,
+                            "unknown capture options",
+                        );
+                        return;
+                    }
+                };
+
+                let source = source.data::<CaptureSource>().cloned();
+                let session = data_init.init(session, ());
+
+                match source {
+                    Some(source) => state.add_capture_session(session, source, paint_cursors),
+                    None => session.stopped(),
+                }
+            }
+            ext_image_copy_capture_manager_v1::Request::CreatePointerCursorSession { session, .. } => {
+                // cursors are painted into the captured image instead
+                data_init.init(session, ());
+            }
+            ext_image_copy_capture_manager_v1::Request::Destroy => {}
+            _ => unreachable!(),
+        }
+    }
+}
+
+impl Dispatch<ExtImageCopyCaptureCursorSessionV1, ()> for GlobalData {
+    fn request(
+        _state: &mut Self,
+        _client: &Client,
+        _cursor_session: &ExtImageCopyCaptureCursorSessionV1,
+        request: ext_image_copy_capture_cursor_session_v1::Request,
+        _data: &(),
+        _dhandle: &DisplayHandle,
+        data_init: &mut DataInit<'_, Self>,
+    ) {
+        match request {
+            ext_image_copy_capture_cursor_session_v1::Request::GetCaptureSession { session } => {
+                let session = data_init.init(session, ());
+                session.stopped();
+            }
+            ext_image_copy_capture_cursor_session_v1::Request::Destroy => {}
+            _ => unreachable!(),
+        }
+    }
+}
+
+impl Dispatch<ExtImageCopyCaptureSessionV1, ()> for GlobalData {
+    fn request(
+        state: &mut Self,
+        _client: &Client,
+        session: &ExtImageCopyCaptureSessionV1,
+        request: ext_image_copy_capture_session_v1::Request,
+        _data: &(),
+        _dhandle: &DisplayHandle,
+        data_init: &mut DataInit<'_, Self>,
+    ) {
+        match request {
+            ext_image_copy_capture_session_v1::Request::CreateFrame { frame } => {
+                let capture_state = &mut state.state.image_capture_state;
+                let has_frame = capture_state.sessions
+                    .get_mut(&session.id())
+                    .map(|session| std::mem::replace(&mut session.has_frame, true))
+                    .unwrap_or(false);
+
+                if has_frame {
+                    session.post_error(
+                        ext_image_copy_capture_session_v1::Error::DuplicateFrame,
+                        "the session already has a frame",
+                    );
+                    return;
+                }
+
+                let frame = data_init.init(frame, CaptureFrameData { session: session.id() });
+                capture_state.frames.insert(frame.id(), CaptureFrame::default());
+            }
+            ext_image_copy_capture_session_v1::Request::Destroy => {}
+            _ => unreachable!(),
+        }
+    }
+
+    fn destroyed(state: &mut Self, _client: ClientId, session: &ExtImageCopyCaptureSessionV1, _data: &()) {
+        state.state.image_capture_state.sessions.remove(&session.id());
+    }
+}
+
+impl Dispatch<ExtImageCopyCaptureFrameV1, CaptureFrameData> for GlobalData {
+    fn request(
+        state: &mut Self,
+        _client: &Client,
+        frame: &ExtImageCopyCaptureFrameV1,
+        request: ext_image_copy_capture_frame_v1::Request,
+        _data: &CaptureFrameData,
+        _dhandle: &DisplayHandle,
+        _data_init: &mut DataInit<'_, Self>,
+    ) {
+        let capture_state = &mut state.state.image_capture_state;
+        let Some(frame_state) = capture_state.frames.get_mut(&frame.id()) else {
+            return;
+        };
+
+        match request {
+            ext_image_copy_capture_frame_v1::Request::AttachBuffer { buffer } => {
+                if frame_state.captured {
+                    frame.post_error(ext_image_copy_capture_frame_v1::Error::AlreadyCaptured, "the frame was already captured");
+                    return;
+                }
+                frame_state.buffer = Some(buffer);
+            }
+            ext_image_copy_capture_frame_v1::Request::DamageBuffer { x, y, width, height } => {
+                if frame_state.captured {
+                    frame.post_error(ext_image_copy_capture_frame_v1::Error::AlreadyCaptured, "the frame was already captured");
+                    return;
+                }
+                // the whole buffer is redrawn anyway, so the damage is only validated
+                if x < 0 || y < 0 || width <= 0 || height <= 0 {
+                    frame.post_error(ext_image_copy_capture_frame_v1::Error::InvalidBufferDamage, "invalid buffer damage");
+                }
+            }
+            ext_image_copy_capture_frame_v1::Request::Capture => {
+                if frame_state.captured {
+                    frame.post_error(ext_image_copy_capture_frame_v1::Error::AlreadyCaptured, "the frame was already captured");
+                    return;
+                }
+                if frame_state.buffer.is_none() {
+                    frame.post_error(ext_image_copy_capture_frame_v1::Error::NoBuffer, "no buffer attached");
+                    return;
+                }
+                frame_state.captured = true;
+
+                // answered after the next frame is drawn
+                capture_state.pending.push(frame.clone());
+                state.queue_redraw();
+            }
+            ext_image_copy_capture_frame_v1::Request::Destroy => {}
+            _ => unreachable!(),
+        }
+    }
+
+    fn destroyed(state: &mut Self, _client: ClientId, frame: &ExtImageCopyCaptureFrameV1, data: &CaptureFrameData) {
+        let capture_state = &mut state.state.image_capture_state;
+        capture_state.frames.remove(&frame.id());
+        capture_state.pending.retain(|pending| pending != frame);
+        if let Some(session) = capture_state.sessions.get_mut(&data.session) {
+            session.has_frame = false;
+        }
+    }
+}
+
+impl GlobalData {
+    fn add_capture_session(&mut self, resource: ExtImageCopyCaptureSessionV1, source: CaptureSource, paint_cursors: bool) {
+        let Some((size, _)) = self.capture_source_size(&source) else {
+            resource.stopped();
+            return;
+        };
+
+        send_buffer_constraints(&resource, size);
+
+        self.state.image_capture_state.sessions.insert(resource.id(), CaptureSession {
+            resource,
+            source,
+            paint_cursors,
+            size,
+            damage_tracker: None,
+            has_frame: false,
+            stopped: false,
+        });
+    }
+
+    /// Physical size and scale of what `source` currently shows.
+    fn capture_source_size(&self, source: &CaptureSource) -> Option<(Size<i32, Physical>, f64)> {
+        match source {
+            CaptureSource::Output(output) => {
+                let output = output.upgrade()?;
+                let mode = output.current_mode()?;
+                let scale = output.current_scale().fractional_scale();
+                Some((output.current_transform().transform_size(mode.size), scale))
+            }
+            CaptureSource::Toplevel(identifier) => {
+                let window = self.window_manager.get_foreign_window(identifier)?;
+                let scale = self.output_manager.current_output().current_scale().fractional_scale();
+                let size = window.geometry().size.to_physical_precise_round(scale);
+                (!size.is_empty()).then_some((size, scale))
+            }
+            CaptureSource::Gone => None,
+        }
+    }
+
+    /// Stop sessions whose source is gone.
+    pub fn refresh_image_captures(&mut self) {
+        let stopped: Vec<ObjectId> = self.state.image_capture_state.sessions
+            .iter()
+            .filter(|(_, session)| !session.stopped && self.capture_source_size(&session.source).is_none())
+            .map(|(id, _)| id.clone())
+            .collect();
+
+        for id in stopped {
+            if let Some(session) = self.state.image_capture_state.sessions.get_mut(&id) {
+                session.stopped = true;
+                session.resource.stopped();
+            }
+        }
+    }
+
+    /// Answer captured frames once their source changed, after a frame was drawn.
+    pub fn process_image_captures(&mut self) {
+        let _span = tracy_client::span!("process_image_captures");
+
+        let pending = std::mem::take(&mut self.state.image_capture_state.pending);
+        let still_pending: Vec<_> = pending
+            .into_iter()
+            .filter(|frame| frame.is_alive() && !self.capture_frame(frame))
+            .collect();
+        self.state.image_capture_state.pending.extend(still_pending);
+    }
+
+    /// Returns false if there was nothing new to capture yet.
+    fn capture_frame(&mut self, frame: &ExtImageCopyCaptureFrameV1) -> bool {
+        let Some(session_id) = frame.data::<CaptureFrameData>().map(|data| data.session.clone()) else {
+            return true;
+        };
+
+        let capture_state = &mut self.state.image_capture_state;
+        let buffer = capture_state.frames.get(&frame.id()).and_then(|frame| frame.buffer.clone());
+        let Some(session) = capture_state.sessions.get_mut(&session_id) else {
+            frame.failed(FailureReason::Stopped);
+            return true;
+        };
+        if session.stopped {
+            frame.failed(FailureReason::Stopped);
+            return true;
+        }
+
+        let (source, paint_cursors, damage_tracker) =
+            (session.source.clone(), session.paint_cursors, session.damage_tracker.take());
+        let Some((size, scale)) = self.capture_source_size(&source) else {
+            frame.failed(FailureReason::Stopped);
+            return true;
+        };
+
+        let session = self.state.image_capture_state.sessions.get_mut(&session_id).unwrap();
+        if size != session.size {
+            // let the client reallocate, the first frame after it is fully damaged
+            session.size = size;
+            send_buffer_constraints(&session.resource, size);
+            frame.failed(FailureReason::BufferConstraints);
+            return true;
+        }
+
+        let buffer_region = Rectangle::from_size(size);
+        let valid = match buffer.as_ref().map(buffer_type) {
+            Some(Some(BufferType::Shm)) => shm_buffer_matches(buffer.as_ref().unwrap(), buffer_region),
+            _ => false,
+        };
+        if !valid {
+            frame.failed(FailureReason::BufferConstraints);
+            return true;
+        }
+        let buffer = buffer.unwrap();
+
+        let mut damage_tracker = damage_tracker
+            .unwrap_or_else(|| OutputDamageTracker::new(size, scale, Transform::Normal));
+
+        let result = match &source {
+            CaptureSource::Output(output) => {
+                let output = output.upgrade().unwrap();
+
+                // only the current output is drawn
+                if &output != self.output_manager.current_output() {
+                    frame.failed(FailureReason::Unknown);
+                    return true;
+                }
+
+                let origin = self.output_manager
+                    .output_geometry(&output)
+                    .map(|geo| geo.loc.to_physical_precise_round(scale))
+                    .unwrap_or_default();
+
+                self.backend.with_renderer(|renderer| {
+                    let elements: Vec<_> = self.render_manager
+                        .get_render_elements(
+                            renderer,
+                            &self.output_manager,
+                            &self.workspace_manager,
+                            &self.window_manager,
+                            &mut self.cursor_manager,
+                            &self.input_manager,
+                            &mut self.animation_manager,
+                            paint_cursors,
+                        )
+                        .into_iter()
+                        .map(|element| RelocateRenderElement::from_element(element, -origin, Relocate::Relative))
+                        .collect();
+
+                    capture_into(renderer, &mut damage_tracker, &elements, size, scale, &buffer)
+                })
+            }
+            CaptureSource::Toplevel(identifier) => {
+                let window = self.window_manager.get_foreign_window(identifier).cloned().unwrap();
+                let loc: Point<i32, Physical> = (-window.geometry().loc).to_physical_precise_round(scale);
+
+                self.backend.with_renderer(|renderer| {
+                    let elements = window.render_elements::<WaylandSurfaceRenderElement<GlesRenderer>>(
+                        renderer,
+                        loc,
+                        Scale::from(scale),
+                        1.0,
+                    );
+
+                    capture_into(renderer, &mut damage_tracker, &elements, size, scale, &buffer)
+                })
+            }
+            CaptureSource::Gone => unreachable!(),
+        };
+
+        if let Some(session) = self.state.image_capture_state.sessions.get_mut(&session_id) {
+            session.damage_tracker = Some(damage_tracker);
+        }
+
+        match result {
+            Some(Ok(Some(damage))) => {
+                frame.transform(wl_output::Transform::Normal);
+                for rect in damage {
+                    frame.damage(rect.loc.x, rect.loc.y, rect.size.w, rect.size.h);
+                }
+
+                let time: Duration = self.clock.now().into();
+                let secs = time.as_secs();
+                frame.presentation_time((secs >> 32) as u32, secs as u32, time.subsec_nanos());
+                frame.ready();
+                true
+            }
+            // unchanged, try again after the next frame
+            Some(Ok(None)) => false,
+            Some(Err(err)) => {
+                warn!("image copy capture failed: {:?}", err);
+                frame.failed(FailureReason::Unknown);
+                true
+            }
+            None => {
+                frame.failed(FailureReason::Unknown);
+                true
+            }
+        }
+    }
+}
+
+fn send_buffer_constraints(session: &ExtImageCopyCaptureSessionV1, size: Size<i32, Physical>) {
+    session.buffer_size(size.w as u32, size.h as u32);
+    session.shm_format(SHM_FORMAT);
+    session.done();
+}
+
+/// Copy the elements into the client buffer if anything changed, returns the damage.
+fn capture_into<E: RenderElement<GlesRenderer>>(
+    renderer: &mut GlesRenderer,
+    damage_tracker: &mut OutputDamageTracker,
+    elements: &[E],
+    size: Size<i32, Physical>,
+    scale: f64,
+    buffer: &WlBuffer,
+) -> anyhow::Result<Option<Vec<Rectangle<i32, Physical>>>> {
+    // age 1: what changed since the previous frame of this session,
+    // a fresh tracker damages everything
+    let (damage, _) = damage_tracker.damage_output(1, elements)?;
+    let Some(damage) = damage.filter(|damage| !damage.is_empty()).cloned() else {
+        return Ok(None);
+    };
+
+    let buffer_region = Rectangle::from_size(size);
+    let pixels = capture_elements(renderer, elements, buffer_region, scale, CAPTURE_FOURCC)?;
+    copy_to_shm(buffer, &pixels, buffer_region)?;
+
+    Ok(Some(damage))
+}
@@ -1,25 +1,32 @@
-use std::{cell::RefCell, time::Duration};
+use std::cell::RefCell;
 
 use smithay::{
     desktop::{layer_map_for_output, LayerSurface, Window}, input::pointer::{
         Focus, GrabStartData as PointerGrabStartData, PointerHandle
-    }, output::Output, utils::{
+    }, output::Output, reexports::wayland_protocols::xdg::shell::server::xdg_toplevel, utils::{
         Logical, Point, Rectangle, Serial, SERIAL_COUNTER
-    }, wayland::shell::wlr_layer::Layer
+    }, wayland::{seat::WaylandFocus, shell::wlr_layer::Layer}
 };
 
 use crate::{
+    config::animation::AnimationEvent,
     input::{
-        focus::PointerFocusTarget, move_grab::MoveSurfaceGrab, resize_grab::ResizeSurfaceGrab
+        focus::{KeyboardFocusTarget, PointerFocusTarget}, move_grab::MoveSurfaceGrab, resize_grab::ResizeSurfaceGrab
     }, 
-    layout::{ResizeEdge, WindowLayout}, 
-    manager::{animation::{AnimationManager, AnimationType}, window::WindowExt}, 
+    layout::{ResizeEdge, TiledScheme, WindowLayout}, 
+    manager::{animation::AnimationManager, window::WindowExt}, 
+    render::snapshot::snapshot_window,
     state::GlobalData
 };
 
 pub mod compositor;
+pub mod decoration;
+pub mod ext_workspace;
 pub mod foreign_toplevel;
+pub mod image_capture;
 pub mod layer_shell;
+pub mod screencopy;
+pub mod xdg_activation;
 pub mod xdg_shell;
 
 #[cfg(feature = "xwayland")]
@@ -44,9 +51,12 @@ impl FullscreenSurface {
 
 impl GlobalData {
     pub fn map_window(&mut self, window: Window) -> bool {
+        self.refresh_title_bar(&window);
+
         // fake fullscreen, no border fullscreen
         if let Some(is_fullscreen) = self.window_manager.get_fullscreen(&window) {
             if is_fullscreen {
+                window.set_title_bar_height(0);
                 window.set_layout(WindowLayout::Floating);
                 self.window_manager.raise_window(&window);
 
@@ -60,6 +70,27 @@ impl GlobalData {
         }
         
         // map window for current workspace
+        let mapped = self.map_window_at_pointer(window.clone());
+
+        self.animation_manager.add_opacity_animation(window, 0.0, 1.0, AnimationEvent::WindowOpen);
+
+        mapped
+    }
+
+    /// Insert `window` into the layout of the current workspace at the preselection if there is one,
+    /// otherwise next to the tiled window under the pointer, or the focused one if configured.
+    fn map_window_at_pointer(&mut self, window: Window) -> bool {
+        let workspace_id = self.workspace_manager.current_workspace().id();
+
+        // other schemes place windows on their own, the preselection waits for a window it applies to
+        let preselectable = matches!(window.get_layout(), WindowLayout::Tiled)
+            && *self.workspace_manager.current_scheme() == TiledScheme::Default;
+        if preselectable {
+            if let Some((target, presel)) = self.window_manager.take_presel(workspace_id) {
+                return self.workspace_manager.map_window_preselected(&target, window, presel, &mut self.animation_manager);
+            }
+        }
+
         let pointer = self.input_manager.get_pointer();
         let pointer = match pointer {
             Some(k) => k,
@@ -70,17 +101,34 @@ impl GlobalData {
         };
         let pointer_loc = pointer.current_location();
 
-        let target_tiled = self.window_manager.window_under_tiled(pointer_loc, self.workspace_manager.current_workspace().id());
+        // the pointer may be parked anywhere while opening apps from the keyboard
+        let focused_tiled = match self.input_manager.get_keyboard_focus() {
+            Some(KeyboardFocusTarget::Window(window)) if self.configs.conf_workspaces.insert_at_focus => {
+                let on_screen = matches!(window.get_layout(), WindowLayout::Tiled)
+                    && self.window_manager.mapped_windows(workspace_id).any(|w| *w == window);
+                on_screen.then_some(window)
+            }
+            _ => None,
+        };
 
-        let edge = if let Some(target_tiled) = &target_tiled {
-            detect_pointer_quadrant(pointer_loc, target_tiled.get_rect().unwrap().to_f64())
+        let (target_tiled, edge) = if let Some(focused) = focused_tiled {
+            // split the longer side, the new window right or below
+            (Some(focused), ResizeEdge::BottomRight)
         } else {
-            ResizeEdge::None
+            let target_tiled = self.window_manager.window_under_tiled(pointer_loc, workspace_id);
+
+            let edge = if let Some(target_tiled) = &target_tiled {
+                detect_pointer_quadrant(pointer_loc, target_tiled.get_rect().unwrap().to_f64())
+            } else {
+                ResizeEdge::None
+            };
+
+            (target_tiled, edge)
         };
 
         self.workspace_manager.map_window(
             target_tiled.as_ref(),
-            window.clone(),
+            window,
             edge,
             &mut self.animation_manager,
         )
@@ -99,12 +147,130 @@ impl GlobalData {
             }
         }
 
-        // is unmapped
-        if self.window_manager.set_unmapped(window) {
+        self.input_manager.forget_window(window);
+        self.workspace_manager.overview_remove(window, &mut self.animation_manager);
+
+        self.snapshot_closing_window(window);
+
+        if let Some(handle) = window.wl_surface().and_then(|surface| self.window_manager.get_foreign_handle(&surface).cloned()) {
+            self.state.foreign_toplevel_state.remove_toplevel(&handle);
+        }
+
+        // is unmapped, minimized windows already left the layout
+        let minimized = self.window_manager.is_minimized(window);
+        if self.window_manager.set_unmapped(window) && !minimized {
             self.workspace_manager.unmap_window(window, &mut self.animation_manager);
         }
 
         self.update_keyboard_focus();
+        self.queue_redraw();
+    }
+
+    fn snapshot_closing_window(&mut self, window: &Window) {
+        // only windows on screen need a close animation
+        let workspace_id = self.workspace_manager.current_workspace().id();
+        if self.window_manager.window_workspace.get(window) != Some(&workspace_id) {
+            return;
+        }
+
+        // the snapshot only holds the client, not its title bar
+        let rect = match window.get_rect() {
+            Some(rect) => window.content_rect(rect),
+            None => return,
+        };
+
+        let scale = self.output_manager.current_output().current_scale().fractional_scale();
+        let snapshot = self.backend.with_renderer(|renderer| snapshot_window(renderer, window, scale));
+
+        match snapshot {
+            Some(Ok(buffer)) => self.animation_manager.add_closing_window(buffer, rect),
+            Some(Err(err)) => debug!("no snapshot for closing window: {:?}", err),
+            None => {}
+        }
+    }
+
+    /// Take the window out of the layout of its workspace, or put it back at the pointer.
+    pub fn set_minimized(&mut self, window: &Window, minimized: bool) {
+        if minimized {
+            // only windows on screen can be minimized
+            let workspace_id = self.workspace_manager.current_workspace().id();
+            if self.window_manager.window_workspace.get(window) != Some(&workspace_id) {
+                return;
+            }
+
+            if self.window_manager.is_maximized(window) {
+                self.set_maximized(window, false);
+            }
+
+            if self.window_manager.set_minimized(window, true) {
+                self.workspace_manager.unmap_window(window, &mut self.animation_manager);
+                self.update_keyboard_focus();
+                self.queue_redraw();
+            }
+        } else if self.window_manager.is_minimized(window) {
+            // restore on the workspace it was minimized on
+            self.activate_window(window, SERIAL_COUNTER.next_serial());
+        }
+    }
+
+    pub fn restore_minimized(&mut self, window: &Window) {
+        if self.window_manager.set_minimized(window, false) {
+            self.map_window_at_pointer(window.clone());
+        }
+    }
+
+    /// Float the window over the working area of the output, unmaximize restores layout and size.
+    pub fn set_maximized(&mut self, window: &Window, maximized: bool) {
+        if maximized == self.window_manager.is_maximized(window) || self.window_manager.is_minimized(window) {
+            return;
+        }
+
+        let Some(rect) = window.get_rect() else {
+            return;
+        };
+
+        if maximized {
+            self.window_manager.set_maximized(window, (window.get_layout(), rect));
+
+            if matches!(window.get_layout(), WindowLayout::Tiled) {
+                self.workspace_manager.unmap_window(window, &mut self.animation_manager);
+                self.window_manager.switch_layout(window);
+                self.workspace_manager.map_window(None, window.clone(), ResizeEdge::TopLeft, &mut self.animation_manager);
+            }
+
+            let output = self.output_manager.current_output();
+            let output_loc = self.output_manager.output_geometry(output).unwrap().loc;
+            let working_area = layer_map_for_output(output).non_exclusive_zone();
+            let maximized_rect = Rectangle::new(working_area.loc + output_loc, working_area.size);
+
+            if let Some(toplevel) = window.toplevel() {
+                toplevel.with_pending_state(|state| state.states.set(xdg_toplevel::State::Maximized));
+            }
+
+            window.set_rect_cache(maximized_rect);
+            window.send_rect(maximized_rect);
+            self.window_manager.raise_window(window);
+        } else {
+            let Some((layout, restore_rect)) = self.window_manager.take_maximized(window) else {
+                return;
+            };
+
+            if let Some(toplevel) = window.toplevel() {
+                toplevel.with_pending_state(|state| state.states.unset(xdg_toplevel::State::Maximized));
+            }
+
+            window.set_rect_cache(restore_rect);
+            window.send_rect(restore_rect);
+
+            if matches!(layout, WindowLayout::Tiled) {
+                // back into the tree where it floated
+                let rect = restore_rect.to_f64();
+                let center = (rect.loc.x + rect.size.w / 2.0, rect.loc.y + rect.size.h / 2.0).into();
+                self.switch_layout(window, center);
+            }
+        }
+
+        self.queue_redraw();
     }
 
     pub fn destroy_window(&mut self, window: &Window) {
@@ -301,7 +467,6 @@ fn set_pointer_as_center(target: &Window, pointer_loc: Point<i32, Logical>, anim
         target.clone(), 
         rect, 
         new_rect, 
-        Duration::from_millis(15), 
-        AnimationType::EaseInOutQuad
+        AnimationEvent::FloatingToggle
     );
 }
\ No newline at end of file
@@ -0,0 +1,495 @@
+use std::{
+    path::PathBuf,
+    sync::atomic::{AtomicBool, Ordering},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use smithay::{
+    backend::{
+        allocator::{Buffer as _, Fourcc},
+        renderer::{buffer_type, element::RenderElement, gles::GlesRenderer, Bind, BufferType, Color32F},
+    },
+    output::Output,
+    reexports::{
+        wayland_protocols_wlr::screencopy::v1::server::{
+            zwlr_screencopy_frame_v1::{self, Flags, ZwlrScreencopyFrameV1},
+            zwlr_screencopy_manager_v1::{self, ZwlrScreencopyManagerV1},
+        },
+        wayland_server::{
+            backend::{ClientId, GlobalId},
+            protocol::{wl_buffer::WlBuffer, wl_shm},
+            Client, DataInit, Dispatch, DisplayHandle, GlobalDispatch, New, Resource,
+        },
+    },
+    utils::{Buffer, Logical, Physical, Point, Rectangle, Size},
+    wayland::{
+        dmabuf::get_dmabuf,
+        shm::{with_buffer_contents, with_buffer_contents_mut},
+    },
+};
+
+use crate::{
+    config::keybinding::ScreenshotTarget,
+    input::focus::KeyboardFocusTarget,
+    render::snapshot::{capture_elements, capture_window, draw_elements},
+    state::GlobalData,
+};
+
+const VERSION: u32 = 3;
+
+// the only format offered, for shm as well as dmabuf
+pub(super) const SHM_FORMAT: wl_shm::Format = wl_shm::Format::Xrgb8888;
+pub(super) const CAPTURE_FOURCC: Fourcc = Fourcc::Xrgb8888;
+
+pub struct ScreencopyManagerState {
+    #[allow(dead_code)]
+    global: GlobalId,
+
+    // copy_with_damage frames waiting for their output to be drawn with damage
+    pending: Vec<(ZwlrScreencopyFrameV1, WlBuffer)>,
+}
+
+impl ScreencopyManagerState {
+    pub fn new(display_handle: &DisplayHandle) -> Self {
+        let global = display_handle.create_global::<GlobalData, ZwlrScreencopyManagerV1, _>(VERSION, ());
+        Self { global, pending: Vec::new() }
+    }
+}
+
+pub struct ScreencopyFrameState {
+    // the region is physical and relative to the output, None if there is nothing to capture
+    target: Option<(Output, Rectangle<i32, Physical>)>,
+    overlay_cursor: bool,
+    used: AtomicBool,
+}
+
+impl GlobalDispatch<ZwlrScreencopyManagerV1, ()> for GlobalData {
+    fn bind(
+        _state: &mut Self,
+        _handle: &DisplayHandle,
+        _client: &Client,
+        resource: New<ZwlrScreencopyManagerV1>,
+        _global_data: &(),
+        data_init: &mut DataInit<'_, Self>,
+    ) {
+        data_init.init(resource, ());
+    }
+}
+
+impl Dispatch<ZwlrScreencopyManagerV1, ()> for GlobalData {
+    fn request(
+        _state: &mut Self,
+        _client: &Client,
+        _manager: &ZwlrScreencopyManagerV1,
+        request: zwlr_screencopy_manager_v1::Request,
+        _data: &(),
+        _dhandle: &DisplayHandle,
+        data_init: &mut DataInit<'_, Self>,
+    ) {
+        let (frame, overlay_cursor, output, region) = match request {
+            zwlr_screencopy_manager_v1::Request::CaptureOutput { frame, overlay_cursor, output } => {
+                (frame, overlay_cursor, output, None)
+            }
+            zwlr_screencopy_manager_v1::Request::CaptureOutputRegion {
+                frame,
+                overlay_cursor,
+                output,
+                x,
+                y,
+                width,
+                height,
+            } => {
+                let region: Rectangle<i32, Logical> = Rectangle::new((x, y).into(), (width, height).into());
+                (frame, overlay_cursor, output, Some(region))
+            }
+            zwlr_screencopy_manager_v1::Request::Destroy => return,
+            _ => unreachable!(),
+        };
+
+        let target = Output::from_resource(&output)
+            .and_then(|output| capture_region(&output, region).map(|region| (output, region)));
+        let region = target.as_ref().map(|(_, region)| *region);
+
+        let frame = data_init.init(frame, ScreencopyFrameState {
+            target,
+            overlay_cursor: overlay_cursor != 0,
+            used: AtomicBool::new(false),
+        });
+
+        let Some(region) = region else {
+            frame.failed();
+            return;
+        };
+
+        let (width, height) = (region.size.w as u32, region.size.h as u32);
+        frame.buffer(SHM_FORMAT, width, height, width * 4);
+        if frame.version() >= 3 {
+            frame.linux_dmabuf(CAPTURE_FOURCC as u32, width, height);
+            frame.buffer_done();
+        }
+    }
+}
+
+impl Dispatch<ZwlrScreencopyFrameV1, ScreencopyFrameState> for GlobalData {
+    fn request(
+        state: &mut Self,
+        _client: &Client,
+        frame: &ZwlrScreencopyFrameV1,
+        request: zwlr_screencopy_frame_v1::Request,
+        data: &ScreencopyFrameState,
+        _dhandle: &DisplayHandle,
+        _data_init: &mut DataInit<'_, Self>,
+    ) {
+        let (buffer, with_damage) = match request {
+            zwlr_screencopy_frame_v1::Request::Copy { buffer } => (buffer, false),
+            zwlr_screencopy_frame_v1::Request::CopyWithDamage { buffer } => (buffer, true),
+            zwlr_screencopy_frame_v1::Request::Destroy => return,
+            _ => unreachable!(),
+        };
+
+        if data.used.swap(true, Ordering::SeqCst) {
+            frame.post_error(
+                zwlr_screencopy_frame_v1::Error::AlreadyUsed,
+                "the frame was already used to copy",
+            );
+            return;
+        }
+
+        state.screencopy(frame, data, &buffer, with_damage);
+    }
+
+    fn destroyed(state: &mut Self, _client: ClientId, frame: &ZwlrScreencopyFrameV1, _data: &ScreencopyFrameState) {
+        state.state.screencopy_state.pending.retain(|(pending, _)| pending != frame);
+    }
+}
+
+/// The part of `output` to capture in physical pixels, `region` is logical and relative to it.
+fn capture_region(output: &Output, region: Option<Rectangle<i32, Logical>>) -> Option<Rectangle<i32, Physical>> {
+    let mode = output.current_mode()?;
+    let output_size = output.current_transform().transform_size(mode.size);
+    let output_rect = Rectangle::from_size(output_size);
+
+    let region = match region {
+        Some(region) => {
+            let scale = output.current_scale().fractional_scale();
+            region.to_physical_precise_round(scale).intersection(output_rect)?
+        }
+        None => output_rect,
+    };
+
+    (!region.is_empty()).then_some(region)
+}
+
+enum CaptureBuffer {
+    Shm,
+    Dma,
+}
+
+fn capture_buffer(buffer: &WlBuffer, region: Rectangle<i32, Physical>) -> Option<CaptureBuffer> {
+    match buffer_type(buffer) {
+        Some(BufferType::Shm) if shm_buffer_matches(buffer, region) => Some(CaptureBuffer::Shm),
+        Some(BufferType::Dma) if dmabuf_matches(buffer, region) => Some(CaptureBuffer::Dma),
+        _ => None,
+    }
+}
+
+impl GlobalData {
+    fn screencopy(
+        &mut self,
+        frame: &ZwlrScreencopyFrameV1,
+        data: &ScreencopyFrameState,
+        buffer: &WlBuffer,
+        with_damage: bool,
+    ) {
+        let _span = tracy_client::span!("screencopy");
+
+        let Some((output, region)) = data.target.as_ref() else {
+            frame.failed();
+            return;
+        };
+
+        if capture_buffer(buffer, *region).is_none() {
+            frame.post_error(
+                zwlr_screencopy_frame_v1::Error::InvalidBuffer,
+                "the buffer does not match the advertised format or size",
+            );
+            return;
+        }
+
+        // only the current output is drawn
+        if output != self.output_manager.current_output() {
+            frame.failed();
+            return;
+        }
+
+        if with_damage {
+            self.state.screencopy_state.pending.push((frame.clone(), buffer.clone()));
+            return;
+        }
+
+        self.copy_frame(frame, data, buffer, false);
+    }
+
+    /// Answer the copy_with_damage frames of `output` after it was drawn.
+    pub fn process_screencopies(&mut self, output: &Output, damaged: bool) {
+        if !damaged {
+            return;
+        }
+
+        let pending = std::mem::take(&mut self.state.screencopy_state.pending);
+        for (frame, buffer) in pending {
+            if !frame.is_alive() {
+                continue;
+            }
+
+            let Some(data) = frame.data::<ScreencopyFrameState>() else {
+                continue;
+            };
+
+            match data.target.as_ref() {
+                Some((target, _)) if target != output => {
+                    self.state.screencopy_state.pending.push((frame.clone(), buffer));
+                }
+                _ => self.copy_frame(&frame, data, &buffer, true),
+            }
+        }
+    }
+
+    fn copy_frame(
+        &mut self,
+        frame: &ZwlrScreencopyFrameV1,
+        data: &ScreencopyFrameState,
+        buffer: &WlBuffer,
+        with_damage: bool,
+    ) {
+        let Some((output, region)) = data.target.as_ref() else {
+            frame.failed();
+            return;
+        };
+        let region = *region;
+
+        // the buffer may be gone, or the output switched while waiting for damage
+        let Some(kind) = capture_buffer(buffer, region) else {
+            frame.failed();
+            return;
+        };
+        if output != self.output_manager.current_output() {
+            frame.failed();
+            return;
+        }
+
+        let Some(output_geo) = self.output_manager.output_geometry(output) else {
+            frame.failed();
+            return;
+        };
+        let scale = output.current_scale().fractional_scale();
+        let origin = output_geo.loc.to_physical_precise_round(scale) + region.loc;
+
+        let result = self.backend.with_renderer(|renderer| {
+            let elements = self.render_manager.get_render_elements(
+                renderer,
+                &self.output_manager,
+                &self.workspace_manager,
+                &self.window_manager,
+                &mut self.cursor_manager,
+                &self.input_manager,
+                &mut self.animation_manager,
+                data.overlay_cursor,
+            );
+
+            match kind {
+                CaptureBuffer::Shm => {
+                    let pixels = capture_elements(
+                        renderer,
+                        &elements,
+                        Rectangle::new(origin, region.size),
+                        scale,
+                        CAPTURE_FOURCC,
+                    )?;
+                    copy_to_shm(buffer, &pixels, region)
+                }
+                CaptureBuffer::Dma => copy_to_dmabuf(renderer, buffer, &elements, region, scale, origin),
+            }
+        });
+
+        match result {
+            Some(Ok(())) => {
+                if with_damage {
+                    // the output changed, but where is not tracked per client
+                    frame.damage(0, 0, region.size.w as u32, region.size.h as u32);
+                }
+                frame.flags(Flags::empty());
+
+                let time: Duration = self.clock.now().into();
+                let secs = time.as_secs();
+                frame.ready((secs >> 32) as u32, secs as u32, time.subsec_nanos());
+            }
+            Some(Err(err)) => {
+                warn!("screencopy failed: {:?}", err);
+                frame.failed();
+            }
+            None => frame.failed(),
+        }
+    }
+}
+
+impl GlobalData {
+    /// Save a png of the current output, a region of it or the focused window.
+    pub fn screenshot(&mut self, target: &ScreenshotTarget) {
+        let _span = tracy_client::span!("screenshot");
+
+        let output = self.output_manager.current_output().clone();
+        let Some(output_geo) = self.output_manager.output_geometry(&output) else {
+            return;
+        };
+        let scale = output.current_scale().fractional_scale();
+
+        let region = match target {
+            ScreenshotTarget::Output => Some(output_geo),
+            ScreenshotTarget::Region(region) => {
+                Rectangle::new(output_geo.loc + region.loc, region.size).intersection(output_geo)
+            }
+            ScreenshotTarget::Window => None,
+        };
+
+        let result = match (target, region) {
+            (ScreenshotTarget::Window, _) => {
+                let Some(KeyboardFocusTarget::Window(window)) = self.input_manager.get_keyboard_focus() else {
+                    info!("no focused window to take a screenshot of");
+                    return;
+                };
+
+                self.backend.with_renderer(|renderer| capture_window(renderer, &window, scale))
+            }
+            (_, Some(region)) => {
+                let region = region.to_physical_precise_round(scale);
+                let size: Size<i32, Buffer> = (region.size.w, region.size.h).into();
+
+                self.backend.with_renderer(|renderer| {
+                    let elements = self.render_manager.get_render_elements(
+                        renderer,
+                        &self.output_manager,
+                        &self.workspace_manager,
+                        &self.window_manager,
+                        &mut self.cursor_manager,
+                        &self.input_manager,
+                        &mut self.animation_manager,
+                        false,
+                    );
+
+                    capture_elements(renderer, &elements, region, scale, Fourcc::Abgr8888)
+                        .map(|pixels| (size, pixels))
+                })
+            }
+            (_, None) => {
+                info!("screenshot region is outside of the output");
+                return;
+            }
+        };
+
+        match result {
+            Some(Ok((size, pixels))) => save_png(size, pixels),
+            Some(Err(err)) => warn!("screenshot failed: {:?}", err),
+            None => {}
+        }
+    }
+}
+
+fn screenshot_dir() -> Option<PathBuf> {
+    dirs::picture_dir()
+        .or_else(|| dirs::home_dir().map(|home| home.join("Pictures")))
+        .map(|pictures| pictures.join("Screenshots"))
+}
+
+/// Encoding can take a while for large outputs, so it happens off the event loop.
+fn save_png(size: Size<i32, Buffer>, pixels: Vec<u8>) {
+    let Some(dir) = screenshot_dir() else {
+        warn!("no directory to save screenshots to");
+        return;
+    };
+
+    let time = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+    let path = dir.join(format!("screenshot_{}.png", time.as_millis()));
+
+    std::thread::spawn(move || {
+        let result = std::fs::create_dir_all(&dir)
+            .map_err(anyhow::Error::from)
+            .and_then(|_| {
+                image::save_buffer(&path, &pixels, size.w as u32, size.h as u32, image::ExtendedColorType::Rgba8)
+                    .map_err(anyhow::Error::from)
+            });
+
+        match result {
+            Ok(()) => info!("screenshot saved to {:?}", path),
+            Err(err) => warn!("failed to save screenshot to {:?}: {:?}", path, err),
+        }
+    });
+}
+
+pub(super) fn shm_buffer_matches(buffer: &WlBuffer, region: Rectangle<i32, Physical>) -> bool {
+    with_buffer_contents(buffer, |_, len, info| {
+        info.format == SHM_FORMAT
+            && info.width == region.size.w
+            && info.height == region.size.h
+            && info.stride >= region.size.w * 4
+            && (info.offset + info.stride * info.height) as usize <= len
+    })
+    .unwrap_or(false)
+}
+
+fn dmabuf_matches(buffer: &WlBuffer, region: Rectangle<i32, Physical>) -> bool {
+    get_dmabuf(buffer)
+        .map(|dmabuf| {
+            let size = dmabuf.size();
+            dmabuf.format().code == CAPTURE_FOURCC && size.w == region.size.w && size.h == region.size.h
+        })
+        .unwrap_or(false)
+}
+
+pub(super) fn copy_to_shm(buffer: &WlBuffer, pixels: &[u8], region: Rectangle<i32, Physical>) -> anyhow::Result<()> {
+    let row_len = region.size.w as usize * 4;
+
+    with_buffer_contents_mut(buffer, |ptr, len, info| {
+        let (offset, stride) = (info.offset as usize, info.stride as usize);
+        if offset + stride * region.size.h as usize > len || pixels.len() < row_len * region.size.h as usize {
+            anyhow::bail!("the shm buffer is too small");
+        }
+
+        for (row, src) in pixels.chunks_exact(row_len).take(region.size.h as usize).enumerate() {
+            // SAFETY: checked above that every row fits into the pool
+            unsafe {
+                std::ptr::copy_nonoverlapping(src.as_ptr(), ptr.add(offset + row * stride), row_len);
+            }
+        }
+
+        Ok(())
+    })?
+}
+
+fn copy_to_dmabuf<E: RenderElement<GlesRenderer>>(
+    renderer: &mut GlesRenderer,
+    buffer: &WlBuffer,
+    elements: &[E],
+    region: Rectangle<i32, Physical>,
+    scale: f64,
+    origin: Point<i32, Physical>,
+) -> anyhow::Result<()> {
+    let mut dmabuf = get_dmabuf(buffer)
+        .map_err(|_| anyhow::anyhow!("the buffer is not a dmabuf"))?
+        .clone();
+    let mut target = renderer.bind(&mut dmabuf)?;
+
+    let sync = draw_elements(
+        renderer,
+        &mut target,
+        region.size,
+        scale,
+        origin,
+        elements,
+        Color32F::new(0.0, 0.0, 0.0, 1.0),
+    )?;
+    // the client reads the buffer right after ready
+    let _ = sync.wait();
+
+    Ok(())
+}
@@ -0,0 +1,70 @@
+use std::time::Duration;
+
+use smithay::{
+    delegate_xdg_activation,
+    reexports::wayland_server::{protocol::wl_surface::WlSurface, Resource},
+    utils::SERIAL_COUNTER,
+    wayland::{
+        seat::WaylandFocus,
+        xdg_activation::{XdgActivationHandler, XdgActivationState, XdgActivationToken, XdgActivationTokenData},
+    },
+};
+
+use crate::state::GlobalData;
+
+// tokens older than this only mark the window urgent
+const TOKEN_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Token user data, the issuing client owned the keyboard focus when it was created.
+struct FocusedIssuer;
+
+impl XdgActivationHandler for GlobalData {
+    fn activation_state(&mut self) -> &mut XdgActivationState {
+        &mut self.state.xdg_activation_state
+    }
+
+    fn token_created(&mut self, _token: XdgActivationToken, data: XdgActivationTokenData) -> bool {
+        let focused_client = self.input_manager
+            .get_keyboard()
+            .and_then(|keyboard| keyboard.current_focus())
+            .and_then(|focus| focus.wl_surface().and_then(|surface| surface.client()))
+            .map(|client| client.id());
+
+        if focused_client.is_some() && focused_client == data.client_id {
+            data.user_data.insert_if_missing(|| FocusedIssuer);
+        }
+
+        // every token is kept, whether it may steal focus is decided on activation
+        true
+    }
+
+    fn request_activation(&mut self, token: XdgActivationToken, token_data: XdgActivationTokenData, surface: WlSurface) {
+        let Some(window) = self.window_manager.get_mapped(&surface.into()).cloned() else {
+            self.state.xdg_activation_state.remove_token(&token);
+            return;
+        };
+
+        // only a token issued to the client that had keyboard focus at that time may take focus
+        let focused_issuer = match (token_data.serial, self.input_manager.get_keyboard()) {
+            (Some((serial, _)), Some(keyboard)) => {
+                token_data.user_data.get::<FocusedIssuer>().is_some()
+                    && keyboard
+                        .last_enter()
+                        .map_or(false, |last_enter| serial.is_no_older_than(&last_enter))
+            }
+            _ => false,
+        };
+        let fresh = token_data.timestamp.elapsed() < TOKEN_TIMEOUT;
+
+        if focused_issuer && fresh {
+            self.activate_window(&window, SERIAL_COUNTER.next_serial());
+        } else {
+            debug!("activation token rejected, marking window urgent");
+            self.window_manager.set_urgent(&window, true);
+            self.queue_redraw();
+        }
+
+        self.state.xdg_activation_state.remove_token(&token);
+    }
+}
+delegate_xdg_activation!(GlobalData);
@@ -12,7 +12,7 @@ use smithay::{
             protocol::{wl_output::WlOutput, wl_seat, wl_surface::WlSurface}, Resource
         },
     }, utils::Serial, wayland::{
-        compositor::{self, with_states},
+        compositor::with_states,
         shell::xdg::{
             PopupSurface, PositionerState, ToplevelSurface, XdgShellHandler, XdgShellState, XdgToplevelSurfaceData
         },
@@ -225,24 +225,34 @@ impl XdgShellHandler for GlobalData {
 
     fn grab(&mut self, _surface: PopupSurface, _seat: wl_seat::WlSeat, _serial: Serial) {}
 
-    fn title_changed(&mut self, surface: ToplevelSurface) {
-        let (title, app_id) = 
-            compositor::with_states(surface.wl_surface(), |states| {
-                let roll= &mut states
-                    .data_map
-                    .get::<XdgToplevelSurfaceData>()
-                    .unwrap()
-                    .lock()
-                    .unwrap();
-                (roll.title.clone(), roll.app_id.clone())
-            });
+    fn title_changed(&mut self, _surface: ToplevelSurface) {
+        self.refresh_foreign_toplevels();
+    }
 
-        self.window_manager.get_foreign_handle(surface.wl_surface())
-            .map(|handle| {
-                handle.send_title(&title.unwrap_or("unkown".to_string()));
-                handle.send_app_id(&app_id.unwrap_or("unkown".to_string()));
-                handle.send_done();
-            });
+    fn app_id_changed(&mut self, surface: ToplevelSurface) {
+        self.refresh_decoration_mode(&surface);
+        self.refresh_foreign_toplevels();
+    }
+
+    fn maximize_request(&mut self, surface: ToplevelSurface) {
+        if let Some(window) = self.window_manager.get_mapped(&surface.clone().into()).cloned() {
+            self.set_maximized(&window, true);
+        }
+
+        // always reply with a configure, like fullscreen
+        if surface.is_initial_configure_sent() {
+            surface.send_pending_configure();
+        }
+    }
+
+    fn unmaximize_request(&mut self, surface: ToplevelSurface) {
+        if let Some(window) = self.window_manager.get_mapped(&surface.clone().into()).cloned() {
+            self.set_maximized(&window, false);
+        }
+
+        if surface.is_initial_configure_sent() {
+            surface.send_pending_configure();
+        }
     }
 }
 delegate_xdg_shell!(GlobalData);
@@ -268,7 +278,7 @@ impl GlobalData {
             }
         };
 
-        let window_rect = window.get_rect().unwrap();
+        let window_rect = window.content_rect(window.get_rect().unwrap());
 
         // The target geometry for the positioner should be relative to its parent's geometry, so
         // we will compute that here.
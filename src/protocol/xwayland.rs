@@ -4,7 +4,7 @@ use std::process::Stdio;
 use smithay::{delegate_xwayland_keyboard_grab, delegate_xwayland_shell, wayland::xwayland_keyboard_grab::XWaylandKeyboardGrabHandler};
 use smithay::{
     desktop::Window, reexports::wayland_server::protocol::wl_surface::WlSurface, utils::{Logical, Rectangle, SERIAL_COUNTER}, wayland::{compositor::CompositorHandler, xwayland_shell::{XWaylandShellHandler, XWaylandShellState}}, xwayland::{
-        xwm::{Reorder, ResizeEdge as X11ResizeEdge, WmWindowType, XwmId}, X11Surface, X11Wm, XWayland, XWaylandEvent, XwmHandler
+        xwm::{Reorder, ResizeEdge as X11ResizeEdge, WmWindowProperty, WmWindowType, XwmId}, X11Surface, X11Wm, XWayland, XWaylandEvent, XwmHandler
     }
 };
 
@@ -189,7 +189,7 @@ impl XwmHandler for GlobalData {
                     window.send_rect(rect);
                 }
                 WindowLayout::Tiled => {
-                    let rect = window.get_rect();
+                    let rect = window.get_rect().map(|rect| window.content_rect(rect));
                     let _ = surface.configure(rect);
                 }
             }
@@ -224,7 +224,7 @@ impl XwmHandler for GlobalData {
             surface.set_fullscreen(false).unwrap();
 
             if let Some(rect) = window.get_rect() {
-                let _ = surface.configure(rect);
+                let _ = surface.configure(window.content_rect(rect));
             }
 
             let output = self.output_manager.current_output().clone();
@@ -232,6 +232,23 @@ impl XwmHandler for GlobalData {
         }
     }
 
+    fn property_notify(&mut self, _xwm: XwmId, surface: X11Surface, property: WmWindowProperty) {
+        // only the ICCCM urgency hint in WM_HINTS marks X11 windows urgent, the xwm handles
+        // _NET_WM_STATE client messages itself and only forwards fullscreen, maximize and
+        // minimize, so clients that only request _NET_WM_STATE_DEMANDS_ATTENTION go unnoticed
+        if property != WmWindowProperty::Hints {
+            return;
+        }
+
+        if let Some(window) = self.window_manager.get_mapped(&surface.clone().into()).cloned() {
+            let urgent = surface.hints().map_or(false, |hints| hints.urgent);
+            if urgent != self.window_manager.is_urgent(&window) {
+                self.window_manager.set_urgent(&window, urgent);
+                self.queue_redraw();
+            }
+        }
+    }
+
     fn resize_request(&mut self, _xwm: XwmId, window: X11Surface, _button: u32, _resize_edge: X11ResizeEdge) {
         if !self.input_manager.is_mainmod_pressed() {
             return
@@ -1,22 +1,57 @@
+use std::collections::HashMap;
+
+use image::{imageops, imageops::FilterType, RgbaImage};
 use smithay::{
-    backend::renderer::{
-        element::{Element, Id, Kind, RenderElement, UnderlyingStorage},
-        gles::{
-            GlesError, GlesFrame, GlesPixelProgram, GlesRenderer, Uniform, UniformName, UniformType,
+    backend::{
+        allocator::Fourcc,
+        renderer::{
+            element::{memory::MemoryRenderBuffer, solid::SolidColorBuffer, Element, Id, Kind, RenderElement, UnderlyingStorage},
+            gles::{
+                GlesError, GlesFrame, GlesPixelProgram, GlesRenderer, Uniform, UniformName, UniformType,
+            },
+            utils::{CommitCounter, OpaqueRegions},
         },
-        utils::{CommitCounter, OpaqueRegions},
     },
-    utils::{Buffer, Logical, Physical, Rectangle, Scale, Transform},
+    reexports::calloop::channel::Sender,
+    utils::{Buffer, Logical, Physical, Rectangle, Scale, Size, Transform},
 };
 
-use crate::backend::tty::{TtyFrame, TtyRenderer, TtyRendererError};
+use crate::{
+    backend::tty::{TtyFrame, TtyRenderer, TtyRendererError},
+    config::background::{BackgroundShader, BackgroundSource, ImageFit},
+};
 
 use super::AsGlesFrame;
 
-#[allow(dead_code)]
-pub struct Background(pub GlesPixelProgram);
+pub struct BackgroundShaders(pub HashMap<BackgroundShader, GlesPixelProgram>);
+
+/// What is drawn behind everything on one output.
+pub enum CachedBackground {
+    Image(MemoryRenderBuffer),
+    // decoding on a thread, nothing is drawn until it arrives
+    Loading,
+    Color(SolidColorBuffer),
+    Shader { element: BackgroundRenderElement, animate: bool },
+    // loading failed, not retried until the config or the output changes
+    Failed,
+}
+
+/// A background together with what it was built from.
+pub struct OutputBackground {
+    pub source: BackgroundSource,
+    pub size: Size<i32, Physical>,
+    pub background: CachedBackground,
+}
+
+/// An image decoded off the event loop, for the background it was requested by.
+pub struct DecodedImage {
+    pub output: String,
+    pub source: BackgroundSource,
+    pub size: Size<i32, Physical>,
+    pub image: anyhow::Result<RgbaImage>,
+}
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct BackgroundRenderElement {
     id: Id,
     shader: GlesPixelProgram,
@@ -28,7 +63,6 @@ pub struct BackgroundRenderElement {
     kind: Kind,
 }
 
-#[allow(dead_code)]
 impl BackgroundRenderElement {
     pub fn new(
         shader: GlesPixelProgram,
@@ -54,24 +88,40 @@ impl BackgroundRenderElement {
     }
 
     pub fn complie_shaders(renderer: &mut GlesRenderer) {
-        let background = renderer
-            .compile_custom_pixel_shader(
-                include_str!("../render/shaders/background.frag"),
+        let sources = [
+            (BackgroundShader::Default, include_str!("../render/shaders/background.frag")),
+            (BackgroundShader::Blobs, include_str!("../render/shaders/background_2.frag")),
+            (BackgroundShader::Slow, include_str!("../render/shaders/background_4_slow.frag")),
+            (BackgroundShader::Dark, include_str!("../render/shaders/background_dark2.frag")),
+            (BackgroundShader::Light, include_str!("../render/shaders/background_light.frag")),
+        ];
+
+        let mut shaders = HashMap::new();
+        for (shader, source) in sources {
+            let program = renderer.compile_custom_pixel_shader(
+                source,
                 &[
                     UniformName::new("u_resolution", UniformType::_2f),
                     UniformName::new("u_time", UniformType::_1f),
                 ],
-            )
-            .unwrap();
+            );
+
+            match program {
+                Ok(program) => {
+                    shaders.insert(shader, program);
+                }
+                Err(err) => warn!("failed to compile background shader {:?}: {:?}", shader, err),
+            }
+        }
 
         renderer
             .egl_context()
             .user_data()
-            .insert_if_missing(|| Background(background));
+            .insert_if_missing(|| BackgroundShaders(shaders));
     }
 
     /// Resize the canvas area
-    pub fn _resize(
+    pub fn resize(
         &mut self,
         area: Rectangle<i32, Logical>,
         opaque_regions: Option<Vec<Rectangle<i32, Logical>>>,
@@ -88,7 +138,7 @@ impl BackgroundRenderElement {
     /// (see [`GlesRenderer::compile_custom_pixel_shader`] and [`GlesFrame::render_pixel_shader_to`]).
     ///
     /// This replaces the stored uniforms, you have to update all of them, partial updates are not possible.
-    pub fn _update_uniforms(&mut self, additional_uniforms: Vec<Uniform<'_>>) {
+    pub fn update_uniforms(&mut self, additional_uniforms: Vec<Uniform<'_>>) {
         self.additional_uniforms = additional_uniforms
             .into_iter()
             .map(|u| u.into_owned())
@@ -181,3 +231,90 @@ impl<'render> RenderElement<TtyRenderer<'render>> for BackgroundRenderElement {
     }
 }
 
+
+/// Decode an image and lay it out on a canvas of the output size.
+/// Decode and scale the image at `path` on a thread, the result is sent to `sender`.
+pub fn decode_image(sender: Sender<DecodedImage>, output: String, source: BackgroundSource, size: Size<i32, Physical>) {
+    let BackgroundSource::Image { path, fit } = source.clone() else {
+        return;
+    };
+
+    std::thread::spawn(move || {
+        let image = render_image(&path, fit, size);
+        let _ = sender.send(DecodedImage { output, source, size, image });
+    });
+}
+
+/// The pixels of a decoded image, as the renderer expects them.
+pub fn image_buffer(image: &RgbaImage) -> MemoryRenderBuffer {
+    MemoryRenderBuffer::from_slice(
+        image.as_raw(),
+        Fourcc::Abgr8888,
+        (image.width() as i32, image.height() as i32),
+        1,
+        Transform::Normal,
+        None,
+    )
+}
+
+fn render_image(path: &str, fit: ImageFit, size: Size<i32, Physical>) -> anyhow::Result<RgbaImage> {
+    let _span = tracy_client::span!("render_image");
+
+    if size.is_empty() {
+        anyhow::bail!("output has no size");
+    }
+
+    let image = image::open(path)?.to_rgba8();
+    let (width, height) = (size.w as u32, size.h as u32);
+
+    let mut canvas = match fit {
+        ImageFit::Fill => {
+            let scale = (width as f64 / image.width() as f64).max(height as f64 / image.height() as f64);
+            let scaled = scale_image(&image, scale);
+
+            // crop the overflow evenly on both sides
+            let x = (scaled.width().saturating_sub(width)) / 2;
+            let y = (scaled.height().saturating_sub(height)) / 2;
+            imageops::crop_imm(&scaled, x, y, width, height).to_image()
+        }
+        ImageFit::Fit => {
+            let scale = (width as f64 / image.width() as f64).min(height as f64 / image.height() as f64);
+            let scaled = scale_image(&image, scale);
+
+            centered(&scaled, width, height)
+        }
+        ImageFit::Center => centered(&image, width, height),
+        ImageFit::Tile => {
+            let mut canvas = RgbaImage::new(width, height);
+            imageops::tile(&mut canvas, &image);
+            canvas
+        }
+    };
+
+    // the renderer blends premultiplied colors
+    for pixel in canvas.pixels_mut() {
+        let alpha = pixel[3] as u32;
+        for channel in &mut pixel.0[..3] {
+            *channel = ((*channel as u32 * alpha + 127) / 255) as u8;
+        }
+    }
+
+    Ok(canvas)
+}
+
+fn scale_image(image: &RgbaImage, scale: f64) -> RgbaImage {
+    let width = ((image.width() as f64 * scale).round() as u32).max(1);
+    let height = ((image.height() as f64 * scale).round() as u32).max(1);
+
+    imageops::resize(image, width, height, FilterType::Triangle)
+}
+
+// black borders around anything smaller than the canvas
+fn centered(image: &RgbaImage, width: u32, height: u32) -> RgbaImage {
+    let mut canvas = RgbaImage::from_pixel(width, height, image::Rgba([0, 0, 0, 255]));
+    let x = (width as i64 - image.width() as i64) / 2;
+    let y = (height as i64 - image.height() as i64) / 2;
+    imageops::overlay(&mut canvas, image, x, y);
+
+    canvas
+}
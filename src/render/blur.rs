@@ -0,0 +1,370 @@
+use smithay::{
+    backend::{
+        allocator::Fourcc,
+        renderer::{
+            element::{Element, Id, Kind, RenderElement, UnderlyingStorage},
+            gles::{GlesError, GlesFrame, GlesRenderer, GlesTexProgram, GlesTexture, Uniform, UniformName, UniformType},
+            utils::{CommitCounter, OpaqueRegions},
+            Bind, Color32F, Frame, Offscreen, Renderer, Texture,
+        },
+    },
+    utils::{Buffer, Logical, Physical, Point, Rectangle, Scale, Size, Transform},
+};
+
+use crate::backend::tty::{TtyFrame, TtyRenderer, TtyRendererError};
+
+use super::AsGlesFrame;
+
+pub struct BlurShaders {
+    down: GlesTexProgram,
+    up: GlesTexProgram,
+}
+
+impl BlurShaders {
+    pub fn complie_shaders(renderer: &mut GlesRenderer) {
+        let uniforms = [
+            UniformName::new("half_pixel", UniformType::_2f),
+            UniformName::new("offset", UniformType::_1f),
+        ];
+
+        let down = renderer
+            .compile_custom_texture_shader(include_str!("../render/shaders/blur_down.frag"), &uniforms)
+            .unwrap();
+        let up = renderer
+            .compile_custom_texture_shader(include_str!("../render/shaders/blur_up.frag"), &uniforms)
+            .unwrap();
+
+        // Save texture shaders in EGL rendering context.
+        renderer
+            .egl_context()
+            .user_data()
+            .insert_if_missing(|| BlurShaders { down, up });
+    }
+}
+
+/// The blurred wallpaper and bottom layers of one output.
+///
+/// Only rebuilt when one of the blurred elements commits, the output
+/// changes size or the blur parameters change.
+#[derive(Debug, Default)]
+pub struct BlurCache {
+    texture: Option<GlesTexture>,
+    // bumped whenever the texture is replaced
+    generation: usize,
+
+    elements: Vec<(Id, CommitCounter)>,
+    origin: Point<i32, Physical>,
+    size: Size<i32, Physical>,
+    passes: usize,
+    offset: f32,
+}
+
+impl BlurCache {
+    pub fn texture(&self) -> Option<(GlesTexture, usize)> {
+        self.texture.clone().map(|texture| (texture, self.generation))
+    }
+
+    /// Whether the texture was blurred from elements with these `commits` and parameters.
+    pub fn is_current(
+        &self,
+        commits: &[(Id, CommitCounter)],
+        origin: Point<i32, Physical>,
+        size: Size<i32, Physical>,
+        passes: usize,
+        offset: f32,
+    ) -> bool {
+        self.elements == commits
+            && self.origin == origin
+            && self.size == size
+            && self.passes == passes
+            && self.offset == offset
+    }
+
+    /// `elements` are front to back, `commits` those of the same elements drawn on screen,
+    /// `origin` is where the output starts.
+    pub fn update<E: RenderElement<GlesRenderer>>(
+        &mut self,
+        renderer: &mut GlesRenderer,
+        elements: &[E],
+        commits: Vec<(Id, CommitCounter)>,
+        origin: Point<i32, Physical>,
+        size: Size<i32, Physical>,
+        scale: f64,
+        passes: usize,
+        offset: f32,
+    ) -> Result<(), GlesError> {
+        let _span = tracy_client::span!("BlurCache::update");
+
+        self.elements = commits;
+        self.origin = origin;
+        self.size = size;
+        self.passes = passes;
+        self.offset = offset;
+        self.generation += 1;
+
+        if elements.is_empty() || size.is_empty() {
+            self.texture = None;
+            return Ok(());
+        }
+
+        let shaders = renderer
+            .egl_context()
+            .user_data()
+            .get::<BlurShaders>()
+            .map(|shaders| (shaders.down.clone(), shaders.up.clone()));
+        let Some((down, up)) = shaders else {
+            self.texture = None;
+            return Ok(());
+        };
+
+        let mut texture: GlesTexture = renderer.create_buffer(Fourcc::Abgr8888, (size.w, size.h).into())?;
+        {
+            let mut target = renderer.bind(&mut texture)?;
+            let mut frame = renderer.render(&mut target, size, Transform::Normal)?;
+            frame.clear(Color32F::new(0.0, 0.0, 0.0, 1.0), &[Rectangle::from_size(size)])?;
+
+            for element in elements.iter().rev() {
+                let mut dst = element.geometry(Scale::from(scale));
+                dst.loc -= origin;
+                element.draw(&mut frame, element.src(), dst, &[Rectangle::from_size(dst.size)], &[])?;
+            }
+
+            let _ = frame.finish()?;
+        }
+
+        // halve the size on the way down and double it on the way up
+        let mut sizes: Vec<Size<i32, Buffer>> = vec![texture.size()];
+        for _ in 0..passes {
+            let last = sizes[sizes.len() - 1];
+            sizes.push(((last.w / 2).max(1), (last.h / 2).max(1)).into());
+        }
+
+        for size in sizes.iter().skip(1) {
+            texture = blur_pass(renderer, &down, &texture, *size, offset)?;
+        }
+        for size in sizes.iter().rev().skip(1) {
+            texture = blur_pass(renderer, &up, &texture, *size, offset)?;
+        }
+
+        self.texture = Some(texture);
+
+        Ok(())
+    }
+}
+
+fn blur_pass(
+    renderer: &mut GlesRenderer,
+    program: &GlesTexProgram,
+    source: &GlesTexture,
+    size: Size<i32, Buffer>,
+    offset: f32,
+) -> Result<GlesTexture, GlesError> {
+    let mut texture: GlesTexture = renderer.create_buffer(Fourcc::Abgr8888, size)?;
+
+    {
+        let output_size: Size<i32, Physical> = (size.w, size.h).into();
+        let dst = Rectangle::from_size(output_size);
+
+        let mut target = renderer.bind(&mut texture)?;
+        let mut frame = renderer.render(&mut target, output_size, Transform::Normal)?;
+        frame.clear(Color32F::TRANSPARENT, &[dst])?;
+
+        let source_size = source.size();
+        let uniforms = [
+            Uniform::new("half_pixel", (0.5 / source_size.w as f32, 0.5 / source_size.h as f32)),
+            Uniform::new("offset", offset),
+        ];
+
+        frame.render_texture_from_to(
+            source,
+            Rectangle::from_size(source_size.to_f64()),
+            dst,
+            &[dst],
+            &[],
+            Transform::Normal,
+            1.0,
+            Some(program),
+            &uniforms,
+        )?;
+
+        let _ = frame.finish()?;
+    }
+
+    Ok(texture)
+}
+
+/// The part of the blurred background behind a window or layer surface.
+///
+/// Kept across frames per surface, so it is only damaged when it moves
+/// or the background is blurred again.
+#[derive(Debug, Clone)]
+pub struct BlurRenderElement {
+    id: Id,
+    commit_counter: CommitCounter,
+    // the rounded clip program
+    program: GlesTexProgram,
+    texture: GlesTexture,
+    generation: usize,
+
+    area: Rectangle<i32, Logical>,
+    output_loc: Point<i32, Logical>,
+    scale: f64,
+    corner_radius: f32,
+    alpha: f32,
+}
+
+impl BlurRenderElement {
+    pub fn new(
+        program: GlesTexProgram,
+        (texture, generation): (GlesTexture, usize),
+        area: Rectangle<i32, Logical>,
+        output_loc: Point<i32, Logical>,
+        scale: f64,
+        corner_radius: f32,
+        alpha: f32,
+    ) -> Self {
+        Self {
+            id: Id::new(),
+            commit_counter: CommitCounter::default(),
+            program,
+            texture,
+            generation,
+            area,
+            output_loc,
+            scale,
+            corner_radius,
+            alpha,
+        }
+    }
+
+    /// Only damage if the background or the placement changed.
+    pub fn update(
+        &mut self,
+        (texture, generation): (GlesTexture, usize),
+        area: Rectangle<i32, Logical>,
+        output_loc: Point<i32, Logical>,
+        scale: f64,
+        corner_radius: f32,
+        alpha: f32,
+    ) {
+        if self.generation != generation
+            || self.area != area
+            || self.output_loc != output_loc
+            || self.scale != scale
+            || self.corner_radius != corner_radius
+            || self.alpha != alpha
+        {
+            self.texture = texture;
+            self.generation = generation;
+            self.area = area;
+            self.output_loc = output_loc;
+            self.scale = scale;
+            self.corner_radius = corner_radius;
+            self.alpha = alpha;
+            self.commit_counter.increment();
+        }
+    }
+
+    fn uniforms(&self, src: Rectangle<f64, Buffer>) -> Vec<Uniform<'static>> {
+        // texture coords of the sampled region, mapped back to 0..1
+        let texture_size = self.texture.size().to_f64();
+        let loc = (src.loc.x / texture_size.w, src.loc.y / texture_size.h);
+        let size = (src.size.w / texture_size.w, src.size.h / texture_size.h);
+
+        let geo_size = self.area.size.to_f64().to_physical(self.scale);
+
+        vec![
+            Uniform::new("geo_offset", ((-loc.0 / size.0) as f32, (-loc.1 / size.1) as f32)),
+            Uniform::new("geo_scale", ((1.0 / size.0) as f32, (1.0 / size.1) as f32)),
+            Uniform::new("geo_size", (geo_size.w as f32, geo_size.h as f32)),
+            Uniform::new("corner_radius", self.corner_radius * self.scale as f32),
+            Uniform::new("clip", 1.0f32),
+            Uniform::new("dim", 0.0f32),
+            Uniform::new("desaturate", 0.0f32),
+        ]
+    }
+}
+
+impl Element for BlurRenderElement {
+    fn id(&self) -> &Id {
+        &self.id
+    }
+
+    fn current_commit(&self) -> CommitCounter {
+        self.commit_counter
+    }
+
+    fn src(&self) -> Rectangle<f64, Buffer> {
+        let local = Rectangle::new(self.area.loc - self.output_loc, self.area.size)
+            .to_f64()
+            .to_physical(self.scale);
+
+        Rectangle::new((local.loc.x, local.loc.y).into(), (local.size.w, local.size.h).into())
+    }
+
+    fn geometry(&self, scale: Scale<f64>) -> Rectangle<i32, Physical> {
+        self.area.to_physical_precise_round(scale)
+    }
+
+    fn opaque_regions(&self, _scale: Scale<f64>) -> OpaqueRegions<i32, Physical> {
+        // the rounded corners are transparent
+        OpaqueRegions::default()
+    }
+
+    fn alpha(&self) -> f32 {
+        self.alpha
+    }
+
+    fn kind(&self) -> Kind {
+        Kind::Unspecified
+    }
+}
+
+impl RenderElement<GlesRenderer> for BlurRenderElement {
+    fn draw(
+        &self,
+        frame: &mut GlesFrame<'_, '_>,
+        src: Rectangle<f64, Buffer>,
+        dst: Rectangle<i32, Physical>,
+        damage: &[Rectangle<i32, Physical>],
+        _opaque_regions: &[Rectangle<i32, Physical>],
+    ) -> Result<(), GlesError> {
+        frame.render_texture_from_to(
+            &self.texture,
+            src,
+            dst,
+            damage,
+            &[],
+            Transform::Normal,
+            self.alpha,
+            Some(&self.program),
+            &self.uniforms(src),
+        )
+    }
+
+    fn underlying_storage(&self, _renderer: &mut GlesRenderer) -> Option<UnderlyingStorage> {
+        None
+    }
+}
+
+impl<'render> RenderElement<TtyRenderer<'render>> for BlurRenderElement {
+    fn draw(
+        &self,
+        frame: &mut TtyFrame<'_, '_, '_>,
+        src: Rectangle<f64, Buffer>,
+        dst: Rectangle<i32, Physical>,
+        damage: &[Rectangle<i32, Physical>],
+        opaque_regions: &[Rectangle<i32, Physical>],
+    ) -> Result<(), TtyRendererError<'_>> {
+        let frame = frame.as_gles_frame();
+        RenderElement::<GlesRenderer>::draw(&self, frame, src, dst, damage, opaque_regions)?;
+        Ok(())
+    }
+
+    fn underlying_storage(
+        &self,
+        _renderer: &mut TtyRenderer<'render>,
+    ) -> Option<UnderlyingStorage> {
+        None
+    }
+}
@@ -15,7 +15,8 @@ use super::AsGlesFrame;
 
 pub struct BorderShader(pub GlesPixelProgram);
 
-#[derive(Debug)]
+/// Kept across frames per window, so an unchanged border produces no damage.
+#[derive(Debug, Clone)]
 pub struct BorderRenderElement {
     id: Id,
     shader: GlesPixelProgram,
@@ -57,10 +58,13 @@ impl BorderRenderElement {
                 include_str!("../render/shaders/border.frag"),
                 &[
                     UniformName::new("u_resolution", UniformType::_2f),
-                    UniformName::new("border_color", UniformType::_3f),
+                    UniformName::new("color_start", UniformType::_4f),
+                    UniformName::new("color_end", UniformType::_4f),
+                    UniformName::new("gradient_angle", UniformType::_1f),
                     UniformName::new("border_thickness", UniformType::_1f),
-                    UniformName::new("u_time", UniformType::_1f),
                     UniformName::new("corner_radius", UniformType::_1f),
+                    UniformName::new("u_time", UniformType::_1f),
+                    UniformName::new("pulse", UniformType::_1f),
                 ],
             )
             .unwrap();
@@ -72,6 +76,24 @@ impl BorderRenderElement {
             .insert_if_missing(|| BorderShader(border_shader));
     }
 
+    /// Move the border and replace its uniforms, only damaging if anything changed.
+    pub fn update(
+        &mut self,
+        area: Rectangle<i32, Logical>,
+        additional_uniforms: Vec<Uniform<'_>>,
+    ) {
+        let additional_uniforms: Vec<Uniform<'static>> = additional_uniforms
+            .into_iter()
+            .map(|u| u.into_owned())
+            .collect();
+
+        if self.area != area || self.additional_uniforms != additional_uniforms {
+            self.area = area;
+            self.additional_uniforms = additional_uniforms;
+            self.commit_counter.increment();
+        }
+    }
+
     /// Resize the canvas area
     pub fn _resize(
         &mut self,
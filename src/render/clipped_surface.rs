@@ -0,0 +1,274 @@
+use smithay::{
+    backend::renderer::{
+        element::{surface::WaylandSurfaceRenderElement, Element, Id, Kind, RenderElement, UnderlyingStorage},
+        gles::{GlesError, GlesFrame, GlesRenderer, GlesTexProgram, Uniform, UniformName, UniformType},
+        utils::{CommitCounter, DamageSet, OpaqueRegions},
+    },
+    utils::{Buffer, Logical, Physical, Rectangle, Scale, Transform},
+};
+
+use crate::backend::tty::{TtyFrame, TtyRenderer, TtyRendererError};
+
+use super::{AsGlesFrame, MondrianRenderer};
+
+pub struct ClippedSurfaceShader(pub GlesTexProgram);
+
+// own commits kept to find the surface commit a damage tracker last saw
+const HISTORY: usize = 16;
+
+/// Kept across frames per surface, so changing effects or clipping damage the surface
+/// even while the client commits nothing.
+#[derive(Debug, Clone)]
+pub struct ClipDamage {
+    id: Id,
+    commit: CommitCounter,
+    inner_commit: Option<CommitCounter>,
+    uniforms: Vec<Uniform<'static>>,
+    // own commit at which the uniforms last changed
+    uniforms_commit: CommitCounter,
+    // own commits with the surface commit they stand for
+    history: Vec<(CommitCounter, CommitCounter)>,
+}
+
+impl Default for ClipDamage {
+    fn default() -> Self {
+        Self {
+            id: Id::new(),
+            commit: CommitCounter::default(),
+            inner_commit: None,
+            uniforms: Vec::new(),
+            uniforms_commit: CommitCounter::default(),
+            history: Vec::new(),
+        }
+    }
+}
+
+impl ClipDamage {
+    fn update(&mut self, inner_commit: CommitCounter, uniforms: &[Uniform<'static>]) {
+        let committed = self.inner_commit != Some(inner_commit);
+        let restyled = self.uniforms != uniforms;
+        if !committed && !restyled {
+            return;
+        }
+
+        self.commit.increment();
+        self.inner_commit = Some(inner_commit);
+        if restyled {
+            self.uniforms = uniforms.to_vec();
+            self.uniforms_commit = self.commit;
+        }
+
+        self.history.push((self.commit, inner_commit));
+        if self.history.len() > HISTORY {
+            self.history.remove(0);
+        }
+    }
+
+    /// The surface commit that stood for `commit`, None if the uniforms changed since.
+    fn inner_commit_at(&self, commit: CommitCounter) -> Option<CommitCounter> {
+        if matches!(self.uniforms_commit.distance(Some(commit)), Some(distance) if distance > 0) {
+            return None;
+        }
+
+        self.history
+            .iter()
+            .find(|(own, _)| *own == commit)
+            .map(|(_, inner)| *inner)
+    }
+}
+
+/// A surface of a window masked to the rounded window geometry,
+/// optionally dimmed and desaturated while the window is inactive.
+#[derive(Debug)]
+pub struct ClippedSurfaceRenderElement<R: MondrianRenderer> {
+    inner: WaylandSurfaceRenderElement<R>,
+    program: GlesTexProgram,
+    // window geometry in the same space the surface is rendered in
+    geometry: Rectangle<f64, Logical>,
+    // none leaves the surface unclipped
+    corner_radius: Option<f32>,
+    scale: Scale<f64>,
+    dim: f32,
+    desaturate: f32,
+    damage: ClipDamage,
+}
+
+impl<R: MondrianRenderer> ClippedSurfaceRenderElement<R> {
+    pub fn new(
+        inner: WaylandSurfaceRenderElement<R>,
+        program: GlesTexProgram,
+        geometry: Rectangle<f64, Logical>,
+        corner_radius: Option<f32>,
+        scale: Scale<f64>,
+        (dim, desaturate): (f32, f32),
+        damage: &mut ClipDamage,
+    ) -> Self {
+        let mut element = Self {
+            inner,
+            program,
+            geometry,
+            corner_radius,
+            scale,
+            dim,
+            desaturate,
+            damage: ClipDamage::default(),
+        };
+
+        damage.update(element.inner.current_commit(), &element.uniforms());
+        element.damage = damage.clone();
+
+        element
+    }
+
+    pub fn complie_shaders(renderer: &mut GlesRenderer) {
+        let clipped_shader = renderer
+            .compile_custom_texture_shader(
+                include_str!("../render/shaders/clipped_surface.frag"),
+                &[
+                    UniformName::new("geo_offset", UniformType::_2f),
+                    UniformName::new("geo_scale", UniformType::_2f),
+                    UniformName::new("geo_size", UniformType::_2f),
+                    UniformName::new("corner_radius", UniformType::_1f),
+                    UniformName::new("clip", UniformType::_1f),
+                    UniformName::new("dim", UniformType::_1f),
+                    UniformName::new("desaturate", UniformType::_1f),
+                ],
+            )
+            .unwrap();
+
+        // Save texture shader in EGL rendering context.
+        renderer
+            .egl_context()
+            .user_data()
+            .insert_if_missing(|| ClippedSurfaceShader(clipped_shader));
+    }
+
+    pub fn shader(renderer: &mut R) -> GlesTexProgram {
+        renderer
+            .as_gles_renderer()
+            .egl_context()
+            .user_data()
+            .get::<ClippedSurfaceShader>()
+            .unwrap()
+            .0
+            .clone()
+    }
+
+    fn uniforms(&self) -> Vec<Uniform<'static>> {
+        // only relative positions matter, so this also holds when rescaled.
+        // assumes untransformed buffers that are not cropped by a viewport.
+        let element = self.inner.geometry(self.scale).to_f64();
+        let geometry = self.geometry.to_physical(self.scale);
+
+        let geo_scale = (
+            (element.size.w / geometry.size.w) as f32,
+            (element.size.h / geometry.size.h) as f32,
+        );
+        let geo_offset = (
+            ((element.loc.x - geometry.loc.x) / geometry.size.w) as f32,
+            ((element.loc.y - geometry.loc.y) / geometry.size.h) as f32,
+        );
+
+        vec![
+            Uniform::new("geo_offset", geo_offset),
+            Uniform::new("geo_scale", geo_scale),
+            Uniform::new("geo_size", (geometry.size.w as f32, geometry.size.h as f32)),
+            Uniform::new("corner_radius", self.corner_radius.unwrap_or(0.0) * self.scale.x as f32),
+            Uniform::new("clip", if self.corner_radius.is_some() { 1.0f32 } else { 0.0 }),
+            Uniform::new("dim", self.dim),
+            Uniform::new("desaturate", self.desaturate),
+        ]
+    }
+}
+
+impl<R: MondrianRenderer> Element for ClippedSurfaceRenderElement<R> {
+    fn id(&self) -> &Id {
+        // not the surface id, its commits count differently
+        &self.damage.id
+    }
+
+    fn current_commit(&self) -> CommitCounter {
+        self.damage.commit
+    }
+
+    fn geometry(&self, scale: Scale<f64>) -> Rectangle<i32, Physical> {
+        self.inner.geometry(scale)
+    }
+
+    fn transform(&self) -> Transform {
+        self.inner.transform()
+    }
+
+    fn src(&self) -> Rectangle<f64, Buffer> {
+        self.inner.src()
+    }
+
+    fn damage_since(&self, scale: Scale<f64>, commit: Option<CommitCounter>) -> DamageSet<i32, Physical> {
+        match commit.and_then(|commit| self.damage.inner_commit_at(commit)) {
+            Some(inner_commit) => self.inner.damage_since(scale, Some(inner_commit)),
+            // effects or clipping changed, the whole surface looks different
+            None => DamageSet::from_slice(&[Rectangle::from_size(self.geometry(scale).size)]),
+        }
+    }
+
+    fn opaque_regions(&self, scale: Scale<f64>) -> OpaqueRegions<i32, Physical> {
+        // the rounded corners are transparent
+        match self.corner_radius {
+            Some(_) => OpaqueRegions::default(),
+            None => self.inner.opaque_regions(scale),
+        }
+    }
+
+    fn alpha(&self) -> f32 {
+        self.inner.alpha()
+    }
+
+    fn kind(&self) -> Kind {
+        self.inner.kind()
+    }
+}
+
+impl RenderElement<GlesRenderer> for ClippedSurfaceRenderElement<GlesRenderer> {
+    fn draw(
+        &self,
+        frame: &mut GlesFrame<'_, '_>,
+        src: Rectangle<f64, Buffer>,
+        dst: Rectangle<i32, Physical>,
+        damage: &[Rectangle<i32, Physical>],
+        opaque_regions: &[Rectangle<i32, Physical>],
+    ) -> Result<(), GlesError> {
+        frame.override_default_tex_program(self.program.clone(), self.uniforms());
+        let result = RenderElement::<GlesRenderer>::draw(&self.inner, frame, src, dst, damage, opaque_regions);
+        frame.clear_tex_program_override();
+        result
+    }
+
+    fn underlying_storage(&self, _renderer: &mut GlesRenderer) -> Option<UnderlyingStorage> {
+        // the clipping cannot be done by scanout
+        None
+    }
+}
+
+impl<'render> RenderElement<TtyRenderer<'render>> for ClippedSurfaceRenderElement<TtyRenderer<'render>> {
+    fn draw(
+        &self,
+        frame: &mut TtyFrame<'render, '_, '_>,
+        src: Rectangle<f64, Buffer>,
+        dst: Rectangle<i32, Physical>,
+        damage: &[Rectangle<i32, Physical>],
+        opaque_regions: &[Rectangle<i32, Physical>],
+    ) -> Result<(), TtyRendererError<'render>> {
+        frame.as_gles_frame().override_default_tex_program(self.program.clone(), self.uniforms());
+        let result = RenderElement::<TtyRenderer<'render>>::draw(&self.inner, frame, src, dst, damage, opaque_regions);
+        frame.as_gles_frame().clear_tex_program_override();
+        result
+    }
+
+    fn underlying_storage(
+        &self,
+        _renderer: &mut TtyRenderer<'render>,
+    ) -> Option<UnderlyingStorage> {
+        // the clipping cannot be done by scanout
+        None
+    }
+}
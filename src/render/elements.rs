@@ -1,26 +1,33 @@
 use smithay::{
     backend::renderer::element::{
-        memory::MemoryRenderBufferRenderElement, surface::WaylandSurfaceRenderElement,
+        memory::MemoryRenderBufferRenderElement, solid::SolidColorRenderElement,
+        surface::WaylandSurfaceRenderElement, utils::RescaleRenderElement,
     },
     desktop::space::SpaceRenderElements,
 };
 
 use crate::niri_render_elements;
 
-use super::{background::BackgroundRenderElement, border::BorderRenderElement};
+use super::{background::BackgroundRenderElement, blur::BlurRenderElement, border::BorderRenderElement, clipped_surface::ClippedSurfaceRenderElement, shadow::ShadowRenderElement};
 
 niri_render_elements! {
     ShaderRenderElement => {
         Border=BorderRenderElement,
         Background=BackgroundRenderElement,
+        Shadow=ShadowRenderElement,
+        Blur=BlurRenderElement,
     }
 }
 
 niri_render_elements! {
     CustomRenderElements<R> => {
         Surface=WaylandSurfaceRenderElement<R>,
-        NamedPointer=MemoryRenderBufferRenderElement<R>,
+        Memory=MemoryRenderBufferRenderElement<R>,
         Shader=ShaderRenderElement,
+        Solid=SolidColorRenderElement,
+        Clipped=ClippedSurfaceRenderElement<R>,
+        Rescaled=RescaleRenderElement<WaylandSurfaceRenderElement<R>>,
+        RescaledClipped=RescaleRenderElement<ClippedSurfaceRenderElement<R>>,
     }
 }
 
@@ -6,10 +6,15 @@ use smithay::backend::renderer::{
 use crate::backend::tty::{TtyFrame, TtyRenderer};
 
 pub mod background;
+pub mod blur;
 pub mod border;
+pub mod clipped_surface;
 pub mod elements;
 pub mod render_elements;
 pub mod shader;
+pub mod shadow;
+pub mod snapshot;
+pub mod title_bar;
 
 /// Trait with our main renderer requirements to save on the typing.
 pub trait MondrianRenderer:
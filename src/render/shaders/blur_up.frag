@@ -0,0 +1,41 @@
+#version 100
+
+//_DEFINES_
+
+#if defined(EXTERNAL)
+#extension GL_OES_EGL_image_external : require
+#endif
+
+precision highp float;
+
+#if defined(EXTERNAL)
+uniform samplerExternalOES tex;
+#else
+uniform sampler2D tex;
+#endif
+
+uniform float alpha;
+varying vec2 v_coords;
+
+#if defined(DEBUG_FLAGS)
+uniform float tint;
+#endif
+
+// dual kawase upsample
+uniform vec2 half_pixel;
+uniform float offset;
+
+void main() {
+    vec2 uv = v_coords;
+
+    vec4 sum = texture2D(tex, uv + vec2(-half_pixel.x * 2.0, 0.0) * offset);
+    sum += texture2D(tex, uv + vec2(-half_pixel.x, half_pixel.y) * offset) * 2.0;
+    sum += texture2D(tex, uv + vec2(0.0, half_pixel.y * 2.0) * offset);
+    sum += texture2D(tex, uv + vec2(half_pixel.x, half_pixel.y) * offset) * 2.0;
+    sum += texture2D(tex, uv + vec2(half_pixel.x * 2.0, 0.0) * offset);
+    sum += texture2D(tex, uv + vec2(half_pixel.x, -half_pixel.y) * offset) * 2.0;
+    sum += texture2D(tex, uv + vec2(0.0, -half_pixel.y * 2.0) * offset);
+    sum += texture2D(tex, uv + vec2(-half_pixel.x, -half_pixel.y) * offset) * 2.0;
+
+    gl_FragColor = sum / 12.0 * alpha;
+}
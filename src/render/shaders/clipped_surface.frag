@@ -0,0 +1,86 @@
+#version 100
+
+//_DEFINES_
+
+#if defined(EXTERNAL)
+#extension GL_OES_EGL_image_external : require
+#endif
+
+precision highp float;
+
+#if defined(EXTERNAL)
+uniform samplerExternalOES tex;
+#else
+uniform sampler2D tex;
+#endif
+
+uniform float alpha;
+varying vec2 v_coords;
+
+#if defined(DEBUG_FLAGS)
+uniform float tint;
+#endif
+
+// maps texture coords to the window geometry, 0..1 inside of it
+uniform vec2 geo_offset;
+uniform vec2 geo_scale;
+uniform vec2 geo_size;
+uniform float corner_radius;
+// 1.0 cuts off everything outside of the window geometry
+uniform float clip;
+
+// inactive window effect, both 0..1
+uniform float dim;
+uniform float desaturate;
+
+float rounding_alpha(vec2 coords, vec2 size) {
+    vec2 center;
+
+    if (coords.x < corner_radius && coords.y < corner_radius) {
+        center = vec2(corner_radius);
+    } else if (size.x - corner_radius < coords.x && coords.y < corner_radius) {
+        center = vec2(size.x - corner_radius, corner_radius);
+    } else if (size.x - corner_radius < coords.x && size.y - corner_radius < coords.y) {
+        center = size - vec2(corner_radius);
+    } else if (coords.x < corner_radius && size.y - corner_radius < coords.y) {
+        center = vec2(corner_radius, size.y - corner_radius);
+    } else {
+        return 1.0;
+    }
+
+    float dist = distance(coords, center);
+    return 1.0 - smoothstep(corner_radius - 0.5, corner_radius + 0.5, dist);
+}
+
+void main() {
+    vec2 coords_geo = v_coords * geo_scale + geo_offset;
+
+    vec4 color = texture2D(tex, v_coords);
+
+#if defined(NO_ALPHA)
+    color = vec4(color.rgb, 1.0);
+#endif
+
+    if (clip == 1.0) {
+        if (coords_geo.x < 0.0 || 1.0 < coords_geo.x || coords_geo.y < 0.0 || 1.0 < coords_geo.y) {
+            // client side shadows and the like
+            color = vec4(0.0);
+        } else {
+            color = color * rounding_alpha(coords_geo * geo_size, geo_size);
+        }
+    }
+
+    // premultiplied, so the luminance keeps the alpha
+    float luminance = dot(color.rgb, vec3(0.2126, 0.7152, 0.0722));
+    color.rgb = mix(color.rgb, vec3(luminance), desaturate);
+    color.rgb = color.rgb * (1.0 - dim);
+
+    color = color * alpha;
+
+#if defined(DEBUG_FLAGS)
+    if (tint == 1.0)
+        color = vec4(0.0, 0.2, 0.0, 0.2) + color * 0.8;
+#endif
+
+    gl_FragColor = color;
+}
@@ -0,0 +1,36 @@
+precision mediump float;
+
+uniform float alpha;
+uniform vec2 u_resolution;
+uniform vec4 shadow_color;
+uniform float blur_radius;
+uniform float corner_radius;
+// window rect inside the area: x, y, w, h
+uniform vec4 window_rect;
+
+varying vec2 v_coords;
+
+// signed distance to a rounded box centered at the origin
+float rounded_box(vec2 p, vec2 half_size, float r) {
+    vec2 q = abs(p) - half_size + r;
+    return length(max(q, 0.0)) + min(max(q.x, q.y), 0.0) - r;
+}
+
+void main() {
+    vec2 coords = v_coords * u_resolution;
+
+    // nothing under the window itself, it may be translucent
+    vec2 window_half = window_rect.zw / 2.0;
+    if (rounded_box(coords - window_rect.xy - window_half, window_half, corner_radius) < 0.0) discard;
+
+    // the shadow box is the area shrunk by the blur radius
+    float blur = max(blur_radius, 0.5);
+    vec2 half_size = u_resolution / 2.0;
+    float dist = rounded_box(coords - half_size, half_size - blur, corner_radius);
+
+    float shadow = 1.0 - smoothstep(-blur, blur, dist);
+    if (shadow <= 0.0) discard;
+
+    float a = shadow_color.a * shadow * alpha;
+    gl_FragColor = vec4(shadow_color.rgb * a, a);
+}
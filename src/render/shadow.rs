@@ -0,0 +1,169 @@
+use smithay::{
+    backend::renderer::{
+        element::{Element, Id, Kind, RenderElement, UnderlyingStorage},
+        gles::{
+            GlesError, GlesFrame, GlesPixelProgram, GlesRenderer, Uniform, UniformName, UniformType,
+        },
+        utils::{CommitCounter, OpaqueRegions},
+    },
+    utils::{Buffer, Logical, Physical, Rectangle, Scale, Transform},
+};
+
+use crate::backend::tty::{TtyFrame, TtyRenderer, TtyRendererError};
+
+use super::AsGlesFrame;
+
+pub struct ShadowShader(pub GlesPixelProgram);
+
+/// Kept across frames per window, so an unchanged shadow produces no damage.
+#[derive(Debug, Clone)]
+pub struct ShadowRenderElement {
+    id: Id,
+    shader: GlesPixelProgram,
+    commit_counter: CommitCounter,
+    area: Rectangle<i32, Logical>,
+    alpha: f32,
+    additional_uniforms: Vec<Uniform<'static>>,
+    kind: Kind,
+}
+
+impl ShadowRenderElement {
+    pub fn new(
+        shader: GlesPixelProgram,
+        area: Rectangle<i32, Logical>,
+        alpha: f32,
+        additional_uniforms: Vec<Uniform<'_>>,
+        kind: Kind,
+    ) -> Self {
+        Self {
+            id: Id::new(),
+            shader,
+            commit_counter: CommitCounter::default(),
+            area,
+            alpha,
+            additional_uniforms: additional_uniforms
+                .into_iter()
+                .map(|u| u.into_owned())
+                .collect(),
+            kind,
+        }
+    }
+
+    pub fn complie_shaders(renderer: &mut GlesRenderer) {
+        let shadow_shader = renderer
+            .compile_custom_pixel_shader(
+                include_str!("../render/shaders/shadow.frag"),
+                &[
+                    UniformName::new("u_resolution", UniformType::_2f),
+                    UniformName::new("shadow_color", UniformType::_4f),
+                    UniformName::new("blur_radius", UniformType::_1f),
+                    UniformName::new("corner_radius", UniformType::_1f),
+                    UniformName::new("window_rect", UniformType::_4f),
+                ],
+            )
+            .unwrap();
+
+        // Save pixel shader in EGL rendering context.
+        renderer
+            .egl_context()
+            .user_data()
+            .insert_if_missing(|| ShadowShader(shadow_shader));
+    }
+
+    /// Move the shadow and replace its uniforms, only damaging if anything changed.
+    pub fn update(
+        &mut self,
+        area: Rectangle<i32, Logical>,
+        alpha: f32,
+        additional_uniforms: Vec<Uniform<'_>>,
+    ) {
+        let additional_uniforms: Vec<Uniform<'static>> = additional_uniforms
+            .into_iter()
+            .map(|u| u.into_owned())
+            .collect();
+
+        if self.area != area || self.alpha != alpha || self.additional_uniforms != additional_uniforms {
+            self.area = area;
+            self.alpha = alpha;
+            self.additional_uniforms = additional_uniforms;
+            self.commit_counter.increment();
+        }
+    }
+}
+
+impl Element for ShadowRenderElement {
+    fn id(&self) -> &Id {
+        &self.id
+    }
+
+    fn current_commit(&self) -> CommitCounter {
+        self.commit_counter
+    }
+
+    fn src(&self) -> Rectangle<f64, Buffer> {
+        Rectangle::from_size(self.area.size.to_f64().to_buffer(1.0, Transform::Normal))
+    }
+
+    fn geometry(&self, scale: Scale<f64>) -> Rectangle<i32, Physical> {
+        self.area.to_physical_precise_round(scale)
+    }
+
+    fn opaque_regions(&self, _scale: Scale<f64>) -> OpaqueRegions<i32, Physical> {
+        OpaqueRegions::default()
+    }
+
+    fn alpha(&self) -> f32 {
+        self.alpha
+    }
+
+    fn kind(&self) -> Kind {
+        self.kind
+    }
+}
+
+impl RenderElement<GlesRenderer> for ShadowRenderElement {
+    fn draw(
+        &self,
+        frame: &mut GlesFrame<'_, '_>,
+        src: Rectangle<f64, Buffer>,
+        dst: Rectangle<i32, Physical>,
+        damage: &[Rectangle<i32, Physical>],
+        _opaque_regions: &[Rectangle<i32, Physical>],
+    ) -> Result<(), GlesError> {
+        frame.render_pixel_shader_to(
+            &self.shader,
+            src,
+            dst,
+            self.area.size.to_buffer(1, Transform::Flipped180),
+            Some(damage),
+            self.alpha,
+            &self.additional_uniforms,
+        )
+    }
+
+    fn underlying_storage(&self, _renderer: &mut GlesRenderer) -> Option<UnderlyingStorage> {
+        None
+    }
+}
+
+impl<'render> RenderElement<TtyRenderer<'render>> for ShadowRenderElement {
+    fn draw(
+        &self,
+        frame: &mut TtyFrame<'_, '_, '_>,
+        src: Rectangle<f64, Buffer>,
+        dst: Rectangle<i32, Physical>,
+        damage: &[Rectangle<i32, Physical>],
+        opaque_regions: &[Rectangle<i32, Physical>],
+    ) -> Result<(), TtyRendererError<'_>> {
+        let frame = frame.as_gles_frame();
+        RenderElement::<GlesRenderer>::draw(&self, frame, src, dst, damage, opaque_regions)?;
+        Ok(())
+    }
+
+    fn underlying_storage(
+        &self,
+        _renderer: &mut TtyRenderer<'render>,
+    ) -> Option<UnderlyingStorage> {
+        None
+    }
+}
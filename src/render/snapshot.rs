@@ -0,0 +1,108 @@
+use smithay::{
+    backend::{
+        allocator::Fourcc,
+        renderer::{
+            element::{
+                memory::MemoryRenderBuffer, surface::WaylandSurfaceRenderElement, AsRenderElements, Element, RenderElement
+            },
+            gles::{GlesError, GlesRenderer, GlesTarget, GlesTexture},
+            sync::SyncPoint, Bind, Color32F, ExportMem, Frame, Offscreen, Renderer,
+        },
+    },
+    desktop::Window,
+    utils::{Buffer, Physical, Point, Rectangle, Scale, Size, Transform},
+};
+
+/// Draw `elements`, front to back, into `target` with `origin` at its top left.
+pub fn draw_elements<E: RenderElement<GlesRenderer>>(
+    renderer: &mut GlesRenderer,
+    target: &mut GlesTarget<'_>,
+    size: Size<i32, Physical>,
+    scale: f64,
+    origin: Point<i32, Physical>,
+    elements: &[E],
+    clear_color: Color32F,
+) -> Result<SyncPoint, GlesError> {
+    let target_rect = Rectangle::from_size(size);
+
+    let mut frame = renderer.render(target, size, Transform::Normal)?;
+    frame.clear(clear_color, &[target_rect])?;
+
+    for element in elements.iter().rev() {
+        let mut dst = element.geometry(Scale::from(scale));
+        dst.loc -= origin;
+        if !dst.overlaps(target_rect) {
+            continue;
+        }
+        element.draw(&mut frame, element.src(), dst, &[Rectangle::from_size(dst.size)], &[])?;
+    }
+
+    frame.finish()
+}
+
+/// Render `region` of the composited `elements` and read it back in `format`.
+pub fn capture_elements<E: RenderElement<GlesRenderer>>(
+    renderer: &mut GlesRenderer,
+    elements: &[E],
+    region: Rectangle<i32, Physical>,
+    scale: f64,
+    format: Fourcc,
+) -> anyhow::Result<Vec<u8>> {
+    let _span = tracy_client::span!("capture_elements");
+
+    if region.is_empty() {
+        anyhow::bail!("nothing to capture in an empty region");
+    }
+    let buffer_size: Size<i32, Buffer> = (region.size.w, region.size.h).into();
+
+    let mut texture: GlesTexture = renderer.create_buffer(Fourcc::Abgr8888, buffer_size)?;
+    let mut target = renderer.bind(&mut texture)?;
+    draw_elements(renderer, &mut target, region.size, scale, region.loc, elements, Color32F::new(0.0, 0.0, 0.0, 1.0))?;
+
+    let mapping = renderer.copy_framebuffer(&target, Rectangle::from_size(buffer_size), format)?;
+    Ok(renderer.map_texture(&mapping)?.to_vec())
+}
+
+/// Render the current content of `window` alone and read it back as Abgr8888.
+pub fn capture_window(renderer: &mut GlesRenderer, window: &Window, scale: f64) -> anyhow::Result<(Size<i32, Buffer>, Vec<u8>)> {
+    let geometry = window.geometry();
+    let size: Size<i32, Physical> = geometry.size.to_physical_precise_round(scale);
+    if size.is_empty() {
+        anyhow::bail!("window has no content to snapshot");
+    }
+    let buffer_size: Size<i32, Buffer> = (size.w, size.h).into();
+
+    let elements = window.render_elements::<WaylandSurfaceRenderElement<GlesRenderer>>(
+        renderer,
+        (-geometry.loc).to_physical_precise_round(scale),
+        Scale::from(scale),
+        1.0,
+    );
+    if elements.is_empty() {
+        anyhow::bail!("window has no buffer attached");
+    }
+
+    let mut texture: GlesTexture = renderer.create_buffer(Fourcc::Abgr8888, buffer_size)?;
+    let mut target = renderer.bind(&mut texture)?;
+    draw_elements(renderer, &mut target, size, scale, Point::default(), &elements, Color32F::TRANSPARENT)?;
+
+    let mapping = renderer.copy_framebuffer(&target, Rectangle::from_size(buffer_size), Fourcc::Abgr8888)?;
+    Ok((buffer_size, renderer.map_texture(&mapping)?.to_vec()))
+}
+
+/// Render the current content of `window` into a cpu side buffer,
+/// which stays valid after the client has destroyed its surfaces.
+pub fn snapshot_window(renderer: &mut GlesRenderer, window: &Window, scale: f64) -> anyhow::Result<MemoryRenderBuffer> {
+    let _span = tracy_client::span!("snapshot_window");
+
+    let (buffer_size, data) = capture_window(renderer, window, scale)?;
+
+    Ok(MemoryRenderBuffer::from_slice(
+        &data,
+        Fourcc::Abgr8888,
+        buffer_size,
+        1,
+        Transform::Normal,
+        None,
+    ))
+}
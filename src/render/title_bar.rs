@@ -0,0 +1,245 @@
+use std::fs;
+
+use ab_glyph::{point, Font, FontArc, Glyph, PxScale, ScaleFont};
+use smithay::{
+    backend::{
+        allocator::Fourcc,
+        renderer::element::{memory::MemoryRenderBuffer, solid::SolidColorBuffer},
+    },
+    utils::{Logical, Point, Rectangle, Size, Transform},
+};
+
+use crate::config::decoration::TitleBarConfigs;
+
+// left of the text and around the buttons
+const TEXT_PADDING: i32 = 8;
+const BUTTON_MARGIN: i32 = 5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TitleBarButton {
+    Close,
+    Float,
+}
+
+/// The top of a window rect that its title bar covers.
+pub fn title_bar_rect(rect: Rectangle<i32, Logical>, height: i32) -> Rectangle<i32, Logical> {
+    Rectangle::new(rect.loc, (rect.size.w, height.min(rect.size.h)).into())
+}
+
+/// Square buttons at the right end of the bar, close outermost.
+pub fn button_rects(bar: Rectangle<i32, Logical>) -> [(TitleBarButton, Rectangle<i32, Logical>); 2] {
+    let size = (bar.size.h - BUTTON_MARGIN * 2).max(0);
+
+    let close = Rectangle::new(
+        (bar.loc.x + bar.size.w - BUTTON_MARGIN - size, bar.loc.y + BUTTON_MARGIN).into(),
+        (size, size).into(),
+    );
+    let mut float = close;
+    float.loc.x -= size + BUTTON_MARGIN;
+
+    [(TitleBarButton::Close, close), (TitleBarButton::Float, float)]
+}
+
+pub fn button_under(bar: Rectangle<i32, Logical>, location: Point<f64, Logical>) -> Option<TitleBarButton> {
+    button_rects(bar)
+        .into_iter()
+        .find(|(_, rect)| rect.to_f64().contains(location))
+        .map(|(button, _)| button)
+}
+
+pub fn load_font(path: &str) -> Option<FontArc> {
+    let data = match fs::read(path) {
+        Ok(data) => data,
+        Err(err) => {
+            warn!("cannot read title bar font {}: {:?}", path, err);
+            return None;
+        }
+    };
+
+    match FontArc::try_from_vec(data) {
+        Ok(font) => Some(font),
+        Err(err) => {
+            warn!("cannot parse title bar font {}: {:?}", path, err);
+            None
+        }
+    }
+}
+
+/// Background, buttons and text of one title bar, kept across frames so the text is only
+/// rasterized again when it changes.
+pub struct TitleBar {
+    pub background: SolidColorBuffer,
+    pub close: SolidColorBuffer,
+    pub float: SolidColorBuffer,
+    // tabs of tabbed and stacked containers have none
+    buttons: bool,
+
+    text: Option<(MemoryRenderBuffer, Size<i32, Logical>)>,
+    // text, focus, width and scale the buffer was rasterized for
+    rendered: Option<(String, bool, i32, i32)>,
+}
+
+impl TitleBar {
+    pub fn new() -> Self {
+        Self {
+            background: SolidColorBuffer::default(),
+            close: SolidColorBuffer::default(),
+            float: SolidColorBuffer::default(),
+            buttons: true,
+            text: None,
+            rendered: None,
+        }
+    }
+
+    /// A bar without buttons, for the tabs of a container.
+    pub fn tab() -> Self {
+        Self {
+            buttons: false,
+            ..Self::new()
+        }
+    }
+
+    pub fn update(
+        &mut self,
+        configs: &TitleBarConfigs,
+        font: Option<&FontArc>,
+        text: &str,
+        focused: bool,
+        bar: Rectangle<i32, Logical>,
+        scale: i32,
+    ) {
+        let background = if focused { configs.active } else { configs.inactive };
+        self.background.update(bar.size, background);
+
+        // the text ends before the buttons
+        let text_end = if self.buttons {
+            let [(_, close), (_, float)] = button_rects(bar);
+            self.close.update(close.size, configs.close);
+            self.float.update(float.size, configs.float);
+
+            float.loc.x
+        } else {
+            bar.loc.x + bar.size.w
+        };
+        let max_width = (text_end - bar.loc.x - TEXT_PADDING * 2).max(0);
+        let key = (text.to_string(), focused, max_width, scale);
+        if self.rendered.as_ref() == Some(&key) {
+            return;
+        }
+
+        let color = if focused { configs.text_active } else { configs.text_inactive };
+        self.text = font.and_then(|font| {
+            render_text(font, text, configs.font_size * scale as f32, color, max_width * scale, scale)
+        });
+        self.rendered = Some(key);
+    }
+
+    pub fn text(&self) -> Option<&MemoryRenderBuffer> {
+        self.text.as_ref().map(|(buffer, _)| buffer)
+    }
+
+    /// Where the text sits, vertically centered after the left padding.
+    pub fn text_location(&self, bar: Rectangle<i32, Logical>) -> Point<i32, Logical> {
+        let height = self.text
+            .as_ref()
+            .map(|(_, size)| size.h)
+            .unwrap_or(0);
+
+        (bar.loc.x + TEXT_PADDING, bar.loc.y + (bar.size.h - height) / 2).into()
+    }
+}
+
+/// Lay out `text` on one line, returning the glyphs and the width in pixels.
+fn layout_line(font: &FontArc, text: &str, scale: PxScale) -> (Vec<Glyph>, f32) {
+    let scaled = font.as_scaled(scale);
+
+    let mut glyphs = Vec::new();
+    let mut caret = 0.0;
+    let mut last = None;
+
+    for c in text.chars().filter(|c| !c.is_control()) {
+        let id = scaled.glyph_id(c);
+        if let Some(last) = last {
+            caret += scaled.kern(last, id);
+        }
+
+        glyphs.push(id.with_scale_and_position(scale, point(caret, scaled.ascent())));
+        caret += scaled.h_advance(id);
+        last = Some(id);
+    }
+
+    (glyphs, caret)
+}
+
+/// Rasterize one line of text into a premultiplied buffer, ellipsized to `max_width` pixels.
+fn render_text(
+    font: &FontArc,
+    text: &str,
+    size: f32,
+    color: [f32; 4],
+    max_width: i32,
+    scale: i32,
+) -> Option<(MemoryRenderBuffer, Size<i32, Logical>)> {
+    let _span = tracy_client::span!("render_title_text");
+
+    let px_scale = PxScale::from(size);
+    let (mut glyphs, mut width) = layout_line(font, text, px_scale);
+
+    // drop characters until the line and the ellipsis fit
+    if width > max_width as f32 {
+        let mut chars: Vec<char> = text.chars().collect();
+        loop {
+            chars.pop();
+            let line = format!("{}…", chars.iter().collect::<String>().trim_end());
+            (glyphs, width) = layout_line(font, &line, px_scale);
+
+            if width <= max_width as f32 || chars.is_empty() {
+                break;
+            }
+        }
+    }
+
+    let scaled = font.as_scaled(px_scale);
+    let (w, h) = (width.ceil() as i32, (scaled.ascent() - scaled.descent()).ceil() as i32);
+    if w <= 0 || h <= 0 || w > max_width {
+        return None;
+    }
+
+    let mut pixels = vec![0u8; (w * h * 4) as usize];
+    for glyph in glyphs {
+        let Some(outlined) = font.outline_glyph(glyph) else {
+            continue;
+        };
+        let bounds = outlined.px_bounds();
+
+        outlined.draw(|x, y, coverage| {
+            let px = bounds.min.x as i32 + x as i32;
+            let py = bounds.min.y as i32 + y as i32;
+            if px < 0 || py < 0 || px >= w || py >= h {
+                return;
+            }
+
+            // Argb8888 is stored as b, g, r, a
+            let idx = ((py * w + px) * 4) as usize;
+            let alpha = coverage.clamp(0.0, 1.0) * color[3];
+            if alpha * 255.0 <= pixels[idx + 3] as f32 {
+                return;
+            }
+            pixels[idx] = (color[2] * alpha * 255.0) as u8;
+            pixels[idx + 1] = (color[1] * alpha * 255.0) as u8;
+            pixels[idx + 2] = (color[0] * alpha * 255.0) as u8;
+            pixels[idx + 3] = (alpha * 255.0) as u8;
+        });
+    }
+
+    let buffer = MemoryRenderBuffer::from_slice(
+        &pixels,
+        Fourcc::Argb8888,
+        (w, h),
+        scale,
+        Transform::Normal,
+        None,
+    );
+
+    Some((buffer, (w / scale, h / scale).into()))
+}
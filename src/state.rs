@@ -12,8 +12,9 @@ use smithay::{
 };
 
 use smithay::{
-    backend::allocator::dmabuf::Dmabuf, delegate_data_device, delegate_dmabuf, delegate_drm_syncobj, delegate_output, delegate_shm, delegate_viewporter, desktop::PopupManager, reexports::{
+    backend::allocator::dmabuf::Dmabuf, delegate_data_device, delegate_dmabuf, delegate_drm_syncobj, delegate_output, delegate_shm, delegate_viewporter, desktop::{PopupManager, Window}, reexports::{
         calloop::LoopHandle,
+        wayland_protocols_misc::server_decoration::server::org_kde_kwin_server_decoration_manager::Mode as KdeMode,
         wayland_server::{
             backend::ClientData, protocol::{wl_buffer, wl_shm}, DisplayHandle,
         },
@@ -33,12 +34,12 @@ use smithay::{
                 ClientDndGrabHandler, DataDeviceHandler, DataDeviceState, ServerDndGrabHandler
             }, 
             primary_selection::PrimarySelectionState, SelectionHandler
-        }, shell::{wlr_layer::WlrLayerShellState, xdg::XdgShellState}, shm::{ShmHandler, ShmState}, socket::ListeningSocketSource, viewporter::ViewporterState
+        }, shell::{kde::decoration::KdeDecorationState, wlr_layer::WlrLayerShellState, xdg::{decoration::XdgDecorationState, XdgShellState}}, shm::{ShmHandler, ShmState}, socket::ListeningSocketSource, viewporter::ViewporterState, xdg_activation::XdgActivationState
     }
 };
 
 use crate::{
-    backend::Backend, config::Configs, manager::{
+    backend::Backend, config::Configs, protocol::{ext_workspace::ExtWorkspaceState, FullscreenSurface, foreign_toplevel::WlrForeignToplevelState, image_capture::ImageCaptureState, screencopy::ScreencopyManagerState}, manager::{
         animation::AnimationManager, cursor::CursorManager, input::InputManager, output::OutputManager, render::RenderManager, window::WindowManager, workspace::{WorkspaceId, WorkspaceManager}
     }
 };
@@ -133,8 +134,8 @@ impl GlobalData {
                 configs.conf_keybindings.clone()
             )
             .context("Failed to create input_manager")?;
-        let render_manager = RenderManager::new();
-        let animation_manager = AnimationManager::new();
+        let render_manager = RenderManager::new(configs.conf_decoration.clone(), configs.conf_background.clone(), &loop_handle);
+        let animation_manager = AnimationManager::new(configs.conf_animations.clone());
     
         let popups = PopupManager::default();
         let cursor_manager = CursorManager::new("default", 24);
@@ -188,9 +189,37 @@ impl GlobalData {
         })
     }
 
+    /// Windows the next frame draws, the only ones whose animations advance.
+    fn drawn_windows(&self) -> Vec<Window> {
+        let fullscreen = self.output_manager
+            .current_output()
+            .user_data()
+            .get::<FullscreenSurface>()
+            .and_then(|fullscreen| fullscreen.get());
+
+        if fullscreen.is_some() {
+            return Vec::new();
+        }
+
+        self.workspace_manager.drawn_windows(&self.window_manager)
+    }
+
     pub fn refresh(&mut self) {
         // TODO: release death data
-        self.animation_manager.refresh();
+        let drawn = self.drawn_windows();
+        self.animation_manager.refresh(&drawn);
+
+        // captures of windows or outputs that are gone
+        self.refresh_image_captures();
+
+        // taskbars follow titles, focus and states
+        self.refresh_foreign_toplevels();
+        self.refresh_ext_workspaces();
+
+        // keep drawing while something moves on its own
+        if self.animation_manager.is_running() || self.render_manager.is_animated() {
+            self.queue_redraw();
+        }
     }
 }
 
@@ -219,6 +248,15 @@ pub struct State {
     #[allow(dead_code)]
     pub viewporter_state: ViewporterState,
     pub foreign_toplevel_state: ForeignToplevelListState,
+    pub wlr_foreign_toplevel_state: WlrForeignToplevelState,
+    #[allow(dead_code)]
+    pub screencopy_state: ScreencopyManagerState,
+    pub image_capture_state: ImageCaptureState,
+    pub ext_workspace_state: ExtWorkspaceState,
+    pub xdg_activation_state: XdgActivationState,
+    #[allow(dead_code)]
+    pub xdg_decoration_state: XdgDecorationState,
+    pub kde_decoration_state: KdeDecorationState,
 }
 
 impl State {
@@ -246,6 +284,14 @@ impl State {
         let layer_shell_state = WlrLayerShellState::new::<GlobalData>(display_handle);
         let viewporter_state = ViewporterState::new::<GlobalData>(display_handle);
         let foreign_toplevel_state = ForeignToplevelListState::new::<GlobalData>(display_handle);
+        let wlr_foreign_toplevel_state = WlrForeignToplevelState::new(display_handle);
+        let screencopy_state = ScreencopyManagerState::new(display_handle);
+        let image_capture_state = ImageCaptureState::new(display_handle);
+        let ext_workspace_state = ExtWorkspaceState::new(display_handle);
+        let xdg_activation_state = XdgActivationState::new::<GlobalData>(display_handle);
+        let xdg_decoration_state = XdgDecorationState::new::<GlobalData>(display_handle);
+        // the mode each surface gets is picked per app_id when it asks
+        let kde_decoration_state = KdeDecorationState::new::<GlobalData>(display_handle, KdeMode::Server);
 
         Ok(State {
             compositor_state,
@@ -264,6 +310,13 @@ impl State {
             layer_shell_state,
             viewporter_state,
             foreign_toplevel_state,
+            wlr_foreign_toplevel_state,
+            screencopy_state,
+            image_capture_state,
+            ext_workspace_state,
+            xdg_activation_state,
+            xdg_decoration_state,
+            kde_decoration_state,
         })
     }
 }